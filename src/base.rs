@@ -11,6 +11,27 @@ impl default::Default for Handle {
     fn default() -> Handle { Handle(ptr::null_mut()) }
 }
 
+impl Handle {
+    pub const fn null() -> Handle {
+        Handle(ptr::null_mut())
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.0.is_null()
+    }
+}
+
+#[cfg(feature = "mock")]
+impl Handle {
+    pub(crate) fn new(handle: *mut CVoid) -> Handle {
+        Handle(handle)
+    }
+
+    pub(crate) fn as_ptr(&self) -> *mut CVoid {
+        self.0
+    }
+}
+
 #[derive(Debug)]
 pub struct Handles(*const Handle, usize);
 
@@ -20,10 +41,10 @@ impl Handles {
     }
 }
 
-#[cfg(target_os = "efi")]
+#[cfg(any(target_os = "efi", target_os = "uefi", feature = "mock"))]
 impl ::core::ops::Drop for Handles {
 	fn drop(&mut self) {
-        let bs = systemtable::get_system_table().boot_services();
+        let bs = ::get_system_table().boot_services();
         bs.free_pool(self.0);
     }
 }
@@ -67,50 +88,147 @@ impl<'a> ::core::iter::ExactSizeIterator for HandlesIterator<'a> {
 #[repr(C)]
 pub struct Event(pub *mut CVoid);
 
-#[cfg(target_pointer_width = "32")]
-const ERR_FLAG: u32 = 1 << 31;
+const ERR_FLAG: usize = !(usize::MAX >> 1);
+const OEM_FLAG: usize = ERR_FLAG >> 1;
 
-#[cfg(target_pointer_width = "64")]
-const ERR_FLAG: u64 = 1 << 63;
+/// Type for EFI_STATUS. Firmware may return codes beyond those defined here, such as OEM
+/// codes, so this is an integer rather than an enum. Errors have the high bit set; other
+/// non-zero codes are warnings.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+#[repr(transparent)]
+pub struct Status(pub usize);
 
-/// Type for EFI_STATUS
-#[cfg_attr(target_pointer_width = "32", repr(u32))]
-#[cfg_attr(target_pointer_width = "64", repr(u64))]
-#[derive(PartialEq, PartialOrd, Debug, Clone, Copy)]
-pub enum Status {
-    Success = 0,
-    LoadError = 1 | ERR_FLAG,
-    InvalidParameter = 2 | ERR_FLAG,
-    Unsupported = 3 | ERR_FLAG,
-    BadBufferSize = 4 | ERR_FLAG,
-    BufferTooSmall = 5 | ERR_FLAG,
-    NotReady = 6 | ERR_FLAG,
-    DeviceError = 7 | ERR_FLAG,
-    WriteProtected = 8 | ERR_FLAG,
-    OutOfResources = 9 | ERR_FLAG,
-    VolumeCorrupted = 10 | ERR_FLAG,
-    VolumeFull = 11 | ERR_FLAG,
-    NoMedia = 12 | ERR_FLAG,
-    MediaChanged = 13 | ERR_FLAG,
-    NotFound = 14 | ERR_FLAG,
-    AccessDenied = 15 | ERR_FLAG,
-    NoResponse = 16 | ERR_FLAG,
-    NoMapping = 17 | ERR_FLAG,
-    Timeout = 18 | ERR_FLAG,
-    NotStarted = 19 | ERR_FLAG,
-    AlreadyStarted = 20 | ERR_FLAG,
-    Aborted = 21 | ERR_FLAG,
-    IcmpError = 22 | ERR_FLAG,
-    TftpError = 23 | ERR_FLAG,
-    ProtocolError = 24 | ERR_FLAG,
-    IncompatibleVersion = 25 | ERR_FLAG,
-    SecurityViolation = 26 | ERR_FLAG,
-    CrcError = 27 | ERR_FLAG,
-    EndOfMedia = 28 | ERR_FLAG,
-    EndOfFile = 31 | ERR_FLAG,
+/// Shorthand for results whose errors are `Status`.
+pub type Result<T, E = Status> = ::core::result::Result<T, E>;
+
+#[allow(non_upper_case_globals)]
+impl Status {
+    pub const Success: Status = Status(0);
+    pub const LoadError: Status = Status(1 | ERR_FLAG);
+    pub const InvalidParameter: Status = Status(2 | ERR_FLAG);
+    pub const Unsupported: Status = Status(3 | ERR_FLAG);
+    pub const BadBufferSize: Status = Status(4 | ERR_FLAG);
+    pub const BufferTooSmall: Status = Status(5 | ERR_FLAG);
+    pub const NotReady: Status = Status(6 | ERR_FLAG);
+    pub const DeviceError: Status = Status(7 | ERR_FLAG);
+    pub const WriteProtected: Status = Status(8 | ERR_FLAG);
+    pub const OutOfResources: Status = Status(9 | ERR_FLAG);
+    pub const VolumeCorrupted: Status = Status(10 | ERR_FLAG);
+    pub const VolumeFull: Status = Status(11 | ERR_FLAG);
+    pub const NoMedia: Status = Status(12 | ERR_FLAG);
+    pub const MediaChanged: Status = Status(13 | ERR_FLAG);
+    pub const NotFound: Status = Status(14 | ERR_FLAG);
+    pub const AccessDenied: Status = Status(15 | ERR_FLAG);
+    pub const NoResponse: Status = Status(16 | ERR_FLAG);
+    pub const NoMapping: Status = Status(17 | ERR_FLAG);
+    pub const Timeout: Status = Status(18 | ERR_FLAG);
+    pub const NotStarted: Status = Status(19 | ERR_FLAG);
+    pub const AlreadyStarted: Status = Status(20 | ERR_FLAG);
+    pub const Aborted: Status = Status(21 | ERR_FLAG);
+    pub const IcmpError: Status = Status(22 | ERR_FLAG);
+    pub const TftpError: Status = Status(23 | ERR_FLAG);
+    pub const ProtocolError: Status = Status(24 | ERR_FLAG);
+    pub const IncompatibleVersion: Status = Status(25 | ERR_FLAG);
+    pub const SecurityViolation: Status = Status(26 | ERR_FLAG);
+    pub const CrcError: Status = Status(27 | ERR_FLAG);
+    pub const EndOfMedia: Status = Status(28 | ERR_FLAG);
+    pub const EndOfFile: Status = Status(31 | ERR_FLAG);
+    pub const InvalidLanguage: Status = Status(32 | ERR_FLAG);
+    pub const CompromisedData: Status = Status(33 | ERR_FLAG);
+    pub const IpAddressConflict: Status = Status(34 | ERR_FLAG);
+    pub const HttpError: Status = Status(35 | ERR_FLAG);
+
+    pub const WarnUnknownGlyph: Status = Status(1);
+    pub const WarnDeleteFailure: Status = Status(2);
+    pub const WarnWriteFailure: Status = Status(3);
+    pub const WarnBufferTooSmall: Status = Status(4);
+    pub const WarnStaleData: Status = Status(5);
+    pub const WarnFileSystem: Status = Status(6);
+    pub const WarnResetRequired: Status = Status(7);
 }
 
 impl Status {
+    pub fn is_success(&self) -> bool {
+        *self == Status::Success
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.0 & ERR_FLAG != 0
+    }
+
+    pub fn is_warning(&self) -> bool {
+        !self.is_success() && !self.is_error()
+    }
+
+    /// Whether this is an error or warning code reserved for OEMs.
+    pub fn is_oem(&self) -> bool {
+        self.0 & OEM_FLAG != 0
+    }
+
+    /// `Ok(())` for `Status::Success`, and `Err(self)` for anything else, including warnings.
+    pub fn to_result(self) -> Result<()> {
+        self.to_result_with(())
+    }
+
+    /// `Ok(value)` for `Status::Success`, and `Err(self)` for anything else, including warnings.
+    pub fn to_result_with<T>(self, value: T) -> Result<T> {
+        if self.is_success() {
+            Ok(value)
+        } else {
+            Err(self)
+        }
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        let name = match *self {
+            Status::Success => "Success",
+            Status::LoadError => "LoadError",
+            Status::InvalidParameter => "InvalidParameter",
+            Status::Unsupported => "Unsupported",
+            Status::BadBufferSize => "BadBufferSize",
+            Status::BufferTooSmall => "BufferTooSmall",
+            Status::NotReady => "NotReady",
+            Status::DeviceError => "DeviceError",
+            Status::WriteProtected => "WriteProtected",
+            Status::OutOfResources => "OutOfResources",
+            Status::VolumeCorrupted => "VolumeCorrupted",
+            Status::VolumeFull => "VolumeFull",
+            Status::NoMedia => "NoMedia",
+            Status::MediaChanged => "MediaChanged",
+            Status::NotFound => "NotFound",
+            Status::AccessDenied => "AccessDenied",
+            Status::NoResponse => "NoResponse",
+            Status::NoMapping => "NoMapping",
+            Status::Timeout => "Timeout",
+            Status::NotStarted => "NotStarted",
+            Status::AlreadyStarted => "AlreadyStarted",
+            Status::Aborted => "Aborted",
+            Status::IcmpError => "IcmpError",
+            Status::TftpError => "TftpError",
+            Status::ProtocolError => "ProtocolError",
+            Status::IncompatibleVersion => "IncompatibleVersion",
+            Status::SecurityViolation => "SecurityViolation",
+            Status::CrcError => "CrcError",
+            Status::EndOfMedia => "EndOfMedia",
+            Status::EndOfFile => "EndOfFile",
+            Status::InvalidLanguage => "InvalidLanguage",
+            Status::CompromisedData => "CompromisedData",
+            Status::IpAddressConflict => "IpAddressConflict",
+            Status::HttpError => "HttpError",
+            Status::WarnUnknownGlyph => "WarnUnknownGlyph",
+            Status::WarnDeleteFailure => "WarnDeleteFailure",
+            Status::WarnWriteFailure => "WarnWriteFailure",
+            Status::WarnBufferTooSmall => "WarnBufferTooSmall",
+            Status::WarnStaleData => "WarnStaleData",
+            Status::WarnFileSystem => "WarnFileSystem",
+            Status::WarnResetRequired => "WarnResetRequired",
+            _ => return None,
+        };
+        Some(name)
+    }
+
+    /// A description of the status, or of its kind if it isn't one defined by the
+    /// specification.
     pub fn str(&self) -> &'static str {
         match *self {
             Status::Success => "success",
@@ -143,13 +261,40 @@ impl Status {
             Status::CrcError => "CRC error",
             Status::EndOfMedia => "end of media",
             Status::EndOfFile => "end of file",
+            Status::InvalidLanguage => "invalid language",
+            Status::CompromisedData => "compromised data",
+            Status::IpAddressConflict => "IP address conflict",
+            Status::HttpError => "HTTP error",
+            Status::WarnUnknownGlyph => "warning: unknown glyph",
+            Status::WarnDeleteFailure => "warning: delete failure",
+            Status::WarnWriteFailure => "warning: write failure",
+            Status::WarnBufferTooSmall => "warning: buffer too small",
+            Status::WarnStaleData => "warning: stale data",
+            Status::WarnFileSystem => "warning: file system",
+            Status::WarnResetRequired => "warning: reset required",
+            _ if self.is_oem() && self.is_error() => "OEM error",
+            _ if self.is_oem() => "OEM warning",
+            _ if self.is_error() => "unknown error",
+            _ => "unknown warning",
+        }
+    }
+}
+
+impl fmt::Debug for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name() {
+            Some(name) => f.write_str(name),
+            None => write!(f, "Status({:#x})", self.0),
         }
     }
 }
 
 impl fmt::Display for Status {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.str())
+        match self.name() {
+            Some(_) => write!(f, "{}", self.str()),
+            None => write!(f, "{} {:#x}", self.str(), self.0 & !(ERR_FLAG | OEM_FLAG)),
+        }
     }
 }
 
@@ -178,6 +323,54 @@ pub enum MemoryType {
     PalCode = 13,
 }
 
+bitflags! {
+    /// Attributes of a region in the memory map.
+    pub struct MemoryAttribute: u64 {
+        const MEMORY_UC = 0x0000000000000001;
+        const MEMORY_WC = 0x0000000000000002;
+        const MEMORY_WT = 0x0000000000000004;
+        const MEMORY_WB = 0x0000000000000008;
+        const MEMORY_UCE = 0x0000000000000010;
+        const MEMORY_WP = 0x0000000000001000;
+        const MEMORY_RP = 0x0000000000002000;
+        const MEMORY_XP = 0x0000000000004000;
+        const MEMORY_NV = 0x0000000000008000;
+        const MEMORY_MORE_RELIABLE = 0x0000000000010000;
+        const MEMORY_RO = 0x0000000000020000;
+        /// The region must be given a virtual mapping by `set_virtual_address_map`.
+        const MEMORY_RUNTIME = 0x8000000000000000;
+    }
+}
+
+/// Type for EFI_MEMORY_DESCRIPTOR, one entry in the memory map.
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct MemoryDescriptor {
+    /// The region's `MemoryType`, kept as an integer since firmware may use OEM types.
+    pub type_: u32,
+    pub physical_start: u64,
+    /// Filled in by the OS loader before calling `set_virtual_address_map`.
+    pub virtual_start: u64,
+    /// Size of the region in 4KiB pages.
+    pub number_of_pages: u64,
+    pub attribute: u64,
+}
+
+impl MemoryDescriptor {
+    pub fn attributes(&self) -> MemoryAttribute {
+        MemoryAttribute::from_bits_truncate(self.attribute)
+    }
+}
+
+/// `Time::timezone` value for a time which isn't tied to a timezone; it is local time.
+pub const UNSPECIFIED_TIMEZONE: i16 = 0x07FF;
+
+/// `Time::daylight` bit set if the time is affected by daylight savings time.
+pub const TIME_ADJUST_DAYLIGHT: u8 = 0x01;
+
+/// `Time::daylight` bit set if the time has been adjusted for daylight savings time.
+pub const TIME_IN_DAYLIGHT: u8 = 0x02;
+
 /// UEFI Time structure.
 #[derive(Copy, Clone, Debug, Default)]
 #[repr(C)]
@@ -208,20 +401,122 @@ pub struct Time {
     /// Timezone [-1440 - 1440] or 2047 for "unspecified timezone"
     pub timezone: i16,
 
-    daylight: u8,
+    /// Daylight savings time bits; see `TIME_ADJUST_DAYLIGHT` and `TIME_IN_DAYLIGHT`.
+    pub daylight: u8,
     __pad2: u8,
 }
 
+fn is_leap_year(year: u16) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 => if is_leap_year(year) { 29 } else { 28 },
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl Time {
+    /// Create a local time with an unspecified timezone, checking that each field is in range.
+    pub fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Result<Time, Status> {
+        let time = Time {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            timezone: UNSPECIFIED_TIMEZONE,
+            ..Time::default()
+        };
+
+        time.validate().map(|_| time)
+    }
+
+    /// Set the nanosecond field, checking that it is in range.
+    pub fn with_nanosecond(mut self, nanosecond: u32) -> Result<Time, Status> {
+        self.nanosecond = nanosecond;
+        self.validate().map(|_| self)
+    }
+
+    /// Set the timezone, as an offset from UTC in minutes (`None` for local time), and the
+    /// daylight savings time bits, checking that they are in range.
+    pub fn with_timezone(mut self, timezone: Option<i16>, daylight: u8) -> Result<Time, Status> {
+        self.timezone = timezone.unwrap_or(UNSPECIFIED_TIMEZONE);
+        self.daylight = daylight;
+        self.validate().map(|_| self)
+    }
+
+    /// The offset from UTC in minutes, or `None` if this is local time.
+    pub fn timezone(&self) -> Option<i16> {
+        if self.timezone == UNSPECIFIED_TIMEZONE {
+            None
+        } else {
+            Some(self.timezone)
+        }
+    }
+
+    /// Check that every field is within the range given by the UEFI specification. Returns
+    /// `Status::InvalidParameter` otherwise, as the firmware would.
+    pub fn validate(&self) -> Result<(), Status> {
+        let valid = self.year >= 1900 && self.year <= 9999 &&
+            self.month >= 1 && self.month <= 12 &&
+            self.day >= 1 && self.day <= days_in_month(self.year, self.month) &&
+            self.hour <= 23 && self.minute <= 59 && self.second <= 59 &&
+            self.nanosecond <= 999_999_999 &&
+            (self.timezone == UNSPECIFIED_TIMEZONE || (self.timezone >= -1440 && self.timezone <= 1440)) &&
+            self.daylight & !(TIME_ADJUST_DAYLIGHT | TIME_IN_DAYLIGHT) == 0;
+
+        if !valid {
+            return Err(Status::InvalidParameter);
+        }
+
+        Ok(())
+    }
+}
+
 impl fmt::Display for Time {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:04}-{:02}-{:02} {:02}:{:02}:{:02}", self.year, self.month, self.day, self.hour, self.minute, self.second)
     }
 }
 
+#[test]
+fn time_new() {
+    assert!(Time::new(2016, 2, 29, 23, 59, 59).is_ok());
+    assert!(Time::new(2017, 2, 29, 0, 0, 0).is_err());
+    assert!(Time::new(1899, 1, 1, 0, 0, 0).is_err());
+    assert!(Time::new(2017, 1, 1, 24, 0, 0).is_err());
+    assert!(Time::new(2017, 1, 1, 0, 0, 0).unwrap().with_nanosecond(1_000_000_000).is_err());
+    assert!(Time::new(2017, 1, 1, 0, 0, 0).unwrap().with_timezone(Some(-1441), 0).is_err());
+    assert_eq!(Time::new(2017, 1, 1, 0, 0, 0).unwrap().with_timezone(Some(60), TIME_ADJUST_DAYLIGHT).unwrap().timezone(), Some(60));
+}
+
+/// Capabilities of the real time clock, returned by `RuntimeServices::get_time_and_capabilities`.
+#[derive(Copy, Clone, Debug, Default)]
 #[repr(C)]
 pub struct TimeCapabilities {
-    resolution: u32,
-    accuracy: u32,
-    sets_to_zero: bool,
+    /// Resolution of the clock in counts per second; 1 for a normal PC-AT CMOS RTC.
+    pub resolution: u32,
+
+    /// Accuracy of the clock in parts per million multiplied by 10^6.
+    pub accuracy: u32,
+
+    sets_to_zero: u8,
 }
 
+impl TimeCapabilities {
+    /// Whether setting the time clears everything below the clock's resolution.
+    pub fn sets_to_zero(&self) -> bool {
+        self.sets_to_zero != 0
+    }
+}
+
+#[cfg(feature = "mock")]
+impl TimeCapabilities {
+    pub(crate) fn new(resolution: u32, accuracy: u32, sets_to_zero: bool) -> TimeCapabilities {
+        TimeCapabilities { resolution, accuracy, sets_to_zero: sets_to_zero as u8 }
+    }
+}
@@ -2,12 +2,14 @@ use core::ptr;
 use core::mem;
 
 use void::{NotYetDef, CVoid};
-use base::{Event, Handle, Handles, MemoryType, Status};
+use base::{Event, Handle, Handles, MemoryDescriptor, MemoryType, Status};
 use event::{EventType, EventNotify, TimerDelay};
 use task::TPL;
 use protocol::{DevicePathProtocol, Protocol, get_current_image};
 use guid;
 use table;
+#[cfg(feature = "mock")]
+use mock;
 
 #[repr(C)]
 pub enum LocateSearchType {
@@ -16,58 +18,135 @@ pub enum LocateSearchType {
     ByProtocol = 2,
 }
 
+/// Size of the pages handed out by `allocate_pages`.
+pub const PAGE_SIZE: usize = 4096;
+
+pub(crate) static mut BOOT_SERVICES_EXITED: bool = false;
+
+/// Whether `exit_boot_services` has succeeded, after which boot services, including the console
+/// and other protocols, can no longer be used.
+pub fn boot_services_exited() -> bool {
+    unsafe { BOOT_SERVICES_EXITED }
+}
+
+/// How `allocate_pages` chooses the address of an allocation.
+#[repr(C)]
+pub enum AllocateType {
+    /// Anywhere.
+    AnyPages = 0,
+    /// Anywhere below the given address.
+    MaxAddress = 1,
+    /// Exactly at the given address.
+    Address = 2,
+}
+
 /// See http://wiki.phoenix.com/wiki/index.php/EFI_BOOT_SERVICES
 #[repr(C)]
 pub struct BootServices {
     header: table::TableHeader,
-    raise_tpl: *const NotYetDef,
-    restore_tpl: *const NotYetDef,
-    allocate_pages: *const NotYetDef,
-    free_pages: *const NotYetDef,
-    get_memory_map: *const NotYetDef,
-    allocate_pool: unsafe extern "win64" fn(pool_type: MemoryType, size: usize, out: *mut *mut u8) -> Status,
-    free_pool: unsafe extern "win64" fn(*mut CVoid),
-    create_event: unsafe extern "win64" fn(event_type: EventType, notify_tpl: TPL, notify_function: Option<EventNotify>, notify_context: *const CVoid, event: *mut Event) -> Status,
-    set_timer: unsafe extern "win64" fn(event: Event, delay_type: TimerDelay, delay: u64) -> Status,
+    raise_tpl: unsafe extern "efiapi" fn(new_tpl: usize) -> usize,
+    restore_tpl: unsafe extern "efiapi" fn(old_tpl: usize),
+    allocate_pages: unsafe extern "efiapi" fn(allocate_type: AllocateType, memory_type: MemoryType, pages: usize, memory: *mut u64) -> Status,
+    free_pages: unsafe extern "efiapi" fn(memory: u64, pages: usize) -> Status,
+    get_memory_map: unsafe extern "efiapi" fn(map_size: *mut usize, map: *mut MemoryDescriptor, map_key: *mut usize, descriptor_size: *mut usize, descriptor_version: *mut u32) -> Status,
+    allocate_pool: unsafe extern "efiapi" fn(pool_type: MemoryType, size: usize, out: *mut *mut u8) -> Status,
+    free_pool: unsafe extern "efiapi" fn(*mut CVoid),
+    create_event: unsafe extern "efiapi" fn(event_type: EventType, notify_tpl: TPL, notify_function: Option<EventNotify>, notify_context: *const CVoid, event: *mut Event) -> Status,
+    set_timer: unsafe extern "efiapi" fn(event: Event, delay_type: TimerDelay, delay: u64) -> Status,
     // typedef EFI_STATUS (EFIAPI *EFI_WAIT_FOR_EVENT) (IN UINTN NumberOfEvents, IN EFI_EVENT *Event, OUT UINTN *Index);
-    wait_for_event: unsafe extern "win64" fn(usize, *const Event, *mut usize) -> Status,
+    wait_for_event: unsafe extern "efiapi" fn(usize, *const Event, *mut usize) -> Status,
     signal_event: *const NotYetDef,
     close_event: *const NotYetDef,
     check_event: *const NotYetDef,
     install_protocol_interface: *const NotYetDef,
     reinstall_protocol_interface: *const NotYetDef,
     uninstall_protocol_interface: *const NotYetDef,
-    handle_protocol: unsafe extern "win64" fn(Handle, &guid::Guid, &mut *mut CVoid) -> Status,
+    handle_protocol: unsafe extern "efiapi" fn(Handle, &guid::Guid, &mut *mut CVoid) -> Status,
     __reserved: *const NotYetDef,
     register_protocol_notify: *const NotYetDef,
     locate_handle: *const NotYetDef,
     locate_device_path: *const NotYetDef,
     install_configuration_table: *const NotYetDef,
-    load_image: unsafe extern "win64" fn(boot_policy: u8, parent_image_handle: Handle, device_path: *const DevicePathProtocol, source_buffer: *const CVoid, source_size: usize, image_handle: *mut Handle) -> Status,
-    start_image: unsafe extern "win64" fn(image_handle: Handle, exit_data_size: *mut usize, exit_data: *mut *const u16) -> Status,
-    exit: *const NotYetDef,
+    load_image: unsafe extern "efiapi" fn(boot_policy: u8, parent_image_handle: Handle, device_path: *const DevicePathProtocol, source_buffer: *const CVoid, source_size: usize, image_handle: *mut Handle) -> Status,
+    start_image: unsafe extern "efiapi" fn(image_handle: Handle, exit_data_size: *mut usize, exit_data: *mut *const u16) -> Status,
+    exit: unsafe extern "efiapi" fn(image_handle: Handle, exit_status: Status, exit_data_size: usize, exit_data: *const u16) -> Status,
     unload_image: *const NotYetDef,
-    exit_boot_services: *const NotYetDef,
+    exit_boot_services: unsafe extern "efiapi" fn(image_handle: Handle, map_key: usize) -> Status,
     get_next_monotonic_count: *const NotYetDef,
-    stall: unsafe extern "win64" fn(usize) -> Status,
-    set_watchdog_timer: unsafe extern "win64" fn(timeout: usize, code: u64, data_size: usize, data: *const u16) -> Status,
+    stall: unsafe extern "efiapi" fn(usize) -> Status,
+    set_watchdog_timer: unsafe extern "efiapi" fn(timeout: usize, code: u64, data_size: usize, data: *const u16) -> Status,
     connect_controller: *const NotYetDef,
     disconnect_controller: *const NotYetDef,
     open_protocol: *const NotYetDef,
-    close_protocol: unsafe extern "win64" fn(handle: Handle, protocol: &guid::Guid, agent_handle: Handle, controller_handle: Handle) -> Status,
+    close_protocol: unsafe extern "efiapi" fn(handle: Handle, protocol: &guid::Guid, agent_handle: Handle, controller_handle: Handle) -> Status,
     open_protocol_information: *const NotYetDef,
     protocols_per_handle: *const NotYetDef,
-    locate_handle_buffer: unsafe extern "win64" fn(search_type: LocateSearchType, protocol: &guid::Guid, search_key: *const CVoid, nhandles: *mut usize, handles: *mut *mut CVoid) -> Status,
-    locate_protocol: unsafe extern "win64" fn(protocol: &guid::Guid, registration: *const CVoid, interface: &mut *mut CVoid) -> Status,
+    locate_handle_buffer: unsafe extern "efiapi" fn(search_type: LocateSearchType, protocol: &guid::Guid, search_key: *const CVoid, nhandles: *mut usize, handles: *mut *mut CVoid) -> Status,
+    locate_protocol: unsafe extern "efiapi" fn(protocol: &guid::Guid, registration: *const CVoid, interface: &mut *mut CVoid) -> Status,
     install_multiple_protocol_interfaces: *const NotYetDef,
     uninstall_multiple_protocol_interfaces: *const NotYetDef,
     calculate_crc32: *const NotYetDef,
-    copy_mem: unsafe extern "win64" fn(*mut CVoid, *mut CVoid, usize),
-    set_mem: unsafe extern "win64" fn(*mut CVoid, usize, u8),
+    copy_mem: unsafe extern "efiapi" fn(*mut CVoid, *mut CVoid, usize),
+    set_mem: unsafe extern "efiapi" fn(*mut CVoid, usize, u8),
     create_event_ex: *const NotYetDef,
 }
 
+/// A memory map returned by `BootServices::get_memory_map`. The firmware's descriptors may be
+/// larger than `MemoryDescriptor`, so they are stepped through `descriptor_size` at a time.
+pub struct MemoryMap<'a> {
+    buf: &'a mut [u8],
+    /// Identifies this map to `exit_boot_services`.
+    pub key: usize,
+    pub descriptor_size: usize,
+    pub descriptor_version: u32,
+}
+
+impl<'a> MemoryMap<'a> {
+    /// The number of descriptors in the map.
+    pub fn len(&self) -> usize {
+        self.buf.len() / self.descriptor_size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &MemoryDescriptor> {
+        self.buf.chunks_exact(self.descriptor_size)
+            .map(|d| unsafe { &*(d.as_ptr() as *const MemoryDescriptor) })
+    }
+
+    /// Iterate over the descriptors mutably, e.g. to fill in their virtual addresses before
+    /// `RuntimeServices::set_virtual_address_map`.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut MemoryDescriptor> {
+        self.buf.chunks_exact_mut(self.descriptor_size)
+            .map(|d| unsafe { &mut *(d.as_mut_ptr() as *mut MemoryDescriptor) })
+    }
+
+    /// The raw descriptors, as passed to and from the firmware.
+    pub fn as_mut_bytes(&mut self) -> &mut [u8] {
+        self.buf
+    }
+}
+
 impl BootServices {
+    /// The header of the boot services table, which was checked by `set_system_table`.
+    pub fn header(&self) -> &table::TableHeader {
+        &self.header
+    }
+
+    /// Raise the task priority level to `tpl`, holding off event notifications at or below it.
+    /// Returns the previous level, which must be passed to `restore_tpl`. `tpl` must not be
+    /// lower than the current level.
+    pub fn raise_tpl(&self, tpl: TPL) -> usize {
+        unsafe { (self.raise_tpl)(tpl as usize) }
+    }
+
+    /// Go back to the level returned by `raise_tpl`.
+    pub fn restore_tpl(&self, old_tpl: usize) {
+        unsafe { (self.restore_tpl)(old_tpl) }
+    }
+
     /// Allocate `size` bytes of memory using type `T`.
     pub fn allocate_pool<T>(&self, size: usize) -> Result<*mut T, Status> {
         let mut ptr: *mut u8 = 0 as *mut u8;
@@ -85,6 +164,28 @@ impl BootServices {
         }
     }
 
+    /// Allocate `pages` pages of memory using type `T`, with the placement given by
+    /// `allocate_type` and `address`. Returns the physical address of the allocation, which is
+    /// also its address while boot services are running.
+    pub fn allocate_pages(&self, allocate_type: AllocateType, address: u64, pages: usize) -> Result<u64, Status> {
+        let mut memory = address;
+
+        let result = unsafe { (self.allocate_pages)(allocate_type, get_current_image().image_data_type, pages, &mut memory) };
+        if result != Status::Success {
+            return Err(result);
+        }
+        Ok(memory)
+    }
+
+    /// Free `pages` pages allocated with `allocate_pages` at `memory`.
+    pub fn free_pages(&self, memory: u64, pages: usize) -> Result<(), Status> {
+        let result = unsafe { (self.free_pages)(memory, pages) };
+        if result != Status::Success {
+            return Err(result);
+        }
+        Ok(())
+    }
+
     pub fn create_event(&self, event_type: EventType, notify_tpl: TPL, notify_func: Option<EventNotify>, notify_context: *const CVoid) -> Result<Event, Status> {
         let mut event: Event = Event(0 as *mut CVoid);
 
@@ -199,6 +300,93 @@ impl BootServices {
         Ok(())
     }
 
+    /// Return the size in bytes of a buffer big enough for the current memory map. Allocating the
+    /// buffer may itself grow the map, so leave room for a couple more descriptors.
+    pub fn get_memory_map_size(&self) -> Result<usize, Status> {
+        let mut map_size: usize = 0;
+        let mut map_key: usize = 0;
+        let mut descriptor_size: usize = 0;
+        let mut descriptor_version: u32 = 0;
+
+        let result = unsafe { (self.get_memory_map)(&mut map_size, ptr::null_mut(), &mut map_key, &mut descriptor_size, &mut descriptor_version) };
+        match result {
+            Status::BufferTooSmall | Status::Success => Ok(map_size),
+            e => Err(e),
+        }
+    }
+
+    /// Read the current memory map into `buf`, which must be 8-byte aligned, as memory from
+    /// `allocate_pool` is.
+    pub fn get_memory_map<'a>(&self, buf: &'a mut [u8]) -> Result<MemoryMap<'a>, Status> {
+        if buf.as_ptr() as usize % mem::align_of::<MemoryDescriptor>() != 0 {
+            return Err(Status::InvalidParameter);
+        }
+
+        let mut map_size = buf.len();
+        let mut map_key: usize = 0;
+        let mut descriptor_size: usize = 0;
+        let mut descriptor_version: u32 = 0;
+
+        let result = unsafe { (self.get_memory_map)(&mut map_size, buf.as_mut_ptr() as *mut MemoryDescriptor, &mut map_key, &mut descriptor_size, &mut descriptor_version) };
+        if result != Status::Success {
+            return Err(result);
+        }
+        if descriptor_size < mem::size_of::<MemoryDescriptor>() {
+            return Err(Status::IncompatibleVersion);
+        }
+
+        Ok(MemoryMap {
+            buf: &mut buf[..map_size],
+            key: map_key,
+            descriptor_size,
+            descriptor_version,
+        })
+    }
+
+    /// Exit the image `image_handle`, returning `status` to whoever started it. Only returns if
+    /// the image can't be exited, such as when it hasn't been started.
+    pub fn exit(&self, image_handle: Handle, status: Status) -> Status {
+        unsafe { (self.exit)(image_handle, status, 0, ptr::null()) }
+    }
+
+    /// Like `exit`, also returning `message` as the exit data: a null-terminated UCS-2 string,
+    /// which is allocated from pool as the specification requires. Characters outside the Basic
+    /// Multilingual Plane are replaced with U+FFFD.
+    pub fn exit_with_message(&self, image_handle: Handle, status: Status, message: &str) -> Status {
+        let len = message.chars().count() + 1;
+        let data: *mut u16 = match self.allocate_pool(len * mem::size_of::<u16>()) {
+            Ok(data) => data,
+            Err(e) => return e,
+        };
+
+        unsafe {
+            for (i, c) in message.chars().enumerate() {
+                *data.add(i) = if c.len_utf16() > 1 { 0xFFFD } else { c as u16 };
+            }
+            *data.add(len - 1) = 0;
+        }
+
+        let result = unsafe { (self.exit)(image_handle, status, len * mem::size_of::<u16>(), data) };
+        self.free_pool(data);
+        result
+    }
+
+    /// Terminate boot services. `map_key` must be the key of the most recent memory map; if it
+    /// is stale, `Status::InvalidParameter` is returned and the map must be fetched again. On
+    /// success, only runtime services may be used from then on.
+    pub fn exit_boot_services(&self, image_handle: Handle, map_key: usize) -> Result<(), Status> {
+        let result = unsafe { (self.exit_boot_services)(image_handle, map_key) };
+        if result != Status::Success {
+            return Err(result);
+        }
+
+        unsafe {
+            BOOT_SERVICES_EXITED = true;
+        }
+
+        Ok(())
+    }
+
     /// Sleep for a number of microseconds.
     pub fn stall(&self, microseconds: usize) {
         unsafe {
@@ -249,3 +437,59 @@ impl BootServices {
     }
 }
 
+#[cfg(feature = "mock")]
+impl BootServices {
+    /// A table of the mock firmware's services, with its CRC32 filled in.
+    pub(crate) fn mock() -> BootServices {
+        let mut table = BootServices {
+            header: table::TableHeader::new(table::EFI_BOOT_SERVICES_SIGNATURE, mock::REVISION, mem::size_of::<BootServices>()),
+            raise_tpl: mock::raise_tpl,
+            restore_tpl: mock::restore_tpl,
+            allocate_pages: mock::allocate_pages,
+            free_pages: mock::free_pages,
+            get_memory_map: mock::get_memory_map,
+            allocate_pool: mock::allocate_pool,
+            free_pool: mock::free_pool,
+            create_event: mock::create_event,
+            set_timer: mock::set_timer,
+            wait_for_event: mock::wait_for_event,
+            signal_event: ptr::null(),
+            close_event: ptr::null(),
+            check_event: ptr::null(),
+            install_protocol_interface: ptr::null(),
+            reinstall_protocol_interface: ptr::null(),
+            uninstall_protocol_interface: ptr::null(),
+            handle_protocol: mock::handle_protocol,
+            __reserved: ptr::null(),
+            register_protocol_notify: ptr::null(),
+            locate_handle: ptr::null(),
+            locate_device_path: ptr::null(),
+            install_configuration_table: ptr::null(),
+            load_image: mock::load_image,
+            start_image: mock::start_image,
+            exit: mock::exit,
+            unload_image: ptr::null(),
+            exit_boot_services: mock::exit_boot_services,
+            get_next_monotonic_count: ptr::null(),
+            stall: mock::stall,
+            set_watchdog_timer: mock::set_watchdog_timer,
+            connect_controller: ptr::null(),
+            disconnect_controller: ptr::null(),
+            open_protocol: ptr::null(),
+            close_protocol: mock::close_protocol,
+            open_protocol_information: ptr::null(),
+            protocols_per_handle: ptr::null(),
+            locate_handle_buffer: mock::locate_handle_buffer,
+            locate_protocol: mock::locate_protocol,
+            install_multiple_protocol_interfaces: ptr::null(),
+            uninstall_multiple_protocol_interfaces: ptr::null(),
+            calculate_crc32: ptr::null(),
+            copy_mem: mock::copy_mem,
+            set_mem: mock::set_mem,
+            create_event_ex: ptr::null(),
+        };
+        unsafe { table.header.update_crc32() };
+        table
+    }
+}
+
@@ -0,0 +1,206 @@
+// Copyright 2017 CoreOS, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Capsules, the mechanism for handing firmware updates and other data to the firmware through
+//! `RuntimeServices::update_capsule`.
+
+use core::{mem, ptr, slice};
+
+use base::Status;
+use bootservices::{AllocateType, PAGE_SIZE};
+use guid::Guid;
+use runtimeservices::ResetType;
+
+bitflags! {
+    /// Flags in a capsule header. The low 16 bits are defined by the capsule's owner.
+    pub struct CapsuleFlags: u32 {
+        /// Process the capsule after the next reset, instead of immediately.
+        const CAPSULE_FLAGS_PERSIST_ACROSS_RESET = 0x00010000;
+        /// Put a pointer to the capsule in the configuration table once it's processed.
+        const CAPSULE_FLAGS_POPULATE_SYSTEM_TABLE = 0x00020000;
+        /// Have `update_capsule` reset the system itself.
+        const CAPSULE_FLAGS_INITIATE_RESET = 0x00040000;
+    }
+}
+
+/// Type for EFI_CAPSULE_HEADER, which starts every capsule.
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct CapsuleHeader {
+    /// Identifies the capsule's format, e.g. a firmware class GUID from the ESRT.
+    pub capsule_guid: Guid,
+    /// Size of the header, which may be larger than this structure.
+    pub header_size: u32,
+    pub flags: u32,
+    /// Size of the whole capsule, including the header.
+    pub capsule_image_size: u32,
+}
+
+impl CapsuleHeader {
+    pub fn flags(&self) -> CapsuleFlags {
+        CapsuleFlags::from_bits_truncate(self.flags)
+    }
+}
+
+/// Type for EFI_CAPSULE_BLOCK_DESCRIPTOR. A descriptor with a length describes a block of capsule
+/// data; one with a zero length continues the list at `address`, or ends it if that is zero too.
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct CapsuleBlockDescriptor {
+    pub length: u64,
+    pub address: u64,
+}
+
+fn pages_for(bytes: usize) -> usize {
+    (bytes + PAGE_SIZE - 1) / PAGE_SIZE
+}
+
+/// A capsule copied into its own page allocation, where it can stay until the firmware consumes
+/// it, possibly after a reset.
+pub struct Capsule {
+    address: u64,
+    size: usize,
+}
+
+impl Capsule {
+    /// Check that `data` starts with a consistent capsule header, and copy it into newly
+    /// allocated pages.
+    pub fn new(data: &[u8]) -> Result<Capsule, Status> {
+        let header_len = mem::size_of::<CapsuleHeader>();
+        if data.len() < header_len || data.len() > u32::MAX as usize {
+            return Err(Status::InvalidParameter);
+        }
+
+        let header = unsafe { ptr::read_unaligned(data.as_ptr() as *const CapsuleHeader) };
+        if (header.header_size as usize) < header_len || header.header_size > header.capsule_image_size ||
+            header.capsule_image_size as usize != data.len() {
+            return Err(Status::InvalidParameter);
+        }
+
+        let bs = ::get_system_table().boot_services();
+        let address = bs.allocate_pages(AllocateType::AnyPages, 0, pages_for(data.len()))?;
+        unsafe {
+            ptr::copy_nonoverlapping(data.as_ptr(), address as usize as *mut u8, data.len());
+        }
+
+        Ok(Capsule { address, size: data.len() })
+    }
+
+    pub fn header(&self) -> &CapsuleHeader {
+        unsafe { &*(self.address as usize as *const CapsuleHeader) }
+    }
+
+    /// The physical address of the capsule.
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Free the capsule's pages. This must not be done once a capsule which persists across reset
+    /// has been passed to `update_capsule`, since the firmware reads it after the reset.
+    pub fn free(self) -> Result<(), Status> {
+        ::get_system_table().boot_services().free_pages(self.address, pages_for(self.size))
+    }
+}
+
+/// A scatter-gather list describing where the data of a set of capsules is, as required by
+/// `update_capsule` for capsules which persist across reset. It lives in its own page
+/// allocation, which is left for the firmware to read.
+pub struct ScatterGatherList {
+    address: u64,
+    count: usize,
+}
+
+impl ScatterGatherList {
+    /// Build a list with one block per capsule.
+    pub fn new(capsules: &[&Capsule]) -> Result<ScatterGatherList, Status> {
+        let count = capsules.len() + 1;
+        let size = count * mem::size_of::<CapsuleBlockDescriptor>();
+
+        let bs = ::get_system_table().boot_services();
+        let address = bs.allocate_pages(AllocateType::AnyPages, 0, pages_for(size))?;
+        let descriptors = unsafe { slice::from_raw_parts_mut(address as usize as *mut CapsuleBlockDescriptor, count) };
+
+        for (descriptor, capsule) in descriptors.iter_mut().zip(capsules.iter()) {
+            descriptor.length = capsule.len() as u64;
+            descriptor.address = capsule.address();
+        }
+        descriptors[count - 1] = CapsuleBlockDescriptor::default();
+
+        Ok(ScatterGatherList { address, count })
+    }
+
+    /// The physical address of the first descriptor.
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+
+    pub fn descriptors(&self) -> &[CapsuleBlockDescriptor] {
+        unsafe { slice::from_raw_parts(self.address as usize as *const CapsuleBlockDescriptor, self.count) }
+    }
+
+    /// Free the list's pages; see `Capsule::free`.
+    pub fn free(self) -> Result<(), Status> {
+        let size = self.count * mem::size_of::<CapsuleBlockDescriptor>();
+        ::get_system_table().boot_services().free_pages(self.address, pages_for(size))
+    }
+}
+
+/// Copy `data` into a capsule and hand it to the firmware, checking first that the firmware
+/// accepts it. Returns the reset the caller must perform for the capsule to be processed; if the
+/// capsule is flagged `CAPSULE_FLAGS_INITIATE_RESET`, the firmware resets by itself instead of
+/// returning.
+pub fn deliver_capsule(data: &[u8]) -> Result<ResetType, Status> {
+    let rs = ::get_system_table().runtime_services();
+    let capsule = Capsule::new(data)?;
+
+    let result = {
+        let headers = [capsule.header()];
+        rs.query_capsule_capabilities(&headers).and_then(|(maximum_size, reset_type)| {
+            if capsule.len() as u64 > maximum_size {
+                return Err(Status::BadBufferSize);
+            }
+
+            if !capsule.header().flags().contains(CAPSULE_FLAGS_PERSIST_ACROSS_RESET) {
+                return rs.update_capsule(&headers, None).map(|_| reset_type);
+            }
+
+            let list = ScatterGatherList::new(&[&capsule])?;
+            let result = rs.update_capsule(&headers, Some(&list));
+            if result.is_err() {
+                let _ = list.free();
+            }
+            result.map(|_| reset_type)
+        })
+    };
+
+    match result {
+        // A capsule processed immediately has been consumed already.
+        Ok(reset_type) if !capsule.header().flags().contains(CAPSULE_FLAGS_PERSIST_ACROSS_RESET) => {
+            capsule.free().map(|_| reset_type)
+        },
+        Ok(reset_type) => Ok(reset_type),
+        Err(e) => {
+            let _ = capsule.free();
+            Err(e)
+        },
+    }
+}
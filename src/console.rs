@@ -1,16 +1,106 @@
 use core::fmt;
 
-use void::*;
 use base::{Event, Status};
 use systemtable;
+#[cfg(feature = "mock")]
+use mock;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 #[repr(C)]
 pub struct InputKey {
     pub scan_code: u16,
     pub unicode_char: u16,
 }
 
+impl InputKey {
+    pub fn key(&self) -> Key {
+        Key::from(*self)
+    }
+}
+
+/// A key press, with the scan codes and control characters from the spec given names.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Key {
+    /// A printable character.
+    Char(char),
+    Backspace,
+    Tab,
+    /// Either carriage return or line feed.
+    Enter,
+    Up,
+    Down,
+    Right,
+    Left,
+    Home,
+    End,
+    Insert,
+    Delete,
+    PageUp,
+    PageDown,
+    /// F1 through F24.
+    Function(u8),
+    Escape,
+    Mute,
+    VolumeUp,
+    VolumeDown,
+    BrightnessUp,
+    BrightnessDown,
+    Suspend,
+    Hibernate,
+    ToggleDisplay,
+    Recovery,
+    Eject,
+    /// An OEM-reserved scan code, 0x8000 through 0xFFFF.
+    Oem(u16),
+    /// Anything else, including a key of all zeroes, which reports a change in modifiers
+    /// alone.
+    Unknown(InputKey),
+}
+
+impl From<InputKey> for Key {
+    fn from(key: InputKey) -> Key {
+        match key.scan_code {
+            0x00 => (),
+            0x01 => return Key::Up,
+            0x02 => return Key::Down,
+            0x03 => return Key::Right,
+            0x04 => return Key::Left,
+            0x05 => return Key::Home,
+            0x06 => return Key::End,
+            0x07 => return Key::Insert,
+            0x08 => return Key::Delete,
+            0x09 => return Key::PageUp,
+            0x0A => return Key::PageDown,
+            code @ 0x0B..=0x16 => return Key::Function((code - 0x0B + 1) as u8),
+            0x17 => return Key::Escape,
+            code @ 0x68..=0x73 => return Key::Function((code - 0x68 + 13) as u8),
+            0x7F => return Key::Mute,
+            0x80 => return Key::VolumeUp,
+            0x81 => return Key::VolumeDown,
+            0x100 => return Key::BrightnessUp,
+            0x101 => return Key::BrightnessDown,
+            0x102 => return Key::Suspend,
+            0x103 => return Key::Hibernate,
+            0x104 => return Key::ToggleDisplay,
+            0x105 => return Key::Recovery,
+            0x106 => return Key::Eject,
+            code @ 0x8000..=0xFFFF => return Key::Oem(code),
+            _ => return Key::Unknown(key),
+        }
+
+        match key.unicode_char {
+            0x00 => Key::Unknown(key),
+            0x08 => Key::Backspace,
+            0x09 => Key::Tab,
+            0x0A | 0x0D => Key::Enter,
+            c => match ::core::char::from_u32(c as u32) {
+                Some(c) => Key::Char(c),
+                None => Key::Unknown(key),
+            },
+        }
+    }
+}
+
 #[repr(u8)]
 pub enum ForegroundColor {
     Black = 0x0,
@@ -43,7 +133,7 @@ pub enum BackgroundColor {
     LightGray = 0x70,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Attribute {
     fg: u8,
     bg: u8,
@@ -54,40 +144,226 @@ impl Attribute {
         Attribute { fg: fg as u8, bg: bg as u8, }
     }
 
-    fn to_efi_attribute(&self) -> usize {
-        ((self.bg as usize) << 4) | self.fg as usize
+    /// Split an EFI text attribute, as found in `SimpleTextOutputMode`, into its colors.
+    pub fn from_efi_attribute(attribute: usize) -> Attribute {
+        Attribute { fg: (attribute & 0x0F) as u8, bg: (attribute & 0x70) as u8, }
+    }
+
+    /// This attribute with its foreground color replaced.
+    pub fn with_foreground(self, fg: ForegroundColor) -> Attribute {
+        Attribute { fg: fg as u8, bg: self.bg, }
+    }
+
+    /// Combine the colors into an EFI text attribute, as passed to `set_attribute`. The
+    /// background is in bits 4-6, where `BackgroundColor` values already are, so it isn't shifted
+    /// again.
+    pub fn to_efi_attribute(&self) -> usize {
+        self.bg as usize | self.fg as usize
     }
 }
 
 #[repr(C)]
 pub struct SimpleTextInputProtocol {
-    reset: unsafe extern "win64" fn(*const SimpleTextInputProtocol, u8) -> Status,
-    read_key_stroke: unsafe extern "win64" fn(*const SimpleTextInputProtocol, &mut InputKey) -> Status,
+    reset: unsafe extern "efiapi" fn(*const SimpleTextInputProtocol, u8) -> Status,
+    read_key_stroke: unsafe extern "efiapi" fn(*const SimpleTextInputProtocol, &mut InputKey) -> Status,
     wait_for_key: Event,
 }
 
 #[repr(C)]
 pub struct SimpleTextOutputProtocol {
-    reset: unsafe extern "win64" fn(*const SimpleTextOutputProtocol, u8) -> Status,
-    output_string: unsafe extern "win64" fn(*const SimpleTextOutputProtocol, *const u16) -> Status,
-    test_string: unsafe extern "win64" fn(*const SimpleTextOutputProtocol, *const u16) -> Status,
-    query_mode: *const NotYetDef,
-    set_mode: *const NotYetDef,
-    set_attribute: unsafe extern "win64" fn(*const SimpleTextOutputProtocol, usize) -> Status,
-    clear_screen: unsafe extern "win64" fn(*const SimpleTextOutputProtocol) -> Status,
-    set_cursor_position: *const NotYetDef,
-    enable_cursor: *const NotYetDef,
-    mode: *const NotYetDef,
+    reset: unsafe extern "efiapi" fn(*const SimpleTextOutputProtocol, u8) -> Status,
+    output_string: unsafe extern "efiapi" fn(*const SimpleTextOutputProtocol, *const u16) -> Status,
+    test_string: unsafe extern "efiapi" fn(*const SimpleTextOutputProtocol, *const u16) -> Status,
+    query_mode: unsafe extern "efiapi" fn(*const SimpleTextOutputProtocol, usize, &mut usize, &mut usize) -> Status,
+    set_mode: unsafe extern "efiapi" fn(*const SimpleTextOutputProtocol, usize) -> Status,
+    set_attribute: unsafe extern "efiapi" fn(*const SimpleTextOutputProtocol, usize) -> Status,
+    clear_screen: unsafe extern "efiapi" fn(*const SimpleTextOutputProtocol) -> Status,
+    set_cursor_position: unsafe extern "efiapi" fn(*const SimpleTextOutputProtocol, usize, usize) -> Status,
+    enable_cursor: unsafe extern "efiapi" fn(*const SimpleTextOutputProtocol, u8) -> Status,
+    mode: *const SimpleTextOutputMode,
+}
+
+/// Type for SIMPLE_TEXT_OUTPUT_MODE, the current state of a text output device.
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct SimpleTextOutputMode {
+    /// The number of modes supported by `query_mode` and `set_mode`.
+    pub max_mode: i32,
+    /// The current mode.
+    pub mode: i32,
+    attribute: i32,
+    pub cursor_column: i32,
+    pub cursor_row: i32,
+    cursor_visible: u8,
+}
+
+impl SimpleTextOutputMode {
+    /// The current text attribute.
+    pub fn attribute(&self) -> Attribute {
+        Attribute::from_efi_attribute(self.attribute as usize)
+    }
+
+    pub fn cursor_visible(&self) -> bool {
+        self.cursor_visible != 0
+    }
+}
+
+#[cfg(feature = "mock")]
+impl SimpleTextInputProtocol {
+    /// A console input device implemented by the mock firmware, signalling `wait_for_key`.
+    pub(crate) fn mock(wait_for_key: Event) -> SimpleTextInputProtocol {
+        SimpleTextInputProtocol {
+            reset: mock::input_reset,
+            read_key_stroke: mock::read_key_stroke,
+            wait_for_key,
+        }
+    }
+}
+
+#[cfg(feature = "mock")]
+impl SimpleTextOutputProtocol {
+    /// A console output device implemented by the mock firmware, whose state is in `mode`.
+    pub(crate) fn mock(mode: *const SimpleTextOutputMode) -> SimpleTextOutputProtocol {
+        SimpleTextOutputProtocol {
+            reset: mock::output_reset,
+            output_string: mock::output_string,
+            test_string: mock::test_string,
+            query_mode: mock::query_mode,
+            set_mode: mock::set_mode,
+            set_attribute: mock::set_attribute,
+            clear_screen: mock::clear_screen,
+            set_cursor_position: mock::set_cursor_position,
+            enable_cursor: mock::enable_cursor,
+            mode,
+        }
+    }
+}
+
+#[cfg(feature = "mock")]
+impl SimpleTextOutputMode {
+    pub(crate) fn set_attribute(&mut self, attribute: i32) {
+        self.attribute = attribute;
+    }
+
+    pub(crate) fn set_cursor_visible(&mut self, visible: u8) {
+        self.cursor_visible = visible;
+    }
+}
+
+/// A text mode, as listed by `SimpleTextOutput::modes`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextMode {
+    pub number: usize,
+    pub columns: usize,
+    pub rows: usize,
+}
+
+/// Iterator over the text modes a device supports.
+pub struct TextModes<'a, T: 'a + SimpleTextOutput + ?Sized> {
+    output: &'a T,
+    number: usize,
+    max: usize,
+}
+
+impl<'a, T: SimpleTextOutput + ?Sized> Iterator for TextModes<'a, T> {
+    type Item = TextMode;
+
+    fn next(&mut self) -> Option<TextMode> {
+        while self.number < self.max {
+            let number = self.number;
+            self.number += 1;
+
+            // Modes the device can't do are skipped, as query_mode reports them as unsupported.
+            if let Ok((columns, rows)) = self.output.query_mode(number) {
+                return Some(TextMode { number, columns, rows });
+            }
+        }
+
+        None
+    }
 }
 
 pub trait SimpleTextOutput {
     fn write_raw(&self, str: *const u16) -> Status;
 
+    /// Check whether the device can display every character of a null-terminated UCS-2 string.
+    /// Devices which can't tell are assumed to display everything.
+    fn test_raw(&self, _str: *const u16) -> Status {
+        Status::Success
+    }
+
+    /// Whether `write` should translate `\n` into the `\r\n` UEFI consoles expect.
+    fn translate_newlines(&self) -> bool {
+        true
+    }
+
     fn set_attribute(&self, attribute: Attribute) -> Status;
 
+    /// Clear the screen with the current background color, and move the cursor to (0, 0).
+    fn clear_screen(&self) -> Status {
+        Status::Unsupported
+    }
+
+    /// Return the number of columns and rows of text mode `mode`.
+    fn query_mode(&self, _mode: usize) -> Result<(usize, usize), Status> {
+        Err(Status::Unsupported)
+    }
+
+    /// Switch to text mode `mode`, clearing the screen.
+    fn set_mode(&self, _mode: usize) -> Status {
+        Status::Unsupported
+    }
+
+    /// Move the cursor to `column`, `row`, counted from 0 at the top left.
+    fn set_cursor_position(&self, _column: usize, _row: usize) -> Status {
+        Status::Unsupported
+    }
+
+    /// Show or hide the cursor.
+    fn enable_cursor(&self, _visible: bool) -> Status {
+        Status::Unsupported
+    }
+
+    /// The current mode, attribute and cursor state. Devices without modes report none.
+    fn mode(&self) -> SimpleTextOutputMode {
+        SimpleTextOutputMode::default()
+    }
+
+    /// Iterate over the text modes this device supports.
+    fn modes(&self) -> TextModes<'_, Self> {
+        TextModes {
+            output: self,
+            number: 0,
+            max: self.mode().max_mode as usize,
+        }
+    }
+
+    /// Return the columns and rows of the current mode.
+    fn size(&self) -> Result<(usize, usize), Status> {
+        self.query_mode(self.mode().mode as usize)
+    }
+
+    /// Switch to the supported text mode with the most characters, and return it.
+    fn set_largest_mode(&self) -> Result<TextMode, Status> {
+        let largest = self.modes().max_by_key(|mode| mode.columns * mode.rows);
+
+        match largest {
+            Some(mode) => match self.set_mode(mode.number) {
+                Status::Success => Ok(mode),
+                e => Err(e),
+            },
+            None => Err(Status::Unsupported),
+        }
+    }
+
+    /// Write `s`, translating it to UCS-2. Characters outside the Basic Multilingual Plane, and
+    /// any the device reports it can't display, are replaced with U+FFFD (or `?` if that can't be
+    /// displayed either). If `translate_newlines` is set, each `\n` is sent as `\r\n`.
     fn write(&self, s: &str) -> Status {
         let mut buf = [0u16; 64];
         let mut i = 0;
+        let mut after_cr = false;
+        let translate_newlines = self.translate_newlines();
 
         // don't write nothing
         if s.is_empty() {
@@ -95,13 +371,18 @@ pub trait SimpleTextOutput {
         }
 
         for c in s.chars() {
-            buf[i] = c as u16;
+            if c == '\n' && translate_newlines && !after_cr {
+                buf[i] = '\r' as u16;
+                i += 1;
+            }
+            after_cr = c == '\r';
+
+            buf[i] = if c.len_utf16() > 1 { REPLACEMENT_CHARACTER } else { c as u16 };
             i += 1;
 
-            // if we hit the end of buf, send output
-            if i == buf.len() - 1 {
-                buf[i] = 0;
-                let status = self.write_raw(buf.as_ptr());
+            // if we're near the end of buf, send output; keep room for a \r\n pair.
+            if i >= buf.len() - 2 {
+                let status = write_ucs2(self, &mut buf, i);
                 if status != Status::Success {
                     return status
                 }
@@ -111,8 +392,7 @@ pub trait SimpleTextOutput {
 
         // send the last bits
         if i > 0 {
-            buf[i] = 0;
-            let status = self.write_raw(buf.as_ptr());
+            let status = write_ucs2(self, &mut buf, i);
             if status != Status::Success {
                 return status
             }
@@ -122,6 +402,35 @@ pub trait SimpleTextOutput {
     }
 }
 
+const REPLACEMENT_CHARACTER: u16 = 0xFFFD;
+
+/// Output the first `len` characters of `buf`, replacing those the device can't display. `buf`
+/// must have room for a terminator after them.
+fn write_ucs2<T: SimpleTextOutput + ?Sized>(output: &T, buf: &mut [u16], len: usize) -> Status {
+    buf[len] = 0;
+
+    if output.test_raw(buf.as_ptr()) != Status::Success {
+        let mut replacement = None;
+        for unit in buf[..len].iter_mut() {
+            let single = [*unit, 0];
+            if output.test_raw(single.as_ptr()) == Status::Success {
+                continue;
+            }
+
+            *unit = *replacement.get_or_insert_with(|| {
+                let single = [REPLACEMENT_CHARACTER, 0];
+                if output.test_raw(single.as_ptr()) == Status::Success {
+                    REPLACEMENT_CHARACTER
+                } else {
+                    '?' as u16
+                }
+            });
+        }
+    }
+
+    output.write_raw(buf.as_ptr())
+}
+
 pub trait SimpleTextInput {
     fn read_key_async(&self) -> Result<InputKey, Status>;
     fn read_key(&self) -> Result<InputKey, Status>;
@@ -131,6 +440,7 @@ pub struct Console {
     system_table: &'static systemtable::SystemTable,
     input: &'static SimpleTextInputProtocol,
     output: &'static SimpleTextOutputProtocol,
+    translate_newlines: bool,
 }
 
 /// Console is a thin wrapper around UEFI SimpleTextInputProtocol and SimpleTextOutputProtocol.
@@ -140,9 +450,15 @@ impl Console {
             system_table: st,
             input: input,
             output: out,
+            translate_newlines: true,
         }
     }
 
+    /// Choose whether `write` translates `\n` into `\r\n`; it does by default.
+    pub fn set_newline_translation(&mut self, translate: bool) {
+        self.translate_newlines = translate;
+    }
+
     pub fn reset(&self) -> Status {
         unsafe {
             (self.output.reset)(self.output, 1);
@@ -164,11 +480,51 @@ impl SimpleTextOutput for Console {
         status
     }
 
+    fn test_raw(&self, str: *const u16) -> Status {
+        unsafe { (self.output.test_string)(self.output, str) }
+    }
+
+    fn translate_newlines(&self) -> bool {
+        self.translate_newlines
+    }
+
     fn set_attribute(&self, attribute: Attribute) -> Status {
         unsafe {
             return (self.output.set_attribute)(self.output, attribute.to_efi_attribute());
         }
     }
+
+    fn clear_screen(&self) -> Status {
+        unsafe { (self.output.clear_screen)(self.output) }
+    }
+
+    fn query_mode(&self, mode: usize) -> Result<(usize, usize), Status> {
+        let mut columns: usize = 0;
+        let mut rows: usize = 0;
+
+        let status = unsafe { (self.output.query_mode)(self.output, mode, &mut columns, &mut rows) };
+        if status != Status::Success {
+            return Err(status);
+        }
+
+        Ok((columns, rows))
+    }
+
+    fn set_mode(&self, mode: usize) -> Status {
+        unsafe { (self.output.set_mode)(self.output, mode) }
+    }
+
+    fn set_cursor_position(&self, column: usize, row: usize) -> Status {
+        unsafe { (self.output.set_cursor_position)(self.output, column, row) }
+    }
+
+    fn enable_cursor(&self, visible: bool) -> Status {
+        unsafe { (self.output.enable_cursor)(self.output, visible as u8) }
+    }
+
+    fn mode(&self) -> SimpleTextOutputMode {
+        unsafe { *self.output.mode }
+    }
 }
 
 impl SimpleTextInput for Console {
@@ -0,0 +1,65 @@
+// Copyright 2017 CoreOS, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use base::{Handle, Status};
+use protocol::set_current_image;
+use systemtable::{set_system_table, SystemTable};
+
+/// Define the application's entry point, which initializes this library and then calls `$main`,
+/// a `fn(Handle, &SystemTable) -> Result<(), Status>`. Its result is returned to the firmware,
+/// with `Ok` as `Status::Success`.
+///
+/// The entry point is exported as `efi_main` when building for a `*-unknown-uefi` target, and as
+/// `efi_entry` otherwise, for linking with gnu-efi as described in the crate documentation.
+///
+/// ```rust,ignore
+/// #[macro_use]
+/// extern crate uefi;
+///
+/// fn main(image: uefi::Handle, st: &uefi::SystemTable) -> Result<(), uefi::Status> {
+///     println!("hello from {:?}", image);
+///     Ok(())
+/// }
+///
+/// efi_main!(main);
+/// ```
+#[macro_export]
+macro_rules! efi_main {
+    ($main:path) => {
+        #[cfg_attr(target_os = "uefi", export_name = "efi_main")]
+        #[cfg_attr(not(target_os = "uefi"), export_name = "efi_entry")]
+        pub extern "efiapi" fn efi_entry(image_handle: $crate::Handle,
+                                        system_table: *const $crate::SystemTable)
+                                        -> $crate::Status {
+            $crate::_efi_entry(image_handle, system_table, $main)
+        }
+    };
+}
+
+#[doc(hidden)]
+pub fn _efi_entry(image_handle: Handle, system_table: *const SystemTable,
+                  main: fn(Handle, &'static SystemTable) -> Result<(), Status>) -> Status {
+    let st = match set_system_table(system_table) {
+        Ok(st) => st,
+        Err(e) => return e,
+    };
+    if let Err(e) = set_current_image(image_handle) {
+        return e;
+    }
+
+    match main(image_handle, st) {
+        Ok(()) => Status::Success,
+        Err(e) => e,
+    }
+}
@@ -0,0 +1,250 @@
+// Copyright 2017 CoreOS, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The EFI System Resource Table, which lists the firmware components that can be updated with
+//! capsules.
+
+use core::{fmt, mem, ptr, slice};
+
+use base::Status;
+use guid::Guid;
+
+/// GUID for the ESRT in the system configuration table
+pub static EFI_SYSTEM_RESOURCE_TABLE_GUID: Guid = Guid(0xB122A263, 0x3661, 0x4F68, [0x99,0x29,0x78,0xF8,0xB0,0xD6,0x21,0x80]);
+
+/// The only ESRT format version defined so far.
+pub const ESRT_FIRMWARE_RESOURCE_VERSION: u64 = 1;
+
+/// The kind of component an ESRT entry describes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FirmwareType {
+    Unknown,
+    SystemFirmware,
+    DeviceFirmware,
+    UefiDriver,
+    /// A type defined after this crate was written.
+    Other(u32),
+}
+
+impl From<u32> for FirmwareType {
+    fn from(value: u32) -> FirmwareType {
+        match value {
+            0 => FirmwareType::Unknown,
+            1 => FirmwareType::SystemFirmware,
+            2 => FirmwareType::DeviceFirmware,
+            3 => FirmwareType::UefiDriver,
+            n => FirmwareType::Other(n),
+        }
+    }
+}
+
+/// The result of the last attempt to update a component.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LastAttemptStatus {
+    Success,
+    Unsuccessful,
+    InsufficientResources,
+    IncorrectVersion,
+    InvalidFormat,
+    AuthError,
+    PowerEventAc,
+    PowerEventBattery,
+    UnsatisfiedDependencies,
+    /// A vendor-specific error, in the range 0x1000 - 0x4000.
+    Vendor(u32),
+    /// A status defined after this crate was written.
+    Other(u32),
+}
+
+impl LastAttemptStatus {
+    pub fn is_success(&self) -> bool {
+        *self == LastAttemptStatus::Success
+    }
+
+    pub fn str(&self) -> &'static str {
+        match *self {
+            LastAttemptStatus::Success => "success",
+            LastAttemptStatus::Unsuccessful => "unsuccessful",
+            LastAttemptStatus::InsufficientResources => "insufficient resources",
+            LastAttemptStatus::IncorrectVersion => "incorrect version",
+            LastAttemptStatus::InvalidFormat => "invalid format",
+            LastAttemptStatus::AuthError => "authentication error",
+            LastAttemptStatus::PowerEventAc => "insufficient AC power",
+            LastAttemptStatus::PowerEventBattery => "insufficient battery",
+            LastAttemptStatus::UnsatisfiedDependencies => "unsatisfied dependencies",
+            LastAttemptStatus::Vendor(_) => "vendor error",
+            LastAttemptStatus::Other(_) => "unknown error",
+        }
+    }
+}
+
+impl From<u32> for LastAttemptStatus {
+    fn from(value: u32) -> LastAttemptStatus {
+        match value {
+            0 => LastAttemptStatus::Success,
+            1 => LastAttemptStatus::Unsuccessful,
+            2 => LastAttemptStatus::InsufficientResources,
+            3 => LastAttemptStatus::IncorrectVersion,
+            4 => LastAttemptStatus::InvalidFormat,
+            5 => LastAttemptStatus::AuthError,
+            6 => LastAttemptStatus::PowerEventAc,
+            7 => LastAttemptStatus::PowerEventBattery,
+            8 => LastAttemptStatus::UnsatisfiedDependencies,
+            n @ 0x1000..=0x4000 => LastAttemptStatus::Vendor(n),
+            n => LastAttemptStatus::Other(n),
+        }
+    }
+}
+
+impl fmt::Display for LastAttemptStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LastAttemptStatus::Vendor(n) | LastAttemptStatus::Other(n) => write!(f, "{} {:#x}", self.str(), n),
+            _ => write!(f, "{}", self.str()),
+        }
+    }
+}
+
+/// Type for EFI_SYSTEM_RESOURCE_ENTRY, describing one updatable component.
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct EsrtEntry {
+    /// The component's firmware class, which is also the GUID of capsules that update it.
+    pub fw_class: Guid,
+    fw_type: u32,
+    pub fw_version: u32,
+    pub lowest_supported_fw_version: u32,
+    /// Capsule flags the component's update capsules must have set.
+    pub capsule_flags: u32,
+    pub last_attempt_version: u32,
+    last_attempt_status: u32,
+}
+
+impl EsrtEntry {
+    pub fn fw_type(&self) -> FirmwareType {
+        FirmwareType::from(self.fw_type)
+    }
+
+    pub fn last_attempt_status(&self) -> LastAttemptStatus {
+        LastAttemptStatus::from(self.last_attempt_status)
+    }
+}
+
+/// Size of the table header before the entries.
+const ESRT_HEADER_SIZE: usize = 16;
+
+/// A decoded EFI_SYSTEM_RESOURCE_TABLE.
+#[derive(Clone, Copy, Debug)]
+pub struct Esrt<'a> {
+    /// The number of entries the firmware has room for.
+    pub fw_resource_count_max: u32,
+    entries: &'a [u8],
+}
+
+impl<'a> Esrt<'a> {
+    /// Decode an ESRT from its raw bytes, which may run past the end of the table.
+    pub fn parse(data: &'a [u8]) -> Result<Esrt<'a>, Status> {
+        if data.len() < ESRT_HEADER_SIZE {
+            return Err(Status::InvalidParameter);
+        }
+
+        let count = unsafe { ptr::read_unaligned(data.as_ptr() as *const u32) } as usize;
+        let count_max = unsafe { ptr::read_unaligned(data.as_ptr().offset(4) as *const u32) };
+        let version = unsafe { ptr::read_unaligned(data.as_ptr().offset(8) as *const u64) };
+        if version != ESRT_FIRMWARE_RESOURCE_VERSION {
+            return Err(Status::IncompatibleVersion);
+        }
+
+        if count > count_max as usize {
+            return Err(Status::InvalidParameter);
+        }
+        let size = match table_size(count) {
+            Some(size) if size <= data.len() => size,
+            _ => return Err(Status::InvalidParameter),
+        };
+
+        Ok(Esrt {
+            fw_resource_count_max: count_max,
+            entries: &data[ESRT_HEADER_SIZE..size],
+        })
+    }
+
+    /// The number of entries in the table.
+    pub fn len(&self) -> usize {
+        self.entries.len() / mem::size_of::<EsrtEntry>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> EsrtEntries<'a> {
+        EsrtEntries { bytes: self.entries }
+    }
+
+    /// Find the entry for firmware class `fw_class`.
+    pub fn find(&self, fw_class: &Guid) -> Option<EsrtEntry> {
+        self.entries().find(|entry| entry.fw_class == *fw_class)
+    }
+}
+
+/// Iterator over the entries of an ESRT.
+#[derive(Clone, Debug)]
+pub struct EsrtEntries<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Iterator for EsrtEntries<'a> {
+    type Item = EsrtEntry;
+
+    fn next(&mut self) -> Option<EsrtEntry> {
+        let size = mem::size_of::<EsrtEntry>();
+        if self.bytes.len() < size {
+            return None;
+        }
+
+        let entry = unsafe { ptr::read_unaligned(self.bytes.as_ptr() as *const EsrtEntry) };
+        self.bytes = &self.bytes[size..];
+        Some(entry)
+    }
+}
+
+/// Find the ESRT in the system configuration table. Returns `Status::NotFound` on firmware
+/// without one.
+pub fn esrt() -> Result<Esrt<'static>, Status> {
+    let table = match ::get_system_table().find_configuration_table(&EFI_SYSTEM_RESOURCE_TABLE_GUID) {
+        Some(table) if !table.is_null() => table as *const u8,
+        _ => return Err(Status::NotFound),
+    };
+
+    // Only trust the count as far as the table's own maximum, and don't build a slice over the
+    // table before checking it.
+    let count = unsafe { ptr::read_unaligned(table as *const u32) } as usize;
+    let count_max = unsafe { ptr::read_unaligned(table.offset(4) as *const u32) } as usize;
+    if count > count_max {
+        return Err(Status::InvalidParameter);
+    }
+    let size = match table_size(count) {
+        Some(size) => size,
+        None => return Err(Status::InvalidParameter),
+    };
+
+    Esrt::parse(unsafe { slice::from_raw_parts(table, size) })
+}
+
+/// The size of a table holding `count` entries, if it fits in a `usize`.
+fn table_size(count: usize) -> Option<usize> {
+    count.checked_mul(mem::size_of::<EsrtEntry>())
+        .and_then(|size| size.checked_add(ESRT_HEADER_SIZE))
+}
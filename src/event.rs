@@ -18,4 +18,4 @@ pub enum TimerDelay {
     Relative = 2
 }
 
-pub type EventNotify = extern "win64" fn(event: Event, context: *const CVoid);
+pub type EventNotify = extern "efiapi" fn(event: Event, context: *const CVoid);
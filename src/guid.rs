@@ -1,10 +1,125 @@
 use core::fmt;
+use core::str::FromStr;
+
+use base::Status;
+use protocol::RngProtocol;
+
+/// Construct a `Guid` at compile time from its canonical string form, such as
+/// `guid!("8be4df61-93ca-11d2-aa0d-00e098032b8c")`. Invalid strings fail to compile.
+#[macro_export]
+macro_rules! guid {
+    ($s:expr) => {{
+        const GUID: $crate::Guid = $crate::Guid::parse_or_panic($s);
+        GUID
+    }};
+}
 
 /// Type for EFI_GUID.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[repr(C)]
 pub struct Guid(pub u32, pub u16, pub u16, pub [u8; 8]);
 
+const fn hex_digit(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+impl Guid {
+    /// Read a GUID in the mixed-endian format used in memory and on disk, where the first three
+    /// fields are little-endian.
+    pub const fn from_bytes(b: [u8; 16]) -> Guid {
+        Guid(u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+             u16::from_le_bytes([b[4], b[5]]),
+             u16::from_le_bytes([b[6], b[7]]),
+             [b[8], b[9], b[10], b[11], b[12], b[13], b[14], b[15]])
+    }
+
+    /// The GUID in its mixed-endian wire format; see `from_bytes`.
+    pub const fn to_bytes(&self) -> [u8; 16] {
+        let a = self.0.to_le_bytes();
+        let b = self.1.to_le_bytes();
+        let c = self.2.to_le_bytes();
+        let d = self.3;
+        [a[0], a[1], a[2], a[3], b[0], b[1], c[0], c[1], d[0], d[1], d[2], d[3], d[4], d[5], d[6], d[7]]
+    }
+
+    /// Parse the canonical form, e.g. `8be4df61-93ca-11d2-aa0d-00e098032b8c`, in either case.
+    /// Returns `Status::InvalidParameter` for anything else.
+    pub const fn parse(s: &str) -> Result<Guid, Status> {
+        let s = s.as_bytes();
+        if s.len() != 36 {
+            return Err(Status::InvalidParameter);
+        }
+
+        // the bytes in the order they're written
+        let mut b = [0u8; 16];
+        let mut i = 0;
+        let mut n = 0;
+        while i < s.len() {
+            if i == 8 || i == 13 || i == 18 || i == 23 {
+                if s[i] != b'-' {
+                    return Err(Status::InvalidParameter);
+                }
+                i += 1;
+                continue;
+            }
+
+            match (hex_digit(s[i]), hex_digit(s[i + 1])) {
+                (Some(hi), Some(lo)) => b[n] = (hi << 4) | lo,
+                _ => return Err(Status::InvalidParameter),
+            }
+            i += 2;
+            n += 1;
+        }
+
+        Ok(Guid(u32::from_be_bytes([b[0], b[1], b[2], b[3]]),
+                u16::from_be_bytes([b[4], b[5]]),
+                u16::from_be_bytes([b[6], b[7]]),
+                [b[8], b[9], b[10], b[11], b[12], b[13], b[14], b[15]]))
+    }
+
+    #[doc(hidden)]
+    pub const fn parse_or_panic(s: &str) -> Guid {
+        match Guid::parse(s) {
+            Ok(guid) => guid,
+            Err(_) => panic!("invalid GUID"),
+        }
+    }
+
+    /// Make a version 4 GUID from 16 random bytes, by setting its version and variant bits.
+    pub fn from_random_bytes(mut b: [u8; 16]) -> Guid {
+        b[6] = (b[6] & 0x0F) | 0x40;
+        b[8] = (b[8] & 0x3F) | 0x80;
+
+        Guid(u32::from_be_bytes([b[0], b[1], b[2], b[3]]),
+             u16::from_be_bytes([b[4], b[5]]),
+             u16::from_be_bytes([b[6], b[7]]),
+             [b[8], b[9], b[10], b[11], b[12], b[13], b[14], b[15]])
+    }
+
+    /// Generate a random (version 4) GUID using the firmware's RNG protocol. Returns
+    /// `Status::NotFound` if there isn't one.
+    pub fn new_v4() -> Result<Guid, Status> {
+        let rng = ::get_system_table().boot_services().locate_protocol::<RngProtocol>(::core::ptr::null())?;
+        let mut bytes = [0u8; 16];
+        rng.get_rng(None, &mut bytes)?;
+
+        Ok(Guid::from_random_bytes(bytes))
+    }
+}
+
+impl FromStr for Guid {
+    type Err = Status;
+
+    fn from_str(s: &str) -> Result<Guid, Status> {
+        Guid::parse(s)
+    }
+}
+
 impl fmt::Display for Guid {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}", self.0,
@@ -14,4 +129,3 @@ impl fmt::Display for Guid {
                self.3[2], self.3[3], self.3[4], self.3[5], self.3[6], self.3[7])
     }
 }
-
@@ -8,13 +8,32 @@
 //!
 //! ## Usage
 //!
-//! Building Rust applications for UEFI is a bit tricky. Rust doesn't support outputting binaries
-//! in the EFI application format, so the best way to build Rust EFI applications is to use the
-//! Rust tooling to build an object file which is linked and formatted by another tool. The easiest
-//! way to do this is to use a Makefile which builds the application's object file via cargo, links
-//! it as if it were a gnuefi application, and copies it to the correct format. For example:
+//! Rust targets UEFI directly with `x86_64-unknown-uefi`, `i686-unknown-uefi` and
+//! `aarch64-unknown-uefi`, which produce EFI applications without any other tooling:
 //!
-//! ### Example Makefile
+//! ```text
+//! rustup target add x86_64-unknown-uefi
+//! cargo build --release --target x86_64-unknown-uefi
+//! ```
+//!
+//! The application is written to `target/x86_64-unknown-uefi/release/APP_NAME.efi`. It should be
+//! `#![no_std]` and `#![no_main]`, and needs a panic handler: either enable this crate's
+//! `panic-handler` feature, or define one and call [handle_panic] from it.
+//!
+//! Functions called by the firmware use the `efiapi` calling convention, which is `win64` on
+//! x86_64 and the C calling convention elsewhere. Callbacks handed to this crate, such as event
+//! notification functions, must be declared `extern "efiapi"`.
+//!
+//! [handle_panic]: fn.handle_panic.html
+//!
+//! ### gnu-efi
+//!
+//! With toolchains which lack these targets, the application can be built as an object file which
+//! is linked and formatted by another tool. The easiest way to do this is to use a Makefile which
+//! builds the application's object file via cargo, links it as if it were a gnuefi application,
+//! and copies it to the correct format. For example:
+//!
+//! #### Example Makefile
 //!
 //! ```text
 //! LIB_DIR ?= /usr/lib64
@@ -54,23 +73,42 @@
 //!
 //! ### Application Structure
 //!
-//! Something you'll notice in the above Makefile is the use of the `-e efi_entry` flag during
-//! linking. This specifies that `efi_entry` should be the entry point to the application. To that
-//! end, your application should provide that entrypoint somewhere. Its signature looks like this:
+//! The `*-unknown-uefi` targets expect the application's entry point to be named `efi_main`.
+//! When using gnu-efi, the `-e efi_entry` flag in the above Makefile names it `efi_entry`
+//! instead. The `efi_main!` macro defines the right one for the target, initializes this library,
+//! and calls your application's main function:
+//!
+//! ```rust,ignore
+//! #[macro_use]
+//! extern crate uefi;
+//!
+//! fn main(image_handle: uefi::Handle, system_table: &uefi::SystemTable)
+//!         -> Result<(), uefi::Status> {
+//!     Ok(())
+//! }
+//!
+//! efi_main!(main);
+//! ```
+//!
+//! The error returned by `main`, or `Status::Success`, is returned to the firmware.
+//!
+//! To write the entry point by hand instead, its signature looks like this, named `efi_main`
+//! for the `*-unknown-uefi` targets:
 //!
 //! ```rust,ignore
 //! #[no_mangle]
-//! pub extern "win64" fn efi_entry(image_handle: uefi::Handle,
+//! pub extern "efiapi" fn efi_entry(image_handle: uefi::Handle,
 //!                                 system_table: *const uefi::SystemTable)
-//!                                 -> isize
+//!                                 -> uefi::Status
 //! ```
 //!
-//! At this point, your application should initialize this library using the two arguments it
-//! receives:
+//! and it should initialize this library using the two arguments it receives:
 //!
 //! ```rust,ignore
-//! uefi::set_system_table(system_table);
-//! uefi::protocol::set_current_image(image_handle);
+//! if let Err(e) = uefi::set_system_table(system_table)
+//!     .and_then(|_| uefi::protocol::set_current_image(image_handle)) {
+//!     return e;
+//! }
 //! ```
 //!
 //! See [set_system_table] and [set_current_image].
@@ -78,16 +116,28 @@
 //! [set_system_table]: fn.set_system_table.html
 //! [set_current_image]: protocol/fn.set_current_image.html
 //!
+//! Once the system table is set, the `print!`, `println!`, `eprint!` and `eprintln!` macros write
+//! to the console, after `#[macro_use] extern crate uefi;`. See [set_print_sink] for output after
+//! `exit_boot_services`.
+//!
+//! [set_print_sink]: fn.set_print_sink.html
+//!
 
 #![allow(dead_code)]
 #![no_std]
 
 #[macro_use] extern crate bitflags;
+#[cfg(feature = "log")]
+extern crate log;
+#[cfg(feature = "mock")]
+#[macro_use] extern crate std;
 
+#[macro_use] mod print;
+#[macro_use] mod entry;
+#[macro_use] mod guid;
 pub mod protocol;
 mod void;
 mod base;
-mod guid;
 mod table;
 mod systemtable;
 mod bootservices;
@@ -95,23 +145,57 @@ mod runtimeservices;
 mod console;
 mod task;
 mod event;
+mod time;
+mod runtime;
+mod capsule;
+mod esrt;
+mod readline;
+mod panic;
 pub mod util;
+pub mod variable;
+#[cfg(feature = "log")]
+pub mod logger;
+#[cfg(feature = "mock")]
+pub mod mock;
 
 
-pub use base::{Handle, Handles, Event, MemoryType, Status, Time};
+pub use base::{Handle, Handles, Event, MemoryType, Status, Result, Time, TimeCapabilities,
+               UNSPECIFIED_TIMEZONE, TIME_ADJUST_DAYLIGHT, TIME_IN_DAYLIGHT};
+pub use base::{MemoryAttribute, MemoryDescriptor, MEMORY_UC, MEMORY_WC, MEMORY_WT, MEMORY_WB,
+               MEMORY_UCE, MEMORY_WP, MEMORY_RP, MEMORY_XP, MEMORY_NV, MEMORY_MORE_RELIABLE,
+               MEMORY_RO, MEMORY_RUNTIME};
 pub use guid::*;
 
 pub use systemtable::*;
 
-pub use bootservices::BootServices;
+pub use bootservices::{AllocateType, BootServices, MemoryMap, PAGE_SIZE, boot_services_exited};
+
+pub use runtimeservices::*;
+
+pub use console::{Attribute, ForegroundColor, BackgroundColor, InputKey, Key, SimpleTextOutput, SimpleTextOutputMode, TextMode, TextModes, SimpleTextInput, Console};
+
+pub use readline::{History, LineEditor};
 
-pub use runtimeservices::{ResetType, RuntimeServices};
+pub use print::{set_print_sink, _print, _eprint};
 
-pub use console::{Attribute, ForegroundColor, BackgroundColor, InputKey, SimpleTextOutput, SimpleTextInput, Console};
+pub use entry::_efi_entry;
+
+pub use panic::{PanicAction, PanicConfig, handle_panic, panic_config, set_panic_config};
 
 pub use event::*;
 
 pub use task::*;
 
+pub use time::Rfc3339;
+
+pub use runtime::*;
+
+pub use capsule::*;
+
+pub use esrt::*;
+
+pub use table::{crc32, ConfigurationTable, Revision, TableHeader, EFI_SYSTEM_TABLE_SIGNATURE,
+                EFI_BOOT_SERVICES_SIGNATURE, EFI_RUNTIME_SERVICES_SIGNATURE};
+
 pub use void::CVoid;
 
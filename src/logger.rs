@@ -0,0 +1,322 @@
+// Copyright 2017 CoreOS, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A backend for the `log` crate, enabled by the `log` feature. Records can go to the console,
+//! colored by level, to a serial port and to an in-memory ring buffer, which keeps working after
+//! `exit_boot_services` and can be dumped later or handed to the OS.
+//!
+//! ```rust,ignore
+//! static mut LOG_BUFFER: [u8; 16384] = [0; 16384];
+//!
+//! let mut logger = uefi::logger::Logger::new();
+//! logger.set_serial(uefi::protocol::SerialIOProtocol::new().ok());
+//! logger.set_ring_buffer(Some(unsafe { &mut *core::ptr::addr_of_mut!(LOG_BUFFER) }));
+//! if let Ok(level) = uefi::logger::level_from_variable(&name, &vendor) {
+//!     logger.set_level(level);
+//! }
+//! uefi::logger::init(logger).unwrap();
+//! ```
+
+use core::cell::UnsafeCell;
+use core::fmt::{self, Write};
+use core::{ptr, str};
+
+use log::{self, Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+use base::Status;
+use bootservices::boot_services_exited;
+use console::{ForegroundColor, SimpleTextOutput};
+use guid::Guid;
+use protocol::SerialIOProtocol;
+use systemtable::SYSTEM_TABLE;
+use task::TPL;
+use util::CStr16;
+
+/// A byte buffer which overwrites its oldest contents once full.
+pub struct RingBuffer<'a> {
+    buf: &'a mut [u8],
+    start: usize,
+    len: usize,
+}
+
+impl<'a> RingBuffer<'a> {
+    pub fn new(buf: &'a mut [u8]) -> RingBuffer<'a> {
+        RingBuffer { buf, start: 0, len: 0 }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// The number of bytes held.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.start = 0;
+        self.len = 0;
+    }
+
+    /// Append `bytes`, dropping the oldest bytes if there isn't room.
+    pub fn push(&mut self, bytes: &[u8]) {
+        let capacity = self.buf.len();
+        if capacity == 0 {
+            return;
+        }
+
+        for &b in bytes {
+            self.buf[(self.start + self.len) % capacity] = b;
+            if self.len == capacity {
+                self.start = (self.start + 1) % capacity;
+            } else {
+                self.len += 1;
+            }
+        }
+    }
+
+    /// The contents, oldest first, in two parts since they may wrap around the end of the buffer.
+    /// After wrapping, the first part may begin in the middle of a record, or of a character.
+    pub fn contents(&self) -> (&[u8], &[u8]) {
+        let capacity = self.buf.len();
+        if self.start + self.len <= capacity {
+            (&self.buf[self.start..self.start + self.len], &[])
+        } else {
+            (&self.buf[self.start..], &self.buf[..self.start + self.len - capacity])
+        }
+    }
+
+    /// Copy the contents, oldest first, into `out`, and return the number of bytes copied. If
+    /// `out` is too small, the most recent bytes are left out.
+    pub fn copy_to(&self, out: &mut [u8]) -> usize {
+        let (first, second) = self.contents();
+        let n = first.len().min(out.len());
+        out[..n].copy_from_slice(&first[..n]);
+        let m = second.len().min(out.len() - n);
+        out[n..n + m].copy_from_slice(&second[..m]);
+        n + m
+    }
+}
+
+impl<'a> Write for RingBuffer<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push(s.as_bytes());
+        Ok(())
+    }
+}
+
+struct SerialWriter<'a>(&'a SerialIOProtocol);
+
+impl<'a> Write for SerialWriter<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write(s).map(|_| ()).map_err(|_| fmt::Error)
+    }
+}
+
+/// A logger writing to any of the console, a serial port and a ring buffer. Install it with
+/// `init`.
+pub struct Logger {
+    level: LevelFilter,
+    console: bool,
+    serial: Option<SerialIOProtocol>,
+    ring_buffer: Option<UnsafeCell<RingBuffer<'static>>>,
+}
+
+// UEFI applications run on a single processor. Records logged from event notification functions
+// while another record is being written may be interleaved with it.
+unsafe impl Sync for Logger {}
+unsafe impl Send for Logger {}
+
+impl Default for Logger {
+    fn default() -> Logger {
+        Logger::new()
+    }
+}
+
+impl Logger {
+    /// Create a logger which writes records at `Level::Info` and above to the console.
+    pub fn new() -> Logger {
+        Logger {
+            level: LevelFilter::Info,
+            console: true,
+            serial: None,
+            ring_buffer: None,
+        }
+    }
+
+    pub fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    pub fn set_level(&mut self, level: LevelFilter) {
+        self.level = level;
+    }
+
+    /// Whether to write to the console's standard output.
+    pub fn set_console(&mut self, console: bool) {
+        self.console = console;
+    }
+
+    /// Write records to `serial` as well.
+    pub fn set_serial(&mut self, serial: Option<SerialIOProtocol>) {
+        self.serial = serial;
+    }
+
+    /// Keep records in `buf`, overwriting the oldest once it is full.
+    pub fn set_ring_buffer(&mut self, buf: Option<&'static mut [u8]>) {
+        self.ring_buffer = buf.map(|buf| UnsafeCell::new(RingBuffer::new(buf)));
+    }
+
+    /// Copy the records kept in the ring buffer, oldest first, into `out`, and return the number
+    /// of bytes copied; see `RingBuffer::copy_to`. Without a ring buffer, nothing is copied.
+    pub fn copy_ring_buffer(&self, out: &mut [u8]) -> usize {
+        self.with_ring_buffer(|ring| ring.copy_to(out)).unwrap_or(0)
+    }
+
+    /// Run `f` on the ring buffer, if there is one. While boot services are available, the TPL is
+    /// raised so that records logged from event notification functions can't interleave.
+    fn with_ring_buffer<R, F: FnOnce(&mut RingBuffer<'static>) -> R>(&self, f: F) -> Option<R> {
+        let ring = self.ring_buffer.as_ref()?;
+
+        let st = unsafe { SYSTEM_TABLE };
+        if st.is_null() || boot_services_exited() {
+            return Some(f(unsafe { &mut *ring.get() }));
+        }
+
+        // the highest level, since records may be logged at any level
+        let bs = unsafe { &*st }.boot_services();
+        let old_tpl = bs.raise_tpl(TPL::HighLevel);
+        let result = f(unsafe { &mut *ring.get() });
+        bs.restore_tpl(old_tpl);
+        Some(result)
+    }
+}
+
+fn level_color(level: Level) -> ForegroundColor {
+    match level {
+        Level::Error => ForegroundColor::LightRed,
+        Level::Warn => ForegroundColor::Yellow,
+        Level::Info => ForegroundColor::LightGreen,
+        Level::Debug => ForegroundColor::LightBlue,
+        Level::Trace => ForegroundColor::DarkGray,
+    }
+}
+
+fn write_record<W: Write>(w: &mut W, record: &Record, newline: &str) -> fmt::Result {
+    write!(w, "{:5} {}: {}{}", record.level(), record.target(), record.args(), newline)
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let _ = self.with_ring_buffer(|ring| write_record(ring, record, "\n"));
+
+        // the console and serial port are boot services
+        let st = unsafe { SYSTEM_TABLE };
+        if st.is_null() || boot_services_exited() {
+            return;
+        }
+
+        if self.console {
+            let mut console = unsafe { &*st }.console();
+            let attribute = console.mode().attribute();
+            console.set_attribute(attribute.with_foreground(level_color(record.level())));
+            let _ = write!(console, "{:5}", record.level());
+            console.set_attribute(attribute);
+            let _ = writeln!(console, " {}: {}", record.target(), record.args());
+        }
+
+        if let Some(ref serial) = self.serial {
+            let _ = write_record(&mut SerialWriter(serial), record, "\r\n");
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static mut LOGGER: Option<Logger> = None;
+
+/// Install `logger` as the `log` crate's logger, and set the maximum level to its level. Fails if
+/// a logger has already been installed.
+pub fn init(logger: Logger) -> Result<(), SetLoggerError> {
+    let installed = unsafe { &mut *ptr::addr_of_mut!(LOGGER) };
+    if let Some(ref existing) = *installed {
+        // already installed, so this fails
+        return log::set_logger(existing);
+    }
+
+    let level = logger.level;
+    let logger = installed.get_or_insert(logger);
+    log::set_logger(logger)?;
+    log::set_max_level(level);
+
+    Ok(())
+}
+
+/// The logger installed by `init`, if any.
+pub fn logger() -> Option<&'static Logger> {
+    unsafe { (*ptr::addr_of!(LOGGER)).as_ref() }
+}
+
+/// Parse a level filter as stored in a variable: either a single byte from 0 (`Off`) to 5
+/// (`Trace`), or a level name such as `debug` in ASCII or UCS-2, optionally null-terminated.
+pub fn parse_level(bytes: &[u8]) -> Option<LevelFilter> {
+    if bytes.len() == 1 {
+        return match bytes[0] {
+            0 => Some(LevelFilter::Off),
+            1 => Some(LevelFilter::Error),
+            2 => Some(LevelFilter::Warn),
+            3 => Some(LevelFilter::Info),
+            4 => Some(LevelFilter::Debug),
+            5 => Some(LevelFilter::Trace),
+            _ => None,
+        };
+    }
+
+    let mut name = [0u8; 8];
+    let mut len = 0;
+    let step = if bytes.len() >= 2 && bytes[1] == 0 { 2 } else { 1 };
+    for c in bytes.chunks(step) {
+        if c[0] == 0 {
+            break;
+        }
+        if len == name.len() || c.iter().skip(1).any(|&b| b != 0) {
+            return None;
+        }
+        name[len] = c[0];
+        len += 1;
+    }
+
+    str::from_utf8(&name[..len]).ok().and_then(|name| name.trim().parse().ok())
+}
+
+/// Read a level filter from the variable `name`, in any format accepted by `parse_level`.
+/// Returns `Status::InvalidParameter` if the contents aren't a level.
+pub fn level_from_variable(name: &CStr16, vendor: &Guid) -> Result<LevelFilter, Status> {
+    let mut buf = [0u8; 16];
+    let (size, _) = ::get_system_table().runtime_services().get_variable(name, vendor, &mut buf)?;
+
+    parse_level(&buf[..size]).ok_or(Status::InvalidParameter)
+}
@@ -0,0 +1,1074 @@
+// Copyright 2017 CoreOS, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Mock firmware for testing code which uses this crate on the host, enabled by the `mock`
+//! feature.
+//!
+//! `MockFirmware::new` builds a system table whose boot and runtime services are implemented in
+//! Rust, and installs it as if `set_system_table` and `set_current_image` had been called. It
+//! provides a pool and page allocator backed by the host's, a variable store, a console which
+//! replays scripted input and records output, and a protocol database.
+//!
+//! ```rust,ignore
+//! let firmware = uefi::mock::MockFirmware::new();
+//! firmware.push_input("y\r");
+//! run_installer(firmware.system_table());
+//! assert!(firmware.output().contains("Installed"));
+//! ```
+//!
+//! The system table is global, so only one `MockFirmware` exists at a time; others wait in `new`
+//! until it is dropped. Services the mock doesn't implement return `Status::Unsupported`. Since
+//! `reset_system` can't return, it records the reset and parks the calling thread for good; run
+//! code which resets in a thread of its own and wait for `MockFirmware::reset` to report it.
+
+use std::alloc::{self, Layout};
+use std::boxed::Box;
+use std::char;
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::mem;
+use std::ptr;
+use std::slice;
+use std::string::String;
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use std::vec::Vec;
+
+use base::{Event, Handle, MemoryDescriptor, MemoryType, Status, Time, TimeCapabilities, MEMORY_RUNTIME};
+use bootservices::{self, AllocateType, BootServices, LocateSearchType, PAGE_SIZE};
+use console::{InputKey, SimpleTextInputProtocol, SimpleTextOutputMode, SimpleTextOutputProtocol};
+use event::{EventNotify, EventType, TimerDelay};
+use guid::Guid;
+use protocol::{self, DevicePathProtocol, KeyData, KeyNotifyFunction, KeyState, LoadedImageProtocol, Protocol,
+               SimpleTextInputExProtocol};
+use runtimeservices::{ResetType, RuntimeServices, VariableAttributes, VARIABLE_APPEND_WRITE};
+use systemtable::{self, SystemTable};
+use table::{ConfigurationTable, Revision};
+use task::TPL;
+use capsule::CapsuleHeader;
+use void::CVoid;
+
+/// The specification revision the mock reports.
+pub(crate) const REVISION: Revision = Revision::new(2, 7, 0);
+
+/// The firmware revision the mock reports.
+pub const FIRMWARE_REVISION: u32 = 0x0001_0000;
+
+/// The vendor name the mock reports.
+pub const FIRMWARE_VENDOR: &str = "Mock firmware";
+
+/// The size of the console, in columns and rows.
+pub const CONSOLE_SIZE: (usize, usize) = (80, 25);
+
+/// The size of the region of conventional memory in the mock's memory map.
+const CONVENTIONAL_MEMORY_PAGES: u64 = 0x1000;
+
+/// The size of each descriptor in the mock's memory map. Like real firmware, the mock pads
+/// them beyond `MemoryDescriptor`, so callers have to step through by `descriptor_size`.
+pub const DESCRIPTOR_SIZE: usize = mem::size_of::<MemoryDescriptor>() + 8;
+
+struct Variable {
+    // null-terminated
+    name: Vec<u16>,
+    vendor: Guid,
+    attributes: u32,
+    data: Vec<u8>,
+}
+
+struct ProtocolEntry {
+    handle: Handle,
+    guid: Guid,
+    interface: *mut CVoid,
+}
+
+struct KeyNotifyEntry {
+    handle: *const CVoid,
+    key: KeyData,
+    notify: KeyNotifyFunction,
+}
+
+struct State {
+    pool: Vec<(usize, Layout)>,
+    pages: Vec<(usize, Layout)>,
+    variables: Vec<Variable>,
+    protocols: Vec<ProtocolEntry>,
+    handles: usize,
+    events: usize,
+    keys: VecDeque<InputKey>,
+    key_notifications: Vec<KeyNotifyEntry>,
+    stdout: String,
+    stderr: String,
+    stderr_protocol: *const SimpleTextOutputProtocol,
+    mode: *mut SimpleTextOutputMode,
+    time: Time,
+    map_key: usize,
+    monotonic_count: u32,
+    stalled: u64,
+    exit: Option<(Status, String)>,
+    reset: Option<(ResetType, Status)>,
+    reset_data: Vec<u8>,
+    capsule_capabilities: Result<(u64, u32), Status>,
+    capsules: Vec<(Vec<CapsuleHeader>, u64)>,
+    configuration_tables: Vec<ConfigurationTable>,
+    tpl: usize,
+}
+
+// The raw pointers are only used while the firmware lock is held.
+unsafe impl Send for State {}
+
+/// Serializes users of the mock firmware, since the system table is global.
+static INSTALLED: Mutex<()> = Mutex::new(());
+static STATE: Mutex<Option<State>> = Mutex::new(None);
+
+fn with_state<R, F: FnOnce(&mut State) -> R>(f: F) -> R {
+    try_with_state(f).expect("mock firmware is not installed")
+}
+
+fn try_with_state<R, F: FnOnce(&mut State) -> R>(f: F) -> Option<R> {
+    let mut state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+    state.as_mut().map(f)
+}
+
+/// Run a closure on the state from one of the firmware's services, which returns
+/// `Status::NotReady` if the `MockFirmware` has been dropped. Services can still be reached
+/// through pointers kept from its tables, and mustn't panic across the ABI.
+macro_rules! service_state {
+    ($f:expr) => {
+        match try_with_state($f) {
+            Some(result) => result,
+            None => return Status::NotReady,
+        }
+    }
+}
+
+fn fake_pointer(n: usize) -> *mut CVoid {
+    // never dereferenced, only compared
+    (0x1000 + n * 0x10) as *mut CVoid
+}
+
+/// The event signalled by the console's input device.
+const KEY_EVENT: usize = 0;
+
+/// Read a null-terminated UCS-2 string, including the terminator.
+unsafe fn read_ucs2(s: *const u16) -> Vec<u16> {
+    let mut v = Vec::new();
+    let mut i = 0;
+    loop {
+        let c = *s.add(i);
+        v.push(c);
+        if c == 0 {
+            return v;
+        }
+        i += 1;
+    }
+}
+
+fn ucs2_to_string(s: &[u16]) -> String {
+    char::decode_utf16(s.iter().cloned().take_while(|&c| c != 0))
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+fn str_to_ucs2(s: &str) -> Vec<u16> {
+    let mut v: Vec<u16> = s.encode_utf16().collect();
+    v.push(0);
+    v
+}
+
+/// A fake system table, installed as this crate's until dropped.
+pub struct MockFirmware {
+    system_table: *mut SystemTable,
+    boot_services: *mut BootServices,
+    runtime_services: *mut RuntimeServices,
+    con_in: *mut SimpleTextInputProtocol,
+    con_in_ex: *mut SimpleTextInputExProtocol,
+    con_out: *mut SimpleTextOutputProtocol,
+    std_err: *mut SimpleTextOutputProtocol,
+    mode: *mut SimpleTextOutputMode,
+    loaded_image: *mut LoadedImageProtocol,
+    vendor: *mut [u16],
+    image_handle: Handle,
+    _installed: MutexGuard<'static, ()>,
+}
+
+impl Default for MockFirmware {
+    fn default() -> MockFirmware {
+        MockFirmware::new()
+    }
+}
+
+impl MockFirmware {
+    /// Build the mock firmware and install it, waiting for any other instance to be dropped
+    /// first.
+    pub fn new() -> MockFirmware {
+        let installed = INSTALLED.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut mode = SimpleTextOutputMode::default();
+        mode.max_mode = 1;
+        mode.set_attribute(0x07);
+        mode.set_cursor_visible(1);
+        let mode = Box::into_raw(Box::new(mode));
+        let con_out = Box::into_raw(Box::new(SimpleTextOutputProtocol::mock(mode)));
+        let std_err = Box::into_raw(Box::new(SimpleTextOutputProtocol::mock(mode)));
+        let con_in = Box::into_raw(Box::new(SimpleTextInputProtocol::mock(Event(fake_pointer(KEY_EVENT)))));
+        let con_in_ex = Box::into_raw(Box::new(SimpleTextInputExProtocol::mock(Event(fake_pointer(KEY_EVENT)))));
+
+        let boot_services = Box::into_raw(Box::new(BootServices::mock()));
+        let runtime_services = Box::into_raw(Box::new(RuntimeServices::mock()));
+        let vendor = Box::into_raw(str_to_ucs2(FIRMWARE_VENDOR).into_boxed_slice());
+
+        let image_handle = Handle::new(fake_pointer(0));
+        let con_in_handle = Handle::new(fake_pointer(1));
+        let con_out_handle = Handle::new(fake_pointer(2));
+        let std_err_handle = Handle::new(fake_pointer(3));
+
+        let system_table = unsafe {
+            Box::into_raw(Box::new(SystemTable::mock(
+                (*vendor).as_ptr(),
+                (con_in_handle, &*con_in),
+                (con_out_handle, &*con_out),
+                (std_err_handle, &*std_err),
+                &*runtime_services,
+                &*boot_services,
+            )))
+        };
+        unsafe { (*system_table).update_crc32() };
+
+        let loaded_image = Box::into_raw(Box::new(LoadedImageProtocol::mock(system_table)));
+
+        *STATE.lock().unwrap_or_else(|e| e.into_inner()) = Some(State {
+            pool: Vec::new(),
+            pages: Vec::new(),
+            variables: Vec::new(),
+            protocols: vec![ProtocolEntry {
+                handle: image_handle,
+                guid: *LoadedImageProtocol::guid(),
+                interface: loaded_image as *mut CVoid,
+            }, ProtocolEntry {
+                handle: con_in_handle,
+                guid: *SimpleTextInputExProtocol::guid(),
+                interface: con_in_ex as *mut CVoid,
+            }],
+            // the image and console handles
+            handles: 4,
+            events: 1,
+            keys: VecDeque::new(),
+            key_notifications: Vec::new(),
+            stdout: String::new(),
+            stderr: String::new(),
+            stderr_protocol: std_err,
+            mode,
+            time: Time::new(2017, 1, 1, 0, 0, 0).unwrap_or_default(),
+            map_key: 1,
+            monotonic_count: 0,
+            stalled: 0,
+            exit: None,
+            reset: None,
+            reset_data: Vec::new(),
+            capsule_capabilities: Err(Status::Unsupported),
+            capsules: Vec::new(),
+            configuration_tables: Vec::new(),
+            tpl: TPL::Application as usize,
+        });
+
+        // can't fail, the tables were just checksummed
+        let _ = systemtable::set_system_table(system_table);
+        // can't fail, the loaded image protocol was just installed
+        let _ = protocol::set_current_image(image_handle);
+
+        MockFirmware {
+            system_table,
+            boot_services,
+            runtime_services,
+            con_in,
+            con_in_ex,
+            con_out,
+            std_err,
+            mode,
+            loaded_image,
+            vendor,
+            image_handle,
+            _installed: installed,
+        }
+    }
+
+    pub fn system_table(&self) -> &'static SystemTable {
+        unsafe { &*self.system_table }
+    }
+
+    /// The handle of the running image, as passed to `set_current_image`.
+    pub fn image_handle(&self) -> Handle {
+        self.image_handle
+    }
+
+    /// Queue keys to be read from the console.
+    pub fn push_keys(&self, keys: &[InputKey]) {
+        with_state(|state| state.keys.extend(keys))
+    }
+
+    /// Press `key`, calling each key notification function registered for it, as the firmware
+    /// does.
+    pub fn press_key_ex(&self, key: KeyData) {
+        let notify: Vec<KeyNotifyFunction> = with_state(|state| {
+            state.key_notifications
+                .iter()
+                .filter(|entry| entry.key.matches(&key))
+                .map(|entry| entry.notify)
+                .collect()
+        });
+
+        for notify in notify {
+            notify(&key);
+        }
+    }
+
+    /// Queue the characters of `s` to be read from the console, as keys with no scan code. Use
+    /// `\r` for Enter. Once the queue is empty, reading a key fails with `Status::DeviceError`.
+    pub fn push_input(&self, s: &str) {
+        with_state(|state| {
+            for c in s.encode_utf16() {
+                state.keys.push_back(InputKey { scan_code: 0, unicode_char: c });
+            }
+        })
+    }
+
+    /// Everything written to the console's standard output so far.
+    pub fn output(&self) -> String {
+        with_state(|state| state.stdout.clone())
+    }
+
+    /// Return and clear what has been written to standard output.
+    pub fn take_output(&self) -> String {
+        with_state(|state| mem::take(&mut state.stdout))
+    }
+
+    /// Everything written to the console's standard error so far.
+    pub fn error_output(&self) -> String {
+        with_state(|state| state.stderr.clone())
+    }
+
+    /// Set the time returned by `get_time`.
+    pub fn set_time(&self, time: Time) {
+        with_state(|state| state.time = time)
+    }
+
+    /// The current task priority level, as set by `raise_tpl` and `restore_tpl`.
+    pub fn tpl(&self) -> usize {
+        with_state(|state| state.tpl)
+    }
+
+    /// Total microseconds spent in `stall`.
+    pub fn stalled(&self) -> u64 {
+        with_state(|state| state.stalled)
+    }
+
+    /// Store a variable directly, without the checks of `set_variable`.
+    pub fn set_variable(&self, name: &str, vendor: &Guid, attributes: VariableAttributes, data: &[u8]) {
+        let name = str_to_ucs2(name);
+        with_state(|state| {
+            state.variables.retain(|v| v.name != name || v.vendor != *vendor);
+            state.variables.push(Variable {
+                name,
+                vendor: *vendor,
+                attributes: attributes.bits(),
+                data: data.to_vec(),
+            });
+        })
+    }
+
+    /// The attributes and contents of a variable, if it exists.
+    pub fn variable(&self, name: &str, vendor: &Guid) -> Option<(VariableAttributes, Vec<u8>)> {
+        let name = str_to_ucs2(name);
+        with_state(|state| {
+            state.variables
+                .iter()
+                .find(|v| v.name == name && v.vendor == *vendor)
+                .map(|v| (VariableAttributes::from_bits_truncate(v.attributes), v.data.clone()))
+        })
+    }
+
+    /// Create a new handle with no protocols.
+    pub fn create_handle(&self) -> Handle {
+        with_state(|state| {
+            state.handles += 1;
+            Handle::new(fake_pointer(state.handles))
+        })
+    }
+
+    /// Install `interface` as protocol `P` on `handle`. It must stay valid while the firmware is
+    /// in use.
+    pub fn install_protocol<P: Protocol>(&self, handle: Handle, interface: *const P) {
+        with_state(|state| {
+            state.protocols.push(ProtocolEntry {
+                handle,
+                guid: *P::guid(),
+                interface: interface as *mut CVoid,
+            })
+        })
+    }
+
+    /// Add `table` to the system configuration table under `guid`, replacing any table already
+    /// there, or remove the entry if `table` is null. It must stay valid while the firmware is in
+    /// use.
+    pub fn install_configuration_table(&self, guid: &Guid, table: *const CVoid) {
+        with_state(|state| {
+            let tables = &mut state.configuration_tables;
+            tables.retain(|entry| entry.vendor_guid() != guid);
+            if !table.is_null() {
+                tables.push(ConfigurationTable::new(*guid, table));
+            }
+
+            unsafe { (*self.system_table).set_configuration_tables(tables) };
+        })
+    }
+
+    /// The number of pool allocations which haven't been freed.
+    pub fn pool_allocations(&self) -> usize {
+        with_state(|state| state.pool.len())
+    }
+
+    /// The number of page allocations which haven't been freed.
+    pub fn page_allocations(&self) -> usize {
+        with_state(|state| state.pages.len())
+    }
+
+    /// The status and exit data passed to `exit`, if it has been called. Since the mock can't
+    /// unwind the image, `exit` returns `Status::Success` to its caller.
+    pub fn exit_status(&self) -> Option<(Status, String)> {
+        with_state(|state| state.exit.clone())
+    }
+
+    /// The type and status passed to `reset_system`, if it has been called.
+    pub fn reset(&self) -> Option<(ResetType, Status)> {
+        with_state(|state| state.reset)
+    }
+
+    /// The data passed to `reset_system`, which is empty if there was none.
+    pub fn reset_data(&self) -> Vec<u8> {
+        with_state(|state| state.reset_data.clone())
+    }
+
+    /// Set what `query_capsule_capabilities` returns: the maximum capsule size and the raw reset
+    /// type, or an error, which `update_capsule` returns too. Capsules are unsupported by default.
+    pub fn set_capsule_capabilities(&self, capabilities: Result<(u64, u32), Status>) {
+        with_state(|state| state.capsule_capabilities = capabilities);
+    }
+
+    /// The headers of the capsules passed to each successful `update_capsule` call, along with
+    /// the address of its scatter-gather list.
+    pub fn capsules(&self) -> Vec<(Vec<CapsuleHeader>, u64)> {
+        with_state(|state| state.capsules.clone())
+    }
+}
+
+impl Drop for MockFirmware {
+    fn drop(&mut self) {
+        unsafe {
+            systemtable::SYSTEM_TABLE = ptr::null();
+            protocol::THIS_LOADED_IMAGE = ptr::null();
+            bootservices::BOOT_SERVICES_EXITED = false;
+        }
+
+        if let Some(state) = STATE.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            for (address, layout) in state.pool.into_iter().chain(state.pages) {
+                unsafe { alloc::dealloc(address as *mut u8, layout) };
+            }
+        }
+
+        unsafe {
+            drop(Box::from_raw(self.system_table));
+            drop(Box::from_raw(self.boot_services));
+            drop(Box::from_raw(self.runtime_services));
+            drop(Box::from_raw(self.con_in));
+            drop(Box::from_raw(self.con_in_ex));
+            drop(Box::from_raw(self.con_out));
+            drop(Box::from_raw(self.std_err));
+            drop(Box::from_raw(self.mode));
+            drop(Box::from_raw(self.loaded_image));
+            drop(Box::from_raw(self.vendor));
+        }
+    }
+}
+
+// Boot services
+
+pub(crate) unsafe extern "efiapi" fn allocate_pages(allocate_type: AllocateType, _memory_type: MemoryType, pages: usize, memory: *mut u64) -> Status {
+    if let AllocateType::Address = allocate_type {
+        return Status::Unsupported;
+    }
+
+    let layout = match Layout::from_size_align(pages.max(1) * PAGE_SIZE, PAGE_SIZE) {
+        Ok(layout) => layout,
+        Err(_) => return Status::OutOfResources,
+    };
+    let address = alloc::alloc_zeroed(layout);
+    if address.is_null() {
+        return Status::OutOfResources;
+    }
+
+    let installed = try_with_state(|state| {
+        state.pages.push((address as usize, layout));
+        state.map_key += 1;
+    });
+    if installed.is_none() {
+        alloc::dealloc(address, layout);
+        return Status::NotReady;
+    }
+    *memory = address as u64;
+    Status::Success
+}
+
+pub(crate) unsafe extern "efiapi" fn free_pages(memory: u64, _pages: usize) -> Status {
+    let found = service_state!(|state| {
+        let index = state.pages.iter().position(|&(address, _)| address as u64 == memory)?;
+        state.map_key += 1;
+        Some(state.pages.swap_remove(index))
+    });
+
+    match found {
+        Some((address, layout)) => {
+            alloc::dealloc(address as *mut u8, layout);
+            Status::Success
+        },
+        None => Status::NotFound,
+    }
+}
+
+pub(crate) unsafe extern "efiapi" fn get_memory_map(map_size: *mut usize, map: *mut MemoryDescriptor, map_key: *mut usize, descriptor_size: *mut usize, descriptor_version: *mut u32) -> Status {
+    let descriptors = [
+        MemoryDescriptor {
+            type_: MemoryType::Conventional as u32,
+            physical_start: 0x10_0000,
+            virtual_start: 0,
+            number_of_pages: CONVENTIONAL_MEMORY_PAGES,
+            attribute: 0xF,
+        },
+        MemoryDescriptor {
+            type_: MemoryType::RuntimeServicesData as u32,
+            physical_start: 0x8000_0000,
+            virtual_start: 0,
+            number_of_pages: 0x10,
+            attribute: MEMORY_RUNTIME.bits() | 0xF,
+        },
+    ];
+
+    *descriptor_size = DESCRIPTOR_SIZE;
+    *descriptor_version = 1;
+
+    let size = descriptors.len() * DESCRIPTOR_SIZE;
+    if *map_size < size {
+        *map_size = size;
+        return Status::BufferTooSmall;
+    }
+
+    *map_size = size;
+    let bytes = map as *mut u8;
+    for (i, descriptor) in descriptors.iter().enumerate() {
+        let entry = bytes.add(i * DESCRIPTOR_SIZE);
+        // garbage in the padding, which nobody should read
+        ptr::write_bytes(entry, 0xFF, DESCRIPTOR_SIZE);
+        ptr::write(entry as *mut MemoryDescriptor, *descriptor);
+    }
+    *map_key = service_state!(|state| state.map_key);
+    Status::Success
+}
+
+pub(crate) unsafe extern "efiapi" fn allocate_pool(_pool_type: MemoryType, size: usize, out: *mut *mut u8) -> Status {
+    let layout = match Layout::from_size_align(size.max(1), 8) {
+        Ok(layout) => layout,
+        Err(_) => return Status::OutOfResources,
+    };
+    let address = alloc::alloc(layout);
+    if address.is_null() {
+        return Status::OutOfResources;
+    }
+
+    if try_with_state(|state| state.pool.push((address as usize, layout))).is_none() {
+        alloc::dealloc(address, layout);
+        return Status::NotReady;
+    }
+    *out = address;
+    Status::Success
+}
+
+pub(crate) unsafe extern "efiapi" fn free_pool(buffer: *mut CVoid) {
+    let found = try_with_state(|state| {
+        let index = state.pool.iter().position(|&(address, _)| address == buffer as usize)?;
+        Some(state.pool.swap_remove(index))
+    });
+
+    if let Some(Some((address, layout))) = found {
+        alloc::dealloc(address as *mut u8, layout);
+    }
+}
+
+pub(crate) unsafe extern "efiapi" fn raise_tpl(new_tpl: usize) -> usize {
+    try_with_state(|state| mem::replace(&mut state.tpl, new_tpl)).unwrap_or(TPL::Application as usize)
+}
+
+pub(crate) unsafe extern "efiapi" fn restore_tpl(old_tpl: usize) {
+    let _ = try_with_state(|state| state.tpl = old_tpl);
+}
+
+pub(crate) unsafe extern "efiapi" fn create_event(_event_type: EventType, _notify_tpl: TPL, _notify_function: Option<EventNotify>, _notify_context: *const CVoid, event: *mut Event) -> Status {
+    *event = service_state!(|state| {
+        state.events += 1;
+        Event(fake_pointer(state.events))
+    });
+    Status::Success
+}
+
+pub(crate) unsafe extern "efiapi" fn set_timer(_event: Event, _delay_type: TimerDelay, _delay: u64) -> Status {
+    Status::Success
+}
+
+/// Every event is treated as already signalled.
+pub(crate) unsafe extern "efiapi" fn wait_for_event(count: usize, _events: *const Event, index: *mut usize) -> Status {
+    if count == 0 {
+        return Status::InvalidParameter;
+    }
+
+    *index = 0;
+    Status::Success
+}
+
+pub(crate) unsafe extern "efiapi" fn handle_protocol(handle: Handle, guid: &Guid, interface: &mut *mut CVoid) -> Status {
+    let found = service_state!(|state| {
+        state.protocols
+            .iter()
+            .find(|p| p.handle.as_ptr() == handle.as_ptr() && p.guid == *guid)
+            .map(|p| p.interface)
+    });
+
+    match found {
+        Some(p) => {
+            *interface = p;
+            Status::Success
+        },
+        None => Status::Unsupported,
+    }
+}
+
+pub(crate) unsafe extern "efiapi" fn load_image(_boot_policy: u8, _parent_image_handle: Handle, _device_path: *const DevicePathProtocol, _source_buffer: *const CVoid, _source_size: usize, _image_handle: *mut Handle) -> Status {
+    Status::Unsupported
+}
+
+pub(crate) unsafe extern "efiapi" fn start_image(_image_handle: Handle, _exit_data_size: *mut usize, _exit_data: *mut *const u16) -> Status {
+    Status::Unsupported
+}
+
+pub(crate) unsafe extern "efiapi" fn exit(_image_handle: Handle, exit_status: Status, exit_data_size: usize, exit_data: *const u16) -> Status {
+    let data = if exit_data.is_null() {
+        String::new()
+    } else {
+        ucs2_to_string(slice::from_raw_parts(exit_data, exit_data_size / 2))
+    };
+
+    service_state!(|state| state.exit = Some((exit_status, data)));
+    Status::Success
+}
+
+pub(crate) unsafe extern "efiapi" fn exit_boot_services(_image_handle: Handle, map_key: usize) -> Status {
+    if service_state!(|state| state.map_key) != map_key {
+        return Status::InvalidParameter;
+    }
+
+    Status::Success
+}
+
+pub(crate) unsafe extern "efiapi" fn stall(microseconds: usize) -> Status {
+    service_state!(|state| state.stalled += microseconds as u64);
+    Status::Success
+}
+
+pub(crate) unsafe extern "efiapi" fn set_watchdog_timer(_timeout: usize, _code: u64, _data_size: usize, _data: *const u16) -> Status {
+    Status::Success
+}
+
+pub(crate) unsafe extern "efiapi" fn close_protocol(_handle: Handle, _protocol: &Guid, _agent_handle: Handle, _controller_handle: Handle) -> Status {
+    Status::Success
+}
+
+pub(crate) unsafe extern "efiapi" fn locate_handle_buffer(search_type: LocateSearchType, protocol: &Guid, _search_key: *const CVoid, nhandles: *mut usize, handles: *mut *mut CVoid) -> Status {
+    let mut found: Vec<Handle> = Vec::new();
+    service_state!(|state| {
+        for p in &state.protocols {
+            let matches = match search_type {
+                LocateSearchType::AllHandles => true,
+                LocateSearchType::ByProtocol => p.guid == *protocol,
+                LocateSearchType::ByRegisterNotify => false,
+            };
+            if matches && !found.iter().any(|h| h.as_ptr() == p.handle.as_ptr()) {
+                found.push(p.handle);
+            }
+        }
+    });
+
+    if found.is_empty() {
+        return Status::NotFound;
+    }
+
+    let mut buffer: *mut u8 = ptr::null_mut();
+    let status = allocate_pool(MemoryType::BootServicesData, found.len() * mem::size_of::<Handle>(), &mut buffer);
+    if status != Status::Success {
+        return status;
+    }
+
+    ptr::copy_nonoverlapping(found.as_ptr(), buffer as *mut Handle, found.len());
+    *nhandles = found.len();
+    *handles = buffer as *mut CVoid;
+    Status::Success
+}
+
+pub(crate) unsafe extern "efiapi" fn locate_protocol(protocol: &Guid, _registration: *const CVoid, interface: &mut *mut CVoid) -> Status {
+    let found = service_state!(|state| state.protocols.iter().find(|p| p.guid == *protocol).map(|p| p.interface));
+
+    match found {
+        Some(p) => {
+            *interface = p;
+            Status::Success
+        },
+        None => Status::NotFound,
+    }
+}
+
+pub(crate) unsafe extern "efiapi" fn copy_mem(destination: *mut CVoid, source: *mut CVoid, length: usize) {
+    ptr::copy(source as *const u8, destination as *mut u8, length);
+}
+
+pub(crate) unsafe extern "efiapi" fn set_mem(buffer: *mut CVoid, size: usize, value: u8) {
+    ptr::write_bytes(buffer as *mut u8, value, size);
+}
+
+// Runtime services
+
+pub(crate) unsafe extern "efiapi" fn get_time(time: &mut Time, capabilities: *mut TimeCapabilities) -> Status {
+    *time = service_state!(|state| state.time);
+    if !capabilities.is_null() {
+        *capabilities = TimeCapabilities::new(1, 50_000_000, false);
+    }
+    Status::Success
+}
+
+pub(crate) unsafe extern "efiapi" fn set_time(time: &Time) -> Status {
+    service_state!(|state| state.time = *time);
+    Status::Success
+}
+
+pub(crate) unsafe extern "efiapi" fn get_wakeup_time(_enabled: &mut u8, _pending: &mut u8, _time: &mut Time) -> Status {
+    Status::Unsupported
+}
+
+pub(crate) unsafe extern "efiapi" fn set_wakeup_time(_enable: u8, _time: *const Time) -> Status {
+    Status::Unsupported
+}
+
+pub(crate) unsafe extern "efiapi" fn set_virtual_address_map(_map_size: usize, _descriptor_size: usize, _descriptor_version: u32, _virtual_map: *mut MemoryDescriptor) -> Status {
+    Status::Unsupported
+}
+
+/// Addresses are identity mapped.
+pub(crate) unsafe extern "efiapi" fn convert_pointer(_debug_disposition: usize, _address: *mut *const CVoid) -> Status {
+    Status::Success
+}
+
+pub(crate) unsafe extern "efiapi" fn get_variable(name: *const u16, guid: &Guid, attributes: *mut u32, size: *mut usize, data: *mut u8) -> Status {
+    let name = read_ucs2(name);
+    service_state!(|state| {
+        let variable = match state.variables.iter().find(|v| v.name == name && v.vendor == *guid) {
+            Some(variable) => variable,
+            None => return Status::NotFound,
+        };
+
+        if !attributes.is_null() {
+            *attributes = variable.attributes;
+        }
+
+        let available = *size;
+        *size = variable.data.len();
+        if available < variable.data.len() {
+            return Status::BufferTooSmall;
+        }
+
+        ptr::copy_nonoverlapping(variable.data.as_ptr(), data, variable.data.len());
+        Status::Success
+    })
+}
+
+pub(crate) unsafe extern "efiapi" fn get_next_variable_name(name_size: *mut usize, name: *mut u16, guid: *mut Guid) -> Status {
+    let current = read_ucs2(name);
+    service_state!(|state| {
+        let next = if current == [0] {
+            0
+        } else {
+            match state.variables.iter().position(|v| v.name == current && v.vendor == *guid) {
+                Some(index) => index + 1,
+                None => return Status::InvalidParameter,
+            }
+        };
+
+        let variable = match state.variables.get(next) {
+            Some(variable) => variable,
+            None => return Status::NotFound,
+        };
+
+        let available = *name_size;
+        *name_size = variable.name.len() * 2;
+        if available < variable.name.len() * 2 {
+            return Status::BufferTooSmall;
+        }
+
+        ptr::copy_nonoverlapping(variable.name.as_ptr(), name, variable.name.len());
+        *guid = variable.vendor;
+        Status::Success
+    })
+}
+
+pub(crate) unsafe extern "efiapi" fn set_variable(name: *const u16, guid: &Guid, attributes: u32, size: usize, data: *const u8) -> Status {
+    let name = read_ucs2(name);
+    if name == [0] {
+        return Status::InvalidParameter;
+    }
+    let data = if size == 0 { &[][..] } else { slice::from_raw_parts(data, size) };
+    let append = attributes & VARIABLE_APPEND_WRITE.bits() != 0;
+
+    service_state!(|state| {
+        let existing = state.variables.iter().position(|v| v.name == name && v.vendor == *guid);
+
+        if !append && (size == 0 || attributes == 0) {
+            return match existing {
+                Some(index) => {
+                    state.variables.remove(index);
+                    Status::Success
+                },
+                None => Status::NotFound,
+            };
+        }
+
+        let attributes = attributes & !VARIABLE_APPEND_WRITE.bits();
+        match existing {
+            Some(index) => {
+                let variable = &mut state.variables[index];
+                if variable.attributes != attributes {
+                    return Status::InvalidParameter;
+                }
+                if !append {
+                    variable.data.clear();
+                }
+                variable.data.extend_from_slice(data);
+            },
+            None => state.variables.push(Variable {
+                name,
+                vendor: *guid,
+                attributes,
+                data: data.to_vec(),
+            }),
+        }
+
+        Status::Success
+    })
+}
+
+pub(crate) unsafe extern "efiapi" fn get_next_highest_monotonic_count(count: *mut u32) -> Status {
+    *count = service_state!(|state| {
+        state.monotonic_count += 1;
+        state.monotonic_count
+    });
+    Status::Success
+}
+
+pub(crate) unsafe extern "efiapi" fn reset_system(reset_type: ResetType, status: Status, data_size: usize, data: *const u8) -> ! {
+    let data = if data.is_null() { Vec::new() } else { slice::from_raw_parts(data, data_size).to_vec() };
+    // Unwinding out of the firmware would abort the whole test binary, so stop here instead.
+    let _ = try_with_state(|state| {
+        state.reset = Some((reset_type, status));
+        state.reset_data = data;
+    });
+    let _ = writeln!(io::stderr(), "mock firmware: reset_system({:?}, {:?}) parks this thread", reset_type, status);
+    loop {
+        thread::park();
+    }
+}
+
+pub(crate) unsafe extern "efiapi" fn update_capsule(capsules: *const *const CapsuleHeader, count: usize, scatter_gather_list: u64) -> Status {
+    let headers = slice::from_raw_parts(capsules, count).iter().map(|&header| *header).collect();
+
+    service_state!(|state| match state.capsule_capabilities {
+        Ok(_) => {
+            state.capsules.push((headers, scatter_gather_list));
+            Status::Success
+        },
+        Err(e) => e,
+    })
+}
+
+pub(crate) unsafe extern "efiapi" fn query_capsule_capabilities(_capsules: *const *const CapsuleHeader, _count: usize, maximum_capsule_size: *mut u64, reset_type: *mut u32) -> Status {
+    match service_state!(|state| state.capsule_capabilities) {
+        Ok((size, reset)) => {
+            *maximum_capsule_size = size;
+            *reset_type = reset;
+            Status::Success
+        },
+        Err(e) => e,
+    }
+}
+
+// Console
+
+pub(crate) unsafe extern "efiapi" fn input_reset(_this: *const SimpleTextInputProtocol, _extended_verification: u8) -> Status {
+    Status::Success
+}
+
+pub(crate) unsafe extern "efiapi" fn read_key_stroke(_this: *const SimpleTextInputProtocol, key: &mut InputKey) -> Status {
+    // not NotReady, which would have callers wait forever
+    match service_state!(|state| state.keys.pop_front()) {
+        Some(k) => {
+            *key = k;
+            Status::Success
+        },
+        None => Status::DeviceError,
+    }
+}
+
+pub(crate) unsafe extern "efiapi" fn input_ex_reset(_this: *const SimpleTextInputExProtocol, _extended_verification: u8) -> Status {
+    Status::Success
+}
+
+/// Reads the same keys as `read_key_stroke`, with no modifiers.
+pub(crate) unsafe extern "efiapi" fn read_key_stroke_ex(_this: *const SimpleTextInputExProtocol, key_data: *mut KeyData) -> Status {
+    match service_state!(|state| state.keys.pop_front()) {
+        Some(key) => {
+            *key_data = KeyData { key, key_state: KeyState::default() };
+            Status::Success
+        },
+        None => Status::DeviceError,
+    }
+}
+
+pub(crate) unsafe extern "efiapi" fn set_state(_this: *const SimpleTextInputExProtocol, _toggle_state: *const u8) -> Status {
+    Status::Unsupported
+}
+
+/// Like real firmware, registering the same key and function again returns the same handle.
+pub(crate) unsafe extern "efiapi" fn register_key_notify(_this: *const SimpleTextInputExProtocol, key_data: *const KeyData, notify: KeyNotifyFunction, handle: *mut *const CVoid) -> Status {
+    let key = *key_data;
+    *handle = service_state!(|state| {
+        let existing = state.key_notifications.iter().find(|entry| {
+            entry.key.key == key.key && entry.key.key_state.shift_state() == key.key_state.shift_state()
+                && entry.notify as usize == notify as usize
+        });
+        if let Some(entry) = existing {
+            return entry.handle;
+        }
+
+        state.handles += 1;
+        let handle = fake_pointer(state.handles) as *const CVoid;
+        state.key_notifications.push(KeyNotifyEntry { handle, key, notify });
+        handle
+    });
+    Status::Success
+}
+
+pub(crate) unsafe extern "efiapi" fn unregister_key_notify(_this: *const SimpleTextInputExProtocol, handle: *const CVoid) -> Status {
+    service_state!(|state| {
+        let count = state.key_notifications.len();
+        state.key_notifications.retain(|entry| entry.handle != handle);
+        if state.key_notifications.len() == count {
+            Status::InvalidParameter
+        } else {
+            Status::Success
+        }
+    })
+}
+
+pub(crate) unsafe extern "efiapi" fn output_reset(_this: *const SimpleTextOutputProtocol, _extended_verification: u8) -> Status {
+    Status::Success
+}
+
+pub(crate) unsafe extern "efiapi" fn output_string(this: *const SimpleTextOutputProtocol, string: *const u16) -> Status {
+    let string = read_ucs2(string);
+    service_state!(|state| {
+        let (columns, rows) = CONSOLE_SIZE;
+        let mode = &mut *state.mode;
+        for &c in &string[..string.len() - 1] {
+            match c {
+                0x0D => mode.cursor_column = 0,
+                0x0A => mode.cursor_row = (mode.cursor_row + 1).min(rows as i32 - 1),
+                _ => {
+                    mode.cursor_column += 1;
+                    if mode.cursor_column == columns as i32 {
+                        mode.cursor_column = 0;
+                        mode.cursor_row = (mode.cursor_row + 1).min(rows as i32 - 1);
+                    }
+                },
+            }
+        }
+
+        let output = if this == state.stderr_protocol { &mut state.stderr } else { &mut state.stdout };
+        output.push_str(&ucs2_to_string(&string));
+    });
+    Status::Success
+}
+
+pub(crate) unsafe extern "efiapi" fn test_string(_this: *const SimpleTextOutputProtocol, _string: *const u16) -> Status {
+    Status::Success
+}
+
+pub(crate) unsafe extern "efiapi" fn query_mode(_this: *const SimpleTextOutputProtocol, mode: usize, columns: &mut usize, rows: &mut usize) -> Status {
+    if mode != 0 {
+        return Status::Unsupported;
+    }
+
+    *columns = CONSOLE_SIZE.0;
+    *rows = CONSOLE_SIZE.1;
+    Status::Success
+}
+
+pub(crate) unsafe extern "efiapi" fn set_mode(_this: *const SimpleTextOutputProtocol, mode: usize) -> Status {
+    if mode != 0 {
+        return Status::Unsupported;
+    }
+
+    clear_screen(_this)
+}
+
+pub(crate) unsafe extern "efiapi" fn set_attribute(_this: *const SimpleTextOutputProtocol, attribute: usize) -> Status {
+    service_state!(|state| (*state.mode).set_attribute(attribute as i32));
+    Status::Success
+}
+
+pub(crate) unsafe extern "efiapi" fn clear_screen(_this: *const SimpleTextOutputProtocol) -> Status {
+    service_state!(|state| {
+        (*state.mode).cursor_column = 0;
+        (*state.mode).cursor_row = 0;
+    });
+    Status::Success
+}
+
+pub(crate) unsafe extern "efiapi" fn set_cursor_position(_this: *const SimpleTextOutputProtocol, column: usize, row: usize) -> Status {
+    if column >= CONSOLE_SIZE.0 || row >= CONSOLE_SIZE.1 {
+        return Status::Unsupported;
+    }
+
+    service_state!(|state| {
+        (*state.mode).cursor_column = column as i32;
+        (*state.mode).cursor_row = row as i32;
+    });
+    Status::Success
+}
+
+pub(crate) unsafe extern "efiapi" fn enable_cursor(_this: *const SimpleTextOutputProtocol, visible: u8) -> Status {
+    service_state!(|state| (*state.mode).set_cursor_visible(visible));
+    Status::Success
+}
@@ -0,0 +1,141 @@
+// Copyright 2017 CoreOS, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::hint;
+use core::panic::PanicInfo;
+
+use base::Status;
+use console::SimpleTextInput;
+use bootservices::boot_services_exited;
+use protocol::{get_current_image_handle, SerialIOProtocol, THIS_LOADED_IMAGE};
+use runtimeservices::ResetType;
+use systemtable::SYSTEM_TABLE;
+use util::format_truncated;
+
+/// What to do once a panic has been reported.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PanicAction {
+    /// Exit the current image with `BootServices::exit`, passing the panic message as exit data.
+    /// After `exit_boot_services`, or if the image can't be exited, the system is cold reset.
+    Exit,
+    /// Reset the system with `RuntimeServices::reset_system`, passing the panic message as the
+    /// reset reason.
+    Reset(ResetType),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PanicConfig {
+    pub action: PanicAction,
+    /// The status to exit or reset with.
+    pub status: Status,
+    /// Wait for a key press before exiting or resetting, so the message can be read. Ignored
+    /// after `exit_boot_services`.
+    pub wait_for_key: bool,
+    /// Also write the message to the first serial port. Ignored after `exit_boot_services`.
+    pub serial: bool,
+}
+
+const DEFAULT_PANIC_CONFIG: PanicConfig = PanicConfig {
+    action: PanicAction::Exit,
+    status: Status::Aborted,
+    wait_for_key: false,
+    serial: true,
+};
+
+impl Default for PanicConfig {
+    fn default() -> PanicConfig {
+        DEFAULT_PANIC_CONFIG
+    }
+}
+
+static mut PANIC_CONFIG: PanicConfig = DEFAULT_PANIC_CONFIG;
+static mut PANICKING: bool = false;
+
+/// Configure `handle_panic`.
+pub fn set_panic_config(config: PanicConfig) {
+    unsafe {
+        PANIC_CONFIG = config;
+    }
+}
+
+pub fn panic_config() -> PanicConfig {
+    unsafe { PANIC_CONFIG }
+}
+
+/// Report a panic on standard error and, if configured, the serial port, and then exit or reset
+/// as set by `set_panic_config`. The `panic-handler` feature installs this as the panic handler;
+/// applications with their own can call it from there.
+pub fn handle_panic(info: &PanicInfo) -> ! {
+    let config = panic_config();
+
+    // don't try anything fancy if reporting the panic panicked
+    if unsafe { PANICKING } {
+        halt(config, "");
+    }
+    unsafe {
+        PANICKING = true;
+    }
+
+    // PanicInfo's Display gives the location and message, without needing
+    // PanicInfo::message, which newer compilers require
+    let mut buf = [0u8; 256];
+    let message = format_truncated(&mut buf, format_args!("{}", info));
+
+    eprintln!("{}", message);
+
+    let st = unsafe { SYSTEM_TABLE };
+    if st.is_null() || boot_services_exited() {
+        halt(config, message);
+    }
+    let st = unsafe { &*st };
+
+    if config.serial {
+        if let Ok(serial) = SerialIOProtocol::new() {
+            let _ = serial.write(message);
+            let _ = serial.write("\r\n");
+        }
+    }
+
+    if config.wait_for_key {
+        eprintln!("Press any key to continue.");
+        let _ = st.console().read_key();
+    }
+
+    if config.action == PanicAction::Exit && unsafe { !THIS_LOADED_IMAGE.is_null() } {
+        st.boot_services().exit_with_message(get_current_image_handle(), config.status, message);
+    }
+
+    halt(config, message)
+}
+
+fn halt(config: PanicConfig, message: &str) -> ! {
+    let st = unsafe { SYSTEM_TABLE };
+    if !st.is_null() {
+        let reset_type = match config.action {
+            PanicAction::Reset(reset_type) => reset_type,
+            PanicAction::Exit => ResetType::Cold,
+        };
+        unsafe { &*st }.runtime_services().reset_system_with_reason(reset_type, config.status, message);
+    }
+
+    loop {
+        hint::spin_loop();
+    }
+}
+
+#[cfg(feature = "panic-handler")]
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    handle_panic(info)
+}
@@ -0,0 +1,91 @@
+// Copyright 2017 CoreOS, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::fmt::{self, Write};
+
+use bootservices::boot_services_exited;
+use systemtable::SYSTEM_TABLE;
+
+/// Print to the console's standard output, through the system table set by `set_system_table`.
+/// Once boot services have exited, or before the system table is set, the output goes to the
+/// sink set by `set_print_sink`, or nowhere.
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => ($crate::_print(format_args!($($arg)*)));
+}
+
+/// Like `print!`, with a newline.
+#[macro_export]
+macro_rules! println {
+    () => ($crate::_print(format_args!("\n")));
+    ($($arg:tt)*) => ($crate::_print(format_args!("{}\n", format_args!($($arg)*))));
+}
+
+/// Like `print!`, but to the console's standard error.
+#[macro_export]
+macro_rules! eprint {
+    ($($arg:tt)*) => ($crate::_eprint(format_args!($($arg)*)));
+}
+
+/// Like `eprint!`, with a newline.
+#[macro_export]
+macro_rules! eprintln {
+    () => ($crate::_eprint(format_args!("\n")));
+    ($($arg:tt)*) => ($crate::_eprint(format_args!("{}\n", format_args!($($arg)*))));
+}
+
+static mut PRINT_SINK: Option<fn(&str)> = None;
+
+/// Send the output of the print macros to `sink` whenever the console can't be used, such as
+/// after `exit_boot_services`. A serial port driven directly by the application, or a buffer
+/// handed to the OS, would be typical sinks.
+pub fn set_print_sink(sink: Option<fn(&str)>) {
+    unsafe {
+        PRINT_SINK = sink;
+    }
+}
+
+struct Sink(fn(&str));
+
+impl Write for Sink {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        (self.0)(s);
+        Ok(())
+    }
+}
+
+fn print_to(stderr: bool, args: fmt::Arguments) {
+    let st = unsafe { SYSTEM_TABLE };
+
+    if st.is_null() || boot_services_exited() {
+        if let Some(sink) = unsafe { PRINT_SINK } {
+            let _ = Sink(sink).write_fmt(args);
+        }
+        return;
+    }
+
+    let st = unsafe { &*st };
+    let mut console = if stderr { st.stderr() } else { st.console() };
+    let _ = console.write_fmt(args);
+}
+
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    print_to(false, args);
+}
+
+#[doc(hidden)]
+pub fn _eprint(args: fmt::Arguments) {
+    print_to(true, args);
+}
@@ -13,13 +13,14 @@
 // limitations under the License.
 
 use core::mem;
+use core::slice;
 
 use base::Status;
 use console::SimpleTextOutput;
 use guid::Guid;
 use protocol::Protocol;
 use void::CVoid;
-use util::{utf16_ptr_to_str, str_to_utf16_ptr};
+use util::{CStr16, CString16};
 
 #[repr(u8)]
 pub enum DevicePathTypes {
@@ -192,12 +193,33 @@ impl DevicePathProtocol {
         let this_u8 = self as *const DevicePathProtocol as *const u8;
         &mut *(this_u8.offset(self.len() as isize) as *mut DevicePathProtocol)
     }
+
+    /// Get the whole device path starting at this node, up to and including its End Entire Path
+    /// node, as bytes; for example to store it in a load option.
+    ///
+    /// # Safety
+    ///
+    /// Like `next`, this trusts the length of each node: the nodes must be valid and followed by
+    /// an End Entire Path node, all within memory which lives as long as `self`.
+    pub unsafe fn as_bytes(&self) -> &[u8] {
+        let mut node = self;
+        let mut len = 0;
+        loop {
+            len += node.len() as usize;
+            if node.type_ == DevicePathTypes::End.into() && node.sub_type == EndPathSubTypes::EndEntirePath.into() {
+                break;
+            }
+            node = node.next();
+        }
+
+        slice::from_raw_parts(self as *const DevicePathProtocol as *const u8, len)
+    }
 }
 
 #[repr(C)]
 pub struct DevicePathToTextProtocol {
-    device_path_node_to_text: unsafe extern "win64" fn(device_node: *const DevicePathProtocol, display_only: u8, allow_shortcuts: u8) -> *const u16,
-    device_path_to_text: unsafe extern "win64" fn(device_path: *const DevicePathProtocol, display_only: u8, allow_shortcuts: u8) -> *const u16
+    device_path_node_to_text: unsafe extern "efiapi" fn(device_node: *const DevicePathProtocol, display_only: u8, allow_shortcuts: u8) -> *const u16,
+    device_path_to_text: unsafe extern "efiapi" fn(device_path: *const DevicePathProtocol, display_only: u8, allow_shortcuts: u8) -> *const u16
 }
 
 impl Protocol for DevicePathToTextProtocol {
@@ -207,18 +229,24 @@ impl Protocol for DevicePathToTextProtocol {
 }
 
 impl DevicePathToTextProtocol {
-    pub fn device_path_node_to_text(&self, device_node: *const DevicePathProtocol, display_only: bool, allow_shortcuts: bool) -> Result<&str, Status> {
-        let chars: *const u16 = unsafe { (self.device_path_node_to_text)(device_node, display_only as u8, allow_shortcuts as u8) };
-        let out = utf16_ptr_to_str(chars);
-        ::get_system_table().boot_services().free_pool(chars);
-        out
+    /// Convert a single device path node to text.
+    pub fn device_path_node_to_text(&self, device_node: *const DevicePathProtocol, display_only: bool, allow_shortcuts: bool) -> Result<CString16, Status> {
+        let chars = unsafe { (self.device_path_node_to_text)(device_node, display_only as u8, allow_shortcuts as u8) };
+        if chars.is_null() {
+            return Err(Status::OutOfResources);
+        }
+
+        unsafe { CString16::from_pool(chars as *mut u16) }
     }
 
-    pub fn device_path_to_text(&self, device_node: *const DevicePathProtocol, display_only: bool, allow_shortcuts: bool) -> Result<&str, Status> {
-        let chars: *const u16 = unsafe { (self.device_path_to_text)(device_node, display_only as u8, allow_shortcuts as u8) };
-        let out = utf16_ptr_to_str(chars);
-        ::get_system_table().boot_services().free_pool(chars);
-        out
+    /// Convert a whole device path to text.
+    pub fn device_path_to_text(&self, device_node: *const DevicePathProtocol, display_only: bool, allow_shortcuts: bool) -> Result<CString16, Status> {
+        let chars = unsafe { (self.device_path_to_text)(device_node, display_only as u8, allow_shortcuts as u8) };
+        if chars.is_null() {
+            return Err(Status::OutOfResources);
+        }
+
+        unsafe { CString16::from_pool(chars as *mut u16) }
     }
 
     pub fn print_device_path_node(device_node: *const DevicePathProtocol, display_only: bool, allow_shortcuts: bool) -> Result<(), Status> {
@@ -230,7 +258,7 @@ impl DevicePathToTextProtocol {
             .and_then(|this| {
                           this.device_path_node_to_text(device_node, display_only, allow_shortcuts)
                               .map(|result| {
-                                       system_table.console().write(result);
+                                       system_table.console().write_raw(result.as_ptr());
                                        ()
                                    })
                      })
@@ -245,7 +273,7 @@ impl DevicePathToTextProtocol {
             .and_then(|this| {
                           this.device_path_to_text(device_node, display_only, allow_shortcuts)
                               .map(|result| {
-                                       system_table.console().write(result);
+                                       system_table.console().write_raw(result.as_ptr());
                                        ()
                                    })
                      })
@@ -254,8 +282,8 @@ impl DevicePathToTextProtocol {
 
 #[repr(C)]
 pub struct DevicePathFromTextProtocol {
-    text_to_device_path_node: unsafe extern "win64" fn(text: *const u16) -> *const DevicePathProtocol,
-    text_to_device_path: unsafe extern "win64" fn(text: *const u16) -> *const DevicePathProtocol,
+    text_to_device_path_node: unsafe extern "efiapi" fn(text: *const u16) -> *const DevicePathProtocol,
+    text_to_device_path: unsafe extern "efiapi" fn(text: *const u16) -> *const DevicePathProtocol,
 }
 
 impl Protocol for DevicePathFromTextProtocol {
@@ -265,26 +293,26 @@ impl Protocol for DevicePathFromTextProtocol {
 }
 
 impl DevicePathFromTextProtocol {
-    pub fn text_to_device_path_node(&self, path: &str) -> Result<&DevicePathProtocol, Status> {
-        str_to_utf16_ptr(path)
-            .map(|utf16_str| {
-                let out = unsafe { &*((self.text_to_device_path_node)(utf16_str)) };
-                // FIXME(csssuf)
-                // Ideally, at this point, we'd free utf16_str. However, free_pool(utf16_str) seems
-                // to hang here for unknown reasons. So we leak it.
-                out
-            })
+    /// Convert text to a single device path node. The node is allocated from pool, and it is the
+    /// caller's responsibility to free it.
+    pub fn text_to_device_path_node(&self, path: &CStr16) -> Result<&DevicePathProtocol, Status> {
+        let node = unsafe { (self.text_to_device_path_node)(path.as_ptr()) };
+        if node.is_null() {
+            return Err(Status::InvalidParameter);
+        }
+
+        Ok(unsafe { &*node })
     }
 
-    pub fn text_to_device_path(&self, path: &str) -> Result<&DevicePathProtocol, Status> {
-        str_to_utf16_ptr(path)
-            .map(|utf16_str| {
-                let out = unsafe { &*((self.text_to_device_path)(utf16_str)) };
-                // FIXME(csssuf)
-                // Ideally, at this point, we'd free utf16_str. However, free_pool(utf16_str) seems
-                // to hang here for unknown reasons. So we leak it.
-                out
-            })
+    /// Convert text to a device path. The path is allocated from pool, and it is the caller's
+    /// responsibility to free it.
+    pub fn text_to_device_path(&self, path: &CStr16) -> Result<&DevicePathProtocol, Status> {
+        let device_path = unsafe { (self.text_to_device_path)(path.as_ptr()) };
+        if device_path.is_null() {
+            return Err(Status::InvalidParameter);
+        }
+
+        Ok(unsafe { &*device_path })
     }
 }
 
@@ -292,13 +320,13 @@ impl DevicePathFromTextProtocol {
 pub struct DevicePathUtilitiesProtocol {
     get_device_path_size: *const CVoid,
     duplicate_device_path:
-        unsafe extern "win64" fn(src: *const DevicePathProtocol) -> *mut DevicePathProtocol,
-    append_device_path: unsafe extern "win64" fn(src1: *const DevicePathProtocol, src2: *const DevicePathProtocol) -> *const DevicePathProtocol,
-    append_device_node: unsafe extern "win64" fn(path: *const DevicePathProtocol, node: *const DevicePathProtocol) -> *const DevicePathProtocol,
+        unsafe extern "efiapi" fn(src: *const DevicePathProtocol) -> *mut DevicePathProtocol,
+    append_device_path: unsafe extern "efiapi" fn(src1: *const DevicePathProtocol, src2: *const DevicePathProtocol) -> *const DevicePathProtocol,
+    append_device_node: unsafe extern "efiapi" fn(path: *const DevicePathProtocol, node: *const DevicePathProtocol) -> *const DevicePathProtocol,
     append_device_path_instance: *const CVoid,
     get_next_device_path_instance: *const CVoid,
     is_device_path_multi_instance: *const CVoid,
-    create_device_node: unsafe extern "win64" fn(node_type: u8, node_subtype: u8, node_length: u16) -> *const DevicePathProtocol
+    create_device_node: unsafe extern "efiapi" fn(node_type: u8, node_subtype: u8, node_length: u16) -> *const DevicePathProtocol
 }
 
 impl Protocol for DevicePathUtilitiesProtocol {
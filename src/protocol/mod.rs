@@ -3,10 +3,14 @@ use guid::Guid;
 use void::NotYetDef;
 
 mod device_path;
+mod rng;
 mod serial;
+mod text_input_ex;
 
 pub use self::device_path::*;
+pub use self::rng::*;
 pub use self::serial::*;
+pub use self::text_input_ex::*;
 
 pub trait Protocol {
     fn guid() -> &'static Guid;
@@ -15,7 +19,8 @@ pub trait Protocol {
 /// GUID for UEFI protocol for loaded images
 pub static EFI_LOADED_IMAGE_PROTOCOL_GUID: Guid = Guid(0x5B1B31A1, 0x9562, 0x11d2, [0x8E,0x3F,0x00,0xA0,0xC9,0x69,0x72,0x3B]);
 
-static mut THIS_LOADED_IMAGE: *const LoadedImageProtocol = 0 as *const LoadedImageProtocol;
+pub(crate) static mut THIS_LOADED_IMAGE: *const LoadedImageProtocol = 0 as *const LoadedImageProtocol;
+static mut THIS_IMAGE_HANDLE: Handle = Handle::null();
 
 #[derive(Debug)]
 #[repr(C)]
@@ -33,7 +38,7 @@ pub struct LoadedImageProtocol {
     image_code_type: MemoryType,
     pub image_data_type: MemoryType,
 
-    //unload: unsafe extern "win64" fn(handle: ::base::Handle),
+    //unload: unsafe extern "efiapi" fn(handle: ::base::Handle),
     unload: *const NotYetDef,
 }
 
@@ -43,6 +48,28 @@ impl Protocol for LoadedImageProtocol {
     }
 }
 
+#[cfg(feature = "mock")]
+impl LoadedImageProtocol {
+    /// The loaded image protocol of an image started by the mock firmware.
+    pub(crate) fn mock(system_table: *const ::systemtable::SystemTable) -> LoadedImageProtocol {
+        LoadedImageProtocol {
+            revision: 0x1000,
+            parent_handle: Handle::null(),
+            system_table: system_table as *const NotYetDef,
+            device_handle: Handle::null(),
+            file_path: ::core::ptr::null(),
+            __reserved: ::core::ptr::null(),
+            load_options_size: 0,
+            load_options: ::core::ptr::null(),
+            image_base: 0,
+            image_size: 0,
+            image_code_type: MemoryType::LoaderCode,
+            image_data_type: MemoryType::LoaderData,
+            unload: ::core::ptr::null(),
+        }
+    }
+}
+
 pub fn set_current_image(handle: Handle) -> Result<&'static LoadedImageProtocol, Status> {
     let st = ::get_system_table();
 
@@ -50,15 +77,25 @@ pub fn set_current_image(handle: Handle) -> Result<&'static LoadedImageProtocol,
     if let Ok(image) = loaded_image_proto {
         unsafe {
             THIS_LOADED_IMAGE = image;
+            THIS_IMAGE_HANDLE = handle;
         }
     }
 
     loaded_image_proto
 }
 
+/// The loaded image protocol of the running image, as set by `set_current_image`. It is
+/// unavailable in virtual mode, after `set_virtual_address_map`, and must not be called then.
 pub fn get_current_image() -> &'static LoadedImageProtocol {
     unsafe {
         &*THIS_LOADED_IMAGE
     }
 }
 
+/// The handle passed to `set_current_image`, as needed by `BootServices::exit`.
+pub fn get_current_image_handle() -> Handle {
+    unsafe {
+        THIS_IMAGE_HANDLE
+    }
+}
+
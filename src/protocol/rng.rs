@@ -0,0 +1,61 @@
+// Copyright 2017 CoreOS, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::{mem, ptr};
+
+use base::Status;
+use guid::Guid;
+use protocol::Protocol;
+
+/// GUID for the random number generator protocol
+pub static EFI_RNG_PROTOCOL_GUID: Guid = guid!("3152bca5-eade-433d-862e-c01cdc291f44");
+
+/// The raw output of the platform's entropy source, without further processing.
+pub static EFI_RNG_ALGORITHM_RAW: Guid = guid!("e43176d7-b6e8-4827-b784-7ffdc4b68561");
+
+#[repr(C)]
+pub struct RngProtocol {
+    get_info: unsafe extern "efiapi" fn(this: *const RngProtocol, list_size: *mut usize, list: *mut Guid) -> Status,
+    get_rng: unsafe extern "efiapi" fn(this: *const RngProtocol, algorithm: *const Guid, length: usize, value: *mut u8) -> Status,
+}
+
+impl Protocol for RngProtocol {
+    fn guid() -> &'static Guid {
+        &EFI_RNG_PROTOCOL_GUID
+    }
+}
+
+impl RngProtocol {
+    /// Fill `buf` with the algorithms supported, and return the part filled.
+    pub fn algorithms<'a>(&self, buf: &'a mut [Guid]) -> Result<&'a [Guid], Status> {
+        let mut size = mem::size_of_val(buf);
+        let status = unsafe { (self.get_info)(self, &mut size, buf.as_mut_ptr()) };
+        if status != Status::Success {
+            return Err(status);
+        }
+
+        Ok(&buf[..size / mem::size_of::<Guid>()])
+    }
+
+    /// Fill `buf` with random bytes from `algorithm`, or the platform's default algorithm.
+    pub fn get_rng(&self, algorithm: Option<&Guid>, buf: &mut [u8]) -> Result<(), Status> {
+        let algorithm = algorithm.map_or(ptr::null(), |a| a as *const Guid);
+        let status = unsafe { (self.get_rng)(self, algorithm, buf.len(), buf.as_mut_ptr()) };
+        if status != Status::Success {
+            return Err(status);
+        }
+
+        Ok(())
+    }
+}
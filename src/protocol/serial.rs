@@ -73,8 +73,8 @@ pub static EFI_SERIAL_IO_PROTOCOL_GUID: Guid = Guid(0xBB25CF6F, 0xF1D4, 0x11D2,
 #[repr(C)]
 struct RawSerialIOProtocol {
     revision: u32,
-    reset: unsafe extern "win64" fn(this: *const RawSerialIOProtocol) -> Status,
-    set_attributes: unsafe extern "win64" fn(this: *const RawSerialIOProtocol,
+    reset: unsafe extern "efiapi" fn(this: *const RawSerialIOProtocol) -> Status,
+    set_attributes: unsafe extern "efiapi" fn(this: *const RawSerialIOProtocol,
                                              baud_rate: u64,
                                              receive_fifo_depth: u32,
                                              timeout: u32,
@@ -83,14 +83,14 @@ struct RawSerialIOProtocol {
                                              stop_bits: StopBits)
                                              -> Status,
     set_control_bits:
-        unsafe extern "win64" fn(this: *const RawSerialIOProtocol, control: u32) -> Status,
+        unsafe extern "efiapi" fn(this: *const RawSerialIOProtocol, control: u32) -> Status,
     get_control_bits:
-        unsafe extern "win64" fn(this: *const RawSerialIOProtocol, control: *mut u32) -> Status,
-    write: unsafe extern "win64" fn(this: *const RawSerialIOProtocol,
+        unsafe extern "efiapi" fn(this: *const RawSerialIOProtocol, control: *mut u32) -> Status,
+    write: unsafe extern "efiapi" fn(this: *const RawSerialIOProtocol,
                                     buffer_size: *mut usize,
                                     buffer: *const CVoid)
                                     -> Status,
-    read: unsafe extern "win64" fn(this: *const RawSerialIOProtocol,
+    read: unsafe extern "efiapi" fn(this: *const RawSerialIOProtocol,
                                    buffer_size: *mut usize,
                                    buffer: *mut CVoid)
                                    -> Status,
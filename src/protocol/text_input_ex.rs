@@ -0,0 +1,323 @@
+// Copyright 2017 CoreOS, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::ptr;
+
+use base::{Event, Status};
+use console::InputKey;
+use guid::Guid;
+use protocol::Protocol;
+#[cfg(feature = "mock")]
+use mock;
+use task::TPL;
+use void::CVoid;
+
+bitflags! {
+    /// Shift keys held down along with a key.
+    pub struct KeyShiftState: u32 {
+        /// The other bits are only meaningful if this one is set.
+        const SHIFT_STATE_VALID = 0x80000000;
+        const RIGHT_SHIFT_PRESSED = 0x00000001;
+        const LEFT_SHIFT_PRESSED = 0x00000002;
+        const RIGHT_CONTROL_PRESSED = 0x00000004;
+        const LEFT_CONTROL_PRESSED = 0x00000008;
+        const RIGHT_ALT_PRESSED = 0x00000010;
+        const LEFT_ALT_PRESSED = 0x00000020;
+        const RIGHT_LOGO_PRESSED = 0x00000040;
+        const LEFT_LOGO_PRESSED = 0x00000080;
+        const MENU_KEY_PRESSED = 0x00000100;
+        const SYS_REQ_PRESSED = 0x00000200;
+    }
+}
+
+impl KeyShiftState {
+    pub fn shift(&self) -> bool {
+        self.intersects(RIGHT_SHIFT_PRESSED | LEFT_SHIFT_PRESSED)
+    }
+
+    pub fn control(&self) -> bool {
+        self.intersects(RIGHT_CONTROL_PRESSED | LEFT_CONTROL_PRESSED)
+    }
+
+    pub fn alt(&self) -> bool {
+        self.intersects(RIGHT_ALT_PRESSED | LEFT_ALT_PRESSED)
+    }
+}
+
+bitflags! {
+    /// State of the lock keys.
+    pub struct KeyToggleState: u8 {
+        /// The other bits are only meaningful if this one is set.
+        const TOGGLE_STATE_VALID = 0x80;
+        /// Report presses of keys which don't produce a character, such as shift keys alone.
+        const KEY_STATE_EXPOSED = 0x40;
+        const SCROLL_LOCK_ACTIVE = 0x01;
+        const NUM_LOCK_ACTIVE = 0x02;
+        const CAPS_LOCK_ACTIVE = 0x04;
+    }
+}
+
+/// Type for EFI_KEY_STATE.
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct KeyState {
+    key_shift_state: u32,
+    key_toggle_state: u8,
+}
+
+impl KeyState {
+    pub fn new(shift_state: KeyShiftState, toggle_state: KeyToggleState) -> KeyState {
+        KeyState {
+            key_shift_state: shift_state.bits(),
+            key_toggle_state: toggle_state.bits(),
+        }
+    }
+
+    pub fn shift_state(&self) -> KeyShiftState {
+        KeyShiftState::from_bits_truncate(self.key_shift_state)
+    }
+
+    pub fn toggle_state(&self) -> KeyToggleState {
+        KeyToggleState::from_bits_truncate(self.key_toggle_state)
+    }
+}
+
+/// Type for EFI_KEY_DATA, a key along with the modifier state when it was pressed.
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct KeyData {
+    pub key: InputKey,
+    pub key_state: KeyState,
+}
+
+impl KeyData {
+    /// Whether a key press matches this key, as used for hotkeys: the modifiers only count if
+    /// this key's shift state is valid.
+    pub fn matches(&self, pressed: &KeyData) -> bool {
+        if self.key != pressed.key {
+            return false;
+        }
+
+        let shift_state = self.key_state.shift_state();
+        !shift_state.contains(SHIFT_STATE_VALID) || shift_state == pressed.key_state.shift_state()
+    }
+}
+
+/// GUID for the extended simple text input protocol
+pub static EFI_SIMPLE_TEXT_INPUT_EX_PROTOCOL_GUID: Guid = Guid(0xDD9E7534, 0x7762, 0x4698, [0x8C,0x14,0xF5,0x85,0x17,0xA6,0x25,0xAA]);
+
+pub(crate) type KeyNotifyFunction = extern "efiapi" fn(key_data: *const KeyData) -> Status;
+
+#[repr(C)]
+pub struct SimpleTextInputExProtocol {
+    reset: unsafe extern "efiapi" fn(this: *const SimpleTextInputExProtocol, extended_verification: u8) -> Status,
+    read_key_stroke_ex: unsafe extern "efiapi" fn(this: *const SimpleTextInputExProtocol, key_data: *mut KeyData) -> Status,
+    wait_for_key_ex: Event,
+    set_state: unsafe extern "efiapi" fn(this: *const SimpleTextInputExProtocol, toggle_state: *const u8) -> Status,
+    register_key_notify: unsafe extern "efiapi" fn(this: *const SimpleTextInputExProtocol, key_data: *const KeyData, notify: KeyNotifyFunction, handle: *mut *const CVoid) -> Status,
+    unregister_key_notify: unsafe extern "efiapi" fn(this: *const SimpleTextInputExProtocol, handle: *const CVoid) -> Status,
+}
+
+#[cfg(feature = "mock")]
+impl SimpleTextInputExProtocol {
+    /// A console input device implemented by the mock firmware, signalling `wait_for_key_ex`.
+    pub(crate) fn mock(wait_for_key_ex: Event) -> SimpleTextInputExProtocol {
+        SimpleTextInputExProtocol {
+            reset: mock::input_ex_reset,
+            read_key_stroke_ex: mock::read_key_stroke_ex,
+            wait_for_key_ex,
+            set_state: mock::set_state,
+            register_key_notify: mock::register_key_notify,
+            unregister_key_notify: mock::unregister_key_notify,
+        }
+    }
+}
+
+impl Protocol for SimpleTextInputExProtocol {
+    fn guid() -> &'static Guid {
+        &EFI_SIMPLE_TEXT_INPUT_EX_PROTOCOL_GUID
+    }
+}
+
+/// The maximum number of key notifications which can be registered at once.
+pub const MAX_KEY_NOTIFICATIONS: usize = 16;
+
+#[derive(Clone, Copy)]
+struct KeyNotification {
+    callback: &'static dyn Fn(&KeyData),
+    handle: *const CVoid,
+}
+
+#[derive(Clone, Copy)]
+enum KeyNotifySlot {
+    Free,
+    /// Claimed by a registration the firmware hasn't confirmed yet.
+    Reserved,
+    Registered(KeyNotification),
+}
+
+// Read by the `KEY_NOTIFY_FUNCTIONS` at `TPL::Notify`, so only changed through
+// `with_key_notifications`.
+static mut KEY_NOTIFICATIONS: [KeyNotifySlot; MAX_KEY_NOTIFICATIONS] = [KeyNotifySlot::Free; MAX_KEY_NOTIFICATIONS];
+
+/// Run `f` on the notification slots with the TPL raised to `TPL::Notify`, so no notification
+/// function can run while they change.
+fn with_key_notifications<R, F>(f: F) -> R
+    where F: FnOnce(&mut [KeyNotifySlot; MAX_KEY_NOTIFICATIONS]) -> R
+{
+    let bs = ::get_system_table().boot_services();
+    let old_tpl = bs.raise_tpl(TPL::Notify);
+    let result = f(unsafe { &mut *ptr::addr_of_mut!(KEY_NOTIFICATIONS) });
+    bs.restore_tpl(old_tpl);
+    result
+}
+
+/// Identifies a key notification registered with `register_key_notify`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KeyNotifyHandle(usize);
+
+/// Run the callback in slot `index`, if it has one.
+fn key_notify(index: usize, key_data: *const KeyData) -> Status {
+    let slot = unsafe { (*ptr::addr_of!(KEY_NOTIFICATIONS))[index] };
+    if let KeyNotifySlot::Registered(notification) = slot {
+        (notification.callback)(unsafe { &*key_data });
+    }
+
+    Status::Success
+}
+
+macro_rules! key_notify_functions {
+    ($($index:expr),*) => {
+        [$({
+            extern "efiapi" fn notify(key_data: *const KeyData) -> Status {
+                key_notify($index, key_data)
+            }
+            notify as KeyNotifyFunction
+        }),*]
+    }
+}
+
+/// The firmware's notification functions get no context, so each slot has its own, which the
+/// firmware calls once for every press matching that slot's key.
+static KEY_NOTIFY_FUNCTIONS: [KeyNotifyFunction; MAX_KEY_NOTIFICATIONS] =
+    key_notify_functions!(0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15);
+
+impl SimpleTextInputExProtocol {
+    /// Locate the extended input protocol on the console input device.
+    pub fn for_console() -> Result<&'static SimpleTextInputExProtocol, Status> {
+        let st = ::get_system_table();
+        st.boot_services().handle_protocol(st.console_in_handle())
+    }
+
+    pub fn reset(&self, extended_verification: bool) -> Result<(), Status> {
+        match unsafe { (self.reset)(self, extended_verification as u8) } {
+            Status::Success => Ok(()),
+            e => Err(e),
+        }
+    }
+
+    /// Read a key press along with its modifiers, without waiting. Returns `Status::NotReady`
+    /// if no key has been pressed. With `KEY_STATE_EXPOSED` toggled on, a key of all zeroes
+    /// reports a change in modifiers alone.
+    pub fn read_key_stroke_ex(&self) -> Result<KeyData, Status> {
+        let mut key_data = KeyData {
+            key: InputKey { scan_code: 0, unicode_char: 0 },
+            key_state: KeyState::default(),
+        };
+
+        match unsafe { (self.read_key_stroke_ex)(self, &mut key_data) } {
+            Status::Success => Ok(key_data),
+            e => Err(e),
+        }
+    }
+
+    /// Wait for a key press and return it along with its modifiers.
+    pub fn read_key_ex(&self) -> Result<KeyData, Status> {
+        let bs = ::get_system_table().boot_services();
+        let events = [self.wait_for_key_ex];
+
+        loop {
+            bs.wait_for_event(&events)?;
+            match self.read_key_stroke_ex() {
+                Err(Status::NotReady) => continue,
+                r => return r,
+            }
+        }
+    }
+
+    /// The event signalled when a key is available.
+    pub fn wait_for_key_ex(&self) -> Event {
+        self.wait_for_key_ex
+    }
+
+    /// Set the lock keys, e.g. `TOGGLE_STATE_VALID | NUM_LOCK_ACTIVE` to turn on Num Lock and
+    /// turn off Caps Lock and Scroll Lock.
+    pub fn set_state(&self, toggle_state: KeyToggleState) -> Result<(), Status> {
+        let state = toggle_state.bits();
+        match unsafe { (self.set_state)(self, &state) } {
+            Status::Success => Ok(()),
+            e => Err(e),
+        }
+    }
+
+    /// Call `callback` whenever `key` is pressed, even while the application isn't reading
+    /// input. Callbacks run at `TPL::Notify`, so they should do little more than set a flag.
+    pub fn register_key_notify(&self, key: KeyData, callback: &'static dyn Fn(&KeyData)) -> Result<KeyNotifyHandle, Status> {
+        let index = with_key_notifications(|slots| {
+            let index = slots.iter().position(|slot| matches!(*slot, KeyNotifySlot::Free))?;
+            slots[index] = KeyNotifySlot::Reserved;
+            Some(index)
+        });
+        let index = match index {
+            Some(index) => index,
+            None => return Err(Status::OutOfResources),
+        };
+
+        let mut handle: *const CVoid = ptr::null();
+        let status = unsafe { (self.register_key_notify)(self, &key, KEY_NOTIFY_FUNCTIONS[index], &mut handle) };
+        with_key_notifications(|slots| {
+            slots[index] = match status {
+                Status::Success => KeyNotifySlot::Registered(KeyNotification { callback, handle }),
+                _ => KeyNotifySlot::Free,
+            }
+        });
+
+        match status {
+            Status::Success => Ok(KeyNotifyHandle(index)),
+            e => Err(e),
+        }
+    }
+
+    /// Remove a notification registered with `register_key_notify`.
+    pub fn unregister_key_notify(&self, handle: KeyNotifyHandle) -> Result<(), Status> {
+        let removed = with_key_notifications(|slots| {
+            match slots.get(handle.0) {
+                Some(&KeyNotifySlot::Registered(notification)) => {
+                    slots[handle.0] = KeyNotifySlot::Free;
+                    Some(notification)
+                },
+                _ => None,
+            }
+        });
+        let notification = match removed {
+            Some(notification) => notification,
+            None => return Err(Status::InvalidParameter),
+        };
+
+        match unsafe { (self.unregister_key_notify)(self, notification.handle) } {
+            Status::Success => Ok(()),
+            e => Err(e),
+        }
+    }
+}
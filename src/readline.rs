@@ -0,0 +1,289 @@
+// Copyright 2017 CoreOS, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::{cmp, str};
+
+use base::Status;
+use console::{Key, SimpleTextInput, SimpleTextOutput};
+
+/// Previously entered lines, kept in a caller-supplied buffer. When the buffer fills up, the
+/// oldest lines are dropped to make room.
+pub struct History<'a> {
+    // each line is followed by a null byte, oldest first
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> History<'a> {
+    pub fn new(buf: &'a mut [u8]) -> History<'a> {
+        History { buf, len: 0 }
+    }
+
+    /// The number of lines stored.
+    pub fn len(&self) -> usize {
+        self.buf[..self.len].iter().filter(|&&b| b == 0).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return the `n`th most recent line, starting from 0.
+    pub fn get(&self, n: usize) -> Option<&str> {
+        if self.len == 0 {
+            return None;
+        }
+
+        self.buf[..self.len - 1].rsplit(|&b| b == 0).nth(n).and_then(|line| str::from_utf8(line).ok())
+    }
+
+    /// Add a line. Empty lines, repeats of the most recent line and lines too long to ever fit
+    /// are ignored.
+    pub fn push(&mut self, line: &str) {
+        let needed = line.len() + 1;
+        if line.is_empty() || line.contains('\0') || needed > self.buf.len() || self.get(0) == Some(line) {
+            return;
+        }
+
+        while self.len + needed > self.buf.len() {
+            let oldest = self.buf.iter().position(|&b| b == 0).unwrap_or(self.len - 1) + 1;
+            self.buf.copy_within(oldest..self.len, 0);
+            self.len -= oldest;
+        }
+
+        self.buf[self.len..self.len + line.len()].copy_from_slice(line.as_bytes());
+        self.buf[self.len + line.len()] = 0;
+        self.len += needed;
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+/// Reads a line of input from a console, with editing.
+///
+/// Left/Right, Home/End, Backspace and Delete edit the line; Up and Down recall lines from the
+/// history, if there is one. Enter finishes the line and Escape abandons it.
+pub struct LineEditor<'h> {
+    history: Option<History<'h>>,
+    mask: Option<char>,
+}
+
+impl<'h> Default for LineEditor<'h> {
+    fn default() -> LineEditor<'h> {
+        LineEditor::new()
+    }
+}
+
+impl<'h> LineEditor<'h> {
+    pub fn new() -> LineEditor<'h> {
+        LineEditor {
+            history: None,
+            mask: None,
+        }
+    }
+
+    /// Create an editor which records entered lines in `history`.
+    pub fn with_history(history: History<'h>) -> LineEditor<'h> {
+        LineEditor {
+            history: Some(history),
+            mask: None,
+        }
+    }
+
+    pub fn history(&self) -> Option<&History<'h>> {
+        self.history.as_ref()
+    }
+
+    pub fn history_mut(&mut self) -> Option<&mut History<'h>> {
+        self.history.as_mut()
+    }
+
+    /// Show each character as `mask` instead of echoing it, for passwords. Masked lines are not
+    /// recorded in or recalled from the history.
+    pub fn set_mask(&mut self, mask: Option<char>) {
+        self.mask = mask;
+    }
+
+    /// Read a line into `buf`, starting at the current cursor position, and return it without
+    /// the newline. Characters which don't fit in `buf` are ignored. Returns `Status::Aborted`
+    /// if Escape is pressed, and `Status::Unsupported` if the console has no columns.
+    pub fn read_line<'b, C>(&mut self, console: &C, buf: &'b mut [u8]) -> Result<&'b str, Status>
+        where C: SimpleTextInput + SimpleTextOutput + ?Sized
+    {
+        let (columns, _) = console.size()?;
+        // the line is wrapped at this width
+        if columns == 0 {
+            return Err(Status::Unsupported);
+        }
+        let mode = console.mode();
+        let mut display = Display {
+            column: mode.cursor_column as usize,
+            row: mode.cursor_row as usize,
+            columns,
+            shown: 0,
+            mask: self.mask,
+        };
+
+        let mut len = 0;
+        let mut cursor = 0;
+        // which history entry is being shown, if any
+        let mut recalled: Option<usize> = None;
+
+        loop {
+            match console.read_key()?.key() {
+                Key::Enter => {
+                    let line = str::from_utf8(&buf[..len]).unwrap_or("");
+                    display.draw(console, line, len)?;
+                    console.write("\n").to_result()?;
+                    if let (&mut Some(ref mut history), None) = (&mut self.history, self.mask) {
+                        history.push(line);
+                    }
+                    return Ok(line);
+                },
+                Key::Escape => {
+                    display.draw(console, str::from_utf8(&buf[..len]).unwrap_or(""), len)?;
+                    console.write("\n").to_result()?;
+                    return Err(Status::Aborted);
+                },
+                Key::Char(c) if !c.is_control() => {
+                    let n = c.len_utf8();
+                    if len + n > buf.len() {
+                        continue;
+                    }
+                    buf.copy_within(cursor..len, cursor + n);
+                    c.encode_utf8(&mut buf[cursor..cursor + n]);
+                    len += n;
+                    cursor += n;
+                },
+                Key::Backspace => {
+                    let n = match prev_char_len(&buf[..cursor]) {
+                        Some(n) => n,
+                        None => continue,
+                    };
+                    buf.copy_within(cursor..len, cursor - n);
+                    len -= n;
+                    cursor -= n;
+                },
+                Key::Delete => {
+                    let n = match next_char_len(&buf[cursor..len]) {
+                        Some(n) => n,
+                        None => continue,
+                    };
+                    buf.copy_within(cursor + n..len, cursor);
+                    len -= n;
+                },
+                Key::Left => cursor -= prev_char_len(&buf[..cursor]).unwrap_or(0),
+                Key::Right => cursor += next_char_len(&buf[cursor..len]).unwrap_or(0),
+                Key::Home => cursor = 0,
+                Key::End => cursor = len,
+                key @ Key::Up | key @ Key::Down if self.mask.is_none() => {
+                    let history = match self.history {
+                        Some(ref history) => history,
+                        None => continue,
+                    };
+                    let wanted = match (key, recalled) {
+                        (Key::Up, None) => Some(0),
+                        (Key::Up, Some(n)) => Some(n + 1),
+                        (_, Some(0)) => None,
+                        (_, Some(n)) => Some(n - 1),
+                        (_, None) => continue,
+                    };
+
+                    // moving down past the most recent line leaves an empty one
+                    let line = match wanted {
+                        Some(n) => match history.get(n) {
+                            Some(line) if line.len() <= buf.len() => line,
+                            _ => continue,
+                        },
+                        None => "",
+                    };
+                    buf[..line.len()].copy_from_slice(line.as_bytes());
+                    len = line.len();
+                    cursor = len;
+                    recalled = wanted;
+                },
+                _ => continue,
+            }
+
+            display.draw(console, str::from_utf8(&buf[..len]).unwrap_or(""), cursor)?;
+        }
+    }
+}
+
+/// Where a line being edited is on screen.
+struct Display {
+    column: usize,
+    row: usize,
+    columns: usize,
+    // the number of characters on screen
+    shown: usize,
+    mask: Option<char>,
+}
+
+impl Display {
+    /// Redraw the line and move the cursor to byte `cursor` within it.
+    fn draw<C: SimpleTextOutput + ?Sized>(&mut self, console: &C, line: &str, cursor: usize) -> Result<(), Status> {
+        let chars = line.chars().count();
+
+        console.set_cursor_position(self.column, self.row).to_result()?;
+        match self.mask {
+            Some(mask) => write_repeated(console, mask, chars)?,
+            None => console.write(line).to_result()?,
+        }
+        // blank out anything left over from a longer line
+        write_repeated(console, ' ', self.shown.saturating_sub(chars))?;
+
+        // if the line ran off the bottom of the screen, it scrolled up
+        let end = self.column + cmp::max(chars, self.shown);
+        let expected_row = self.row + end / self.columns;
+        let row = console.mode().cursor_row as usize;
+        if row < expected_row {
+            self.row = self.row.saturating_sub(expected_row - row);
+        }
+        self.shown = chars;
+
+        let position = self.column + line[..cursor].chars().count();
+        console.set_cursor_position(position % self.columns, self.row + position / self.columns).to_result()
+    }
+}
+
+fn write_repeated<C: SimpleTextOutput + ?Sized>(console: &C, c: char, count: usize) -> Result<(), Status> {
+    let mut buf = [0u8; 64];
+    let width = c.len_utf8();
+    let per_chunk = buf.len() / width;
+    for i in 0..per_chunk {
+        c.encode_utf8(&mut buf[i * width..]);
+    }
+
+    let mut remaining = count;
+    while remaining > 0 {
+        let n = cmp::min(remaining, per_chunk);
+        console.write(str::from_utf8(&buf[..n * width]).unwrap_or("")).to_result()?;
+        remaining -= n;
+    }
+
+    Ok(())
+}
+
+/// The length of the last UTF-8 character in `bytes`.
+fn prev_char_len(bytes: &[u8]) -> Option<usize> {
+    str::from_utf8(bytes).ok().and_then(|s| s.chars().next_back()).map(char::len_utf8)
+}
+
+/// The length of the first UTF-8 character in `bytes`.
+fn next_char_len(bytes: &[u8]) -> Option<usize> {
+    str::from_utf8(bytes).ok().and_then(|s| s.chars().next()).map(char::len_utf8)
+}
@@ -0,0 +1,118 @@
+// Copyright 2017 CoreOS, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Support for EFI_RUNTIME_DRIVER images, which keep running after the OS has called
+//! `set_virtual_address_map`. Any pointer such a driver keeps must be converted to its virtual
+//! address when that happens; `init_runtime_driver` arranges for the pointers registered with
+//! `register_runtime_pointer` to be converted automatically.
+
+use core::ptr;
+
+use base::{Event, Status};
+use event::EventType;
+use protocol::THIS_LOADED_IMAGE;
+use runtimeservices::RuntimeServices;
+use systemtable::SYSTEM_TABLE;
+use task::TPL;
+use void::CVoid;
+
+/// The maximum number of pointers which can be registered with `register_runtime_pointer`.
+pub const MAX_RUNTIME_POINTERS: usize = 32;
+
+static mut RUNTIME_POINTERS: [*mut *const CVoid; MAX_RUNTIME_POINTERS] = [ptr::null_mut(); MAX_RUNTIME_POINTERS];
+static mut RUNTIME_POINTER_COUNT: usize = 0;
+
+/// The runtime services table, kept so the event handler doesn't depend on pointers it is in the
+/// middle of converting.
+static mut RUNTIME_SERVICES: *const RuntimeServices = ptr::null();
+
+static mut VIRTUAL_ADDRESS_CHANGE_EVENT: Option<Event> = None;
+static mut VIRTUAL_MODE: bool = false;
+
+/// Register a pointer to be converted to its virtual address during `set_virtual_address_map`.
+/// Null pointers are allowed and left null, as are pointers the firmware can't convert. Returns `Status::OutOfResources` once
+/// `MAX_RUNTIME_POINTERS` pointers are registered.
+///
+/// # Safety
+///
+/// `pointer` must stay valid (typically, be a `static`) for as long as the driver is loaded, and
+/// what it points to must be in runtime memory.
+pub unsafe fn register_runtime_pointer<T>(pointer: *mut *const T) -> Result<(), Status> {
+    let count = RUNTIME_POINTER_COUNT;
+    if count == MAX_RUNTIME_POINTERS {
+        return Err(Status::OutOfResources);
+    }
+
+    RUNTIME_POINTERS[count] = pointer as *mut *const CVoid;
+    RUNTIME_POINTER_COUNT = count + 1;
+    Ok(())
+}
+
+extern "efiapi" fn virtual_address_change(_event: Event, _context: *const CVoid) {
+    unsafe {
+        let runtime_services = &*RUNTIME_SERVICES;
+
+        let pointers = &*ptr::addr_of!(RUNTIME_POINTERS);
+        for &pointer in &pointers[..RUNTIME_POINTER_COUNT] {
+            convert_or_null(runtime_services, &mut *pointer);
+        }
+
+        // The loaded image is normally in boot services memory, so this usually nulls it.
+        convert_or_null(runtime_services, &mut *ptr::addr_of_mut!(THIS_LOADED_IMAGE));
+        convert_or_null(runtime_services, &mut *ptr::addr_of_mut!(SYSTEM_TABLE));
+
+        VIRTUAL_MODE = true;
+    }
+}
+
+/// Convert `pointer` to its virtual address. A pointer outside runtime memory can't be
+/// converted, so it is set to null rather than left pointing at memory which is gone.
+fn convert_or_null<T>(runtime_services: &RuntimeServices, pointer: &mut *const T) {
+    if runtime_services.convert_pointer(pointer, true).is_err() {
+        *pointer = ptr::null();
+    }
+}
+
+/// Prepare a runtime driver for the switch to virtual addressing. This creates a
+/// `SignalVirtualAddressChange` event which converts the crate's system table and loaded image
+/// pointers, along with every pointer passed to `register_runtime_pointer`. Call it once, after
+/// `set_system_table` and `set_current_image`. The loaded image is usually in boot services
+/// memory, so `get_current_image` can't be used in virtual mode.
+pub fn init_runtime_driver() -> Result<Event, Status> {
+    unsafe {
+        if let Some(event) = VIRTUAL_ADDRESS_CHANGE_EVENT {
+            return Ok(event);
+        }
+    }
+
+    let st = ::get_system_table();
+    unsafe {
+        RUNTIME_SERVICES = st.runtime_services();
+    }
+
+    st.boot_services()
+        .create_event(EventType::SignalVirtualAddressChange, TPL::Notify, Some(virtual_address_change), ptr::null())
+        .map(|event| {
+            unsafe {
+                VIRTUAL_ADDRESS_CHANGE_EVENT = Some(event);
+            }
+            event
+        })
+}
+
+/// Whether `set_virtual_address_map` has been called, so that only virtual addresses may be
+/// used. Only meaningful after `init_runtime_driver`.
+pub fn in_virtual_mode() -> bool {
+    unsafe { VIRTUAL_MODE }
+}
@@ -1,11 +1,37 @@
-use core::ptr;
+use core::{mem, ptr, slice};
 
-use void::NotYetDef;
-use base::{Status, Time, TimeCapabilities};
+use void::{CVoid, NotYetDef};
+use base::{MemoryDescriptor, Status, Time, TimeCapabilities};
+use bootservices::MemoryMap;
+use capsule::{CapsuleHeader, ScatterGatherList};
 use guid::Guid;
 use table::TableHeader;
+use util::{utf16_bytes_strlen, CStr16};
+#[cfg(feature = "mock")]
+use mock;
+
+/// GUID for variables defined by the UEFI specification, such as `BootOrder`.
+pub static EFI_GLOBAL_VARIABLE_GUID: Guid = Guid(0x8BE4DF61, 0x93CA, 0x11D2, [0xAA,0x0D,0x00,0xE0,0x98,0x03,0x2B,0x8C]);
+
+bitflags! {
+    /// Attributes of a UEFI variable, passed to and returned from the variable services.
+    pub struct VariableAttributes: u32 {
+        const VARIABLE_NON_VOLATILE = 0x00000001;
+        const VARIABLE_BOOTSERVICE_ACCESS = 0x00000002;
+        const VARIABLE_RUNTIME_ACCESS = 0x00000004;
+        const VARIABLE_HARDWARE_ERROR_RECORD = 0x00000008;
+        const VARIABLE_AUTHENTICATED_WRITE_ACCESS = 0x00000010;
+        const VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS = 0x00000020;
+        const VARIABLE_APPEND_WRITE = 0x00000040;
+        const VARIABLE_ENHANCED_AUTHENTICATED_ACCESS = 0x00000080;
+    }
+}
+
+/// `convert_pointer` disposition allowing the pointer to be null.
+const EFI_OPTIONAL_PTR: usize = 0x00000001;
 
 /// Reset type passed to RuntimeServices.reset_system
+#[derive(Clone, Copy, Debug, PartialEq)]
 #[repr(C)]
 pub enum ResetType {
     Cold = 0,
@@ -14,28 +40,64 @@ pub enum ResetType {
     PlatformSpecific = 3,
 }
 
+/// The longest reason `reset_system_with_reason` and `reset_platform_specific` will pass on.
+pub const MAX_RESET_REASON: usize = 255;
+
+// in UCS-2 characters: the reason, its terminator and a GUID
+const RESET_DATA_BUFFER_SIZE: usize = MAX_RESET_REASON + 1 + 8;
+
+/// Encode reset data for `RuntimeServices::reset_system_with_data` into `buf`: `reason` as a
+/// null-terminated UCS-2 string, followed by `extra`, e.g. a platform-specific reset GUID.
+/// Characters outside the Basic Multilingual Plane become U+FFFD, and the reason is truncated if
+/// `buf` is too small. Returns the bytes used, which are empty if not even `extra` fits.
+pub fn encode_reset_data<'a>(reason: &str, extra: &[u8], buf: &'a mut [u16]) -> &'a [u8] {
+    if buf.len() * 2 < extra.len() + 2 {
+        return &[];
+    }
+    // Room for the terminator and `extra` comes first.
+    let max_chars = (buf.len() * 2 - extra.len()) / 2 - 1;
+
+    let mut len = 0;
+    for c in reason.chars().take(max_chars) {
+        buf[len] = if c.len_utf16() > 1 { 0xFFFD } else { c as u16 };
+        len += 1;
+    }
+    buf[len] = 0;
+    len += 1;
+
+    let bytes = unsafe { slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut u8, buf.len() * 2) };
+    let end = len * 2 + extra.len();
+    bytes[len * 2..end].copy_from_slice(extra);
+    &bytes[..end]
+}
+
 /// UEFI Runtime Services.
 /// http://wiki.phoenix.com/wiki/index.php/EFI_RUNTIME_SERVICES
 #[repr(C)]
 pub struct RuntimeServices {
     header: TableHeader,
-    get_time: unsafe extern "win64" fn(time: &mut Time, capabilities: *mut TimeCapabilities) -> Status,
-    set_time: *const NotYetDef,
-    get_wakeup_time: *const NotYetDef,
-    set_wakeup_time: *const NotYetDef,
-    set_virtual_address_map: *const NotYetDef,
-    convert_pointer: *const NotYetDef,
-    get_variable: unsafe extern "win64" fn(name: *const u16, guid: &Guid, attributes: *mut u32, size: *mut usize, data: *mut u8) -> Status,
-    get_next_variable_name: *const NotYetDef,
-    set_variable: unsafe extern "win64" fn(name: *const u16, guid: &Guid, attributes: *const u32, size: *const usize, data: *const u8) -> Status,
-    get_next_highest_monotonic_count: unsafe extern "win64" fn(count: *mut u32) -> Status,
-    reset_system: unsafe extern "win64" fn(resettype: ResetType, status: Status, datasize: usize, data: *const u8) -> !,
-    update_capsule: *const NotYetDef,
-    query_capsule_capabilities: *const NotYetDef,
+    get_time: unsafe extern "efiapi" fn(time: &mut Time, capabilities: *mut TimeCapabilities) -> Status,
+    set_time: unsafe extern "efiapi" fn(time: &Time) -> Status,
+    get_wakeup_time: unsafe extern "efiapi" fn(enabled: &mut u8, pending: &mut u8, time: &mut Time) -> Status,
+    set_wakeup_time: unsafe extern "efiapi" fn(enable: u8, time: *const Time) -> Status,
+    set_virtual_address_map: unsafe extern "efiapi" fn(map_size: usize, descriptor_size: usize, descriptor_version: u32, virtual_map: *mut MemoryDescriptor) -> Status,
+    convert_pointer: unsafe extern "efiapi" fn(debug_disposition: usize, address: *mut *const CVoid) -> Status,
+    get_variable: unsafe extern "efiapi" fn(name: *const u16, guid: &Guid, attributes: *mut u32, size: *mut usize, data: *mut u8) -> Status,
+    get_next_variable_name: unsafe extern "efiapi" fn(name_size: *mut usize, name: *mut u16, guid: *mut Guid) -> Status,
+    set_variable: unsafe extern "efiapi" fn(name: *const u16, guid: &Guid, attributes: u32, size: usize, data: *const u8) -> Status,
+    get_next_highest_monotonic_count: unsafe extern "efiapi" fn(count: *mut u32) -> Status,
+    reset_system: unsafe extern "efiapi" fn(resettype: ResetType, status: Status, datasize: usize, data: *const u8) -> !,
+    update_capsule: unsafe extern "efiapi" fn(capsules: *const *const CapsuleHeader, count: usize, scatter_gather_list: u64) -> Status,
+    query_capsule_capabilities: unsafe extern "efiapi" fn(capsules: *const *const CapsuleHeader, count: usize, maximum_capsule_size: *mut u64, reset_type: *mut u32) -> Status,
     query_variable_info: *const NotYetDef,
 }
 
 impl RuntimeServices {
+    /// The header of the runtime services table, which was checked by `set_system_table`.
+    pub fn header(&self) -> &TableHeader {
+        &self.header
+    }
+
     pub fn get_time(&self) -> Result<Time, Status> {
         let mut t : Time = Time::default();
         let status = unsafe { (self.get_time)(&mut t, ptr::null_mut()) };
@@ -46,10 +108,256 @@ impl RuntimeServices {
         Ok(t)
     }
 
+    /// Get the current time along with the capabilities of the real time clock.
+    pub fn get_time_and_capabilities(&self) -> Result<(Time, TimeCapabilities), Status> {
+        let mut t : Time = Time::default();
+        let mut capabilities = TimeCapabilities::default();
+        let status = unsafe { (self.get_time)(&mut t, &mut capabilities) };
+        if status != Status::Success {
+            return Err(status)
+        }
+
+        Ok((t, capabilities))
+    }
+
+    /// Set the real time clock. `time` is checked with `Time::validate` first.
+    pub fn set_time(&self, time: &Time) -> Result<(), Status> {
+        time.validate()?;
+
+        let status = unsafe { (self.set_time)(time) };
+        if status != Status::Success {
+            return Err(status)
+        }
+
+        Ok(())
+    }
+
+    /// Get the wakeup alarm. Returns whether it is enabled, whether it has fired and is waiting
+    /// to be acknowledged, and the time it is set to.
+    pub fn get_wakeup_time(&self) -> Result<(bool, bool, Time), Status> {
+        let mut enabled: u8 = 0;
+        let mut pending: u8 = 0;
+        let mut t : Time = Time::default();
+        let status = unsafe { (self.get_wakeup_time)(&mut enabled, &mut pending, &mut t) };
+        if status != Status::Success {
+            return Err(status)
+        }
+
+        Ok((enabled != 0, pending != 0, t))
+    }
+
+    /// Set the wakeup alarm to `time`, or disable it with `None`. Platforms without a wakeup
+    /// alarm return `Status::Unsupported`.
+    pub fn set_wakeup_time(&self, time: Option<&Time>) -> Result<(), Status> {
+        let status = match time {
+            Some(t) => {
+                t.validate()?;
+                unsafe { (self.set_wakeup_time)(1, t) }
+            },
+            None => unsafe { (self.set_wakeup_time)(0, ptr::null()) },
+        };
+        if status != Status::Success {
+            return Err(status)
+        }
+
+        Ok(())
+    }
+
+    /// Read the variable `name` owned by `vendor` into `data`. On success, returns the number of
+    /// bytes written to `data` and the variable's attributes. If `data` is too small,
+    /// `Status::BufferTooSmall` is returned; use `get_variable_size` to find out how big it needs
+    /// to be.
+    pub fn get_variable(&self, name: &CStr16, vendor: &Guid, data: &mut [u8]) -> Result<(usize, VariableAttributes), Status> {
+        let mut attributes: u32 = 0;
+        let mut size = data.len();
+        let status = unsafe { (self.get_variable)(name.as_ptr(), vendor, &mut attributes, &mut size, data.as_mut_ptr()) };
+        if status != Status::Success {
+            return Err(status);
+        }
+
+        Ok((size, VariableAttributes::from_bits_truncate(attributes)))
+    }
+
+    /// Return the size in bytes of the variable `name` owned by `vendor`.
+    pub fn get_variable_size(&self, name: &CStr16, vendor: &Guid) -> Result<usize, Status> {
+        let mut size: usize = 0;
+        let status = unsafe { (self.get_variable)(name.as_ptr(), vendor, ptr::null_mut(), &mut size, ptr::null_mut()) };
+        match status {
+            Status::BufferTooSmall | Status::Success => Ok(size),
+            e => Err(e),
+        }
+    }
+
+    /// Step through the names of all variables. `name` holds the previous name as a
+    /// null-terminated UCS-2 string (start with an empty string) and `vendor` its vendor GUID;
+    /// both are replaced with the next variable. `Status::NotFound` marks the end of the list.
+    pub fn get_next_variable_name(&self, name: &mut [u16], vendor: &mut Guid) -> Result<(), Status> {
+        let mut size = name.len() * 2;
+        let status = unsafe { (self.get_next_variable_name)(&mut size, name.as_mut_ptr(), vendor) };
+        if status != Status::Success {
+            return Err(status);
+        }
+
+        Ok(())
+    }
+
+    /// Create, replace or (with `APPEND_WRITE`) append to the variable `name` owned by `vendor`.
+    pub fn set_variable(&self, name: &CStr16, vendor: &Guid, attributes: VariableAttributes, data: &[u8]) -> Result<(), Status> {
+        let status = unsafe { (self.set_variable)(name.as_ptr(), vendor, attributes.bits(), data.len(), data.as_ptr()) };
+        if status != Status::Success {
+            return Err(status);
+        }
+
+        Ok(())
+    }
+
+    /// Delete the variable `name` owned by `vendor`.
+    pub fn delete_variable(&self, name: &CStr16, vendor: &Guid) -> Result<(), Status> {
+        self.set_variable(name, vendor, VariableAttributes::empty(), &[])
+    }
+
+    /// Switch the runtime services from physical to virtual addressing. This may only be called
+    /// once, after `exit_boot_services`, with a memory map whose `MEMORY_RUNTIME` regions have
+    /// had their virtual addresses filled in. Runtime drivers are notified through
+    /// `SignalVirtualAddressChange` events while this runs.
+    pub fn set_virtual_address_map(&self, map: &mut MemoryMap) -> Result<(), Status> {
+        let descriptor_size = map.descriptor_size;
+        let descriptor_version = map.descriptor_version;
+        let bytes = map.as_mut_bytes();
+
+        let status = unsafe { (self.set_virtual_address_map)(bytes.len(), descriptor_size, descriptor_version, bytes.as_mut_ptr() as *mut MemoryDescriptor) };
+        if status != Status::Success {
+            return Err(status);
+        }
+
+        Ok(())
+    }
+
+    /// Convert a physical pointer to its new virtual address. This may only be called from a
+    /// `SignalVirtualAddressChange` event handler. A null pointer is left alone if `optional` is
+    /// set; otherwise it is an error.
+    pub fn convert_pointer<T>(&self, pointer: &mut *const T, optional: bool) -> Result<(), Status> {
+        let disposition = if optional { EFI_OPTIONAL_PTR } else { 0 };
+        let address = pointer as *mut *const T as *mut *const CVoid;
+
+        let status = unsafe { (self.convert_pointer)(disposition, address) };
+        if status != Status::Success {
+            return Err(status);
+        }
+
+        Ok(())
+    }
+
+    /// Pass capsules to the firmware. Capsules flagged `CAPSULE_FLAGS_PERSIST_ACROSS_RESET`
+    /// are processed after the next reset and need `scatter_gather_list`, which describes where
+    /// their data is; others are processed immediately.
+    pub fn update_capsule(&self, capsules: &[&CapsuleHeader], scatter_gather_list: Option<&ScatterGatherList>) -> Result<(), Status> {
+        let list = scatter_gather_list.map_or(0, |list| list.address());
+
+        // &CapsuleHeader has the same representation as *const CapsuleHeader.
+        let status = unsafe { (self.update_capsule)(capsules.as_ptr() as *const *const CapsuleHeader, capsules.len(), list) };
+        if status != Status::Success {
+            return Err(status);
+        }
+
+        Ok(())
+    }
+
+    /// Ask whether `capsules` can be passed to `update_capsule`. Returns the maximum total size
+    /// of capsules the firmware supports, and the kind of reset needed to process them.
+    pub fn query_capsule_capabilities(&self, capsules: &[&CapsuleHeader]) -> Result<(u64, ResetType), Status> {
+        let mut maximum_capsule_size: u64 = 0;
+        let mut reset_type: u32 = 0;
+
+        let status = unsafe { (self.query_capsule_capabilities)(capsules.as_ptr() as *const *const CapsuleHeader, capsules.len(), &mut maximum_capsule_size, &mut reset_type) };
+        if status != Status::Success {
+            return Err(status);
+        }
+
+        let reset_type = match reset_type {
+            0 => ResetType::Cold,
+            1 => ResetType::Warm,
+            2 => ResetType::Shutdown,
+            3 => ResetType::PlatformSpecific,
+            _ => return Err(Status::DeviceError),
+        };
+
+        Ok((maximum_capsule_size, reset_type))
+    }
+
     pub fn reset_system(&self, reset_type: ResetType, status: Status) -> ! {
         unsafe {
             (self.reset_system)(reset_type, status, 0, ptr::null());
         }
     }
+
+    /// Reset the system, passing `data` to the firmware. `data` must start with a null-terminated
+    /// UCS-2 string, which may be followed by binary data; see `encode_reset_data`. If it doesn't,
+    /// or it is unaligned and too big to copy, the system is reset without it.
+    pub fn reset_system_with_data(&self, reset_type: ResetType, status: Status, data: &[u8]) -> ! {
+        // the firmware reads the string as CHAR16s, so it needs to be aligned
+        let mut buf = [0u16; RESET_DATA_BUFFER_SIZE];
+        let data = if utf16_bytes_strlen(data).is_none() {
+            &[]
+        } else if data.as_ptr().align_offset(mem::align_of::<u16>()) == 0 {
+            data
+        } else if data.len() <= buf.len() * 2 {
+            let bytes = unsafe { slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut u8, data.len()) };
+            bytes.copy_from_slice(data);
+            &*bytes
+        } else {
+            &[]
+        };
+
+        if data.is_empty() {
+            self.reset_system(reset_type, status)
+        }
+
+        unsafe {
+            (self.reset_system)(reset_type, status, data.len(), data.as_ptr());
+        }
+    }
+
+    /// Reset the system, recording `reason`. Reasons longer than `MAX_RESET_REASON` characters
+    /// are truncated.
+    pub fn reset_system_with_reason(&self, reset_type: ResetType, status: Status, reason: &str) -> ! {
+        let mut buf = [0u16; RESET_DATA_BUFFER_SIZE];
+        let data = encode_reset_data(reason, &[], &mut buf);
+        self.reset_system_with_data(reset_type, status, data)
+    }
+
+    /// Perform the platform-specific reset identified by `reset_subtype`, such as a vendor's
+    /// reset-into-recovery GUID, recording `reason`.
+    pub fn reset_platform_specific(&self, status: Status, reason: &str, reset_subtype: &Guid) -> ! {
+        let mut buf = [0u16; RESET_DATA_BUFFER_SIZE];
+        let data = encode_reset_data(reason, &reset_subtype.to_bytes(), &mut buf);
+        self.reset_system_with_data(ResetType::PlatformSpecific, status, data)
+    }
+}
+
+#[cfg(feature = "mock")]
+impl RuntimeServices {
+    /// A table of the mock firmware's services, with its CRC32 filled in.
+    pub(crate) fn mock() -> RuntimeServices {
+        let mut table = RuntimeServices {
+            header: TableHeader::new(::table::EFI_RUNTIME_SERVICES_SIGNATURE, mock::REVISION, mem::size_of::<RuntimeServices>()),
+            get_time: mock::get_time,
+            set_time: mock::set_time,
+            get_wakeup_time: mock::get_wakeup_time,
+            set_wakeup_time: mock::set_wakeup_time,
+            set_virtual_address_map: mock::set_virtual_address_map,
+            convert_pointer: mock::convert_pointer,
+            get_variable: mock::get_variable,
+            get_next_variable_name: mock::get_next_variable_name,
+            set_variable: mock::set_variable,
+            get_next_highest_monotonic_count: mock::get_next_highest_monotonic_count,
+            reset_system: mock::reset_system,
+            update_capsule: mock::update_capsule,
+            query_capsule_capabilities: mock::query_capsule_capabilities,
+            query_variable_info: ptr::null(),
+        };
+        unsafe { table.header.update_crc32() };
+        table
+    }
 }
 
@@ -1,8 +1,18 @@
-use base;
-use table;
+use core::slice;
+#[cfg(feature = "mock")]
+use core::{mem, ptr};
+
+use base::{self, Status};
+use guid::Guid;
+use table::{self, Revision, TableHeader, EFI_SYSTEM_TABLE_SIGNATURE, EFI_BOOT_SERVICES_SIGNATURE,
+            EFI_RUNTIME_SERVICES_SIGNATURE};
+use util::CStr16;
+use void::CVoid;
 use bootservices;
 use runtimeservices;
 use console;
+#[cfg(feature = "mock")]
+use mock;
 
 /// UEFI System Table.
 /// http://wiki.phoenix.com/wiki/index.php/EFI_SYSTEM_TABLE
@@ -10,7 +20,7 @@ use console;
 pub struct SystemTable {
     header: table::TableHeader,
     vendor: *const u16,
-    revision: u32,
+    firmware_revision: u32,
     con_in_handle: base::Handle,
     con_in: &'static console::SimpleTextInputProtocol,
     con_out_handle: base::Handle,
@@ -20,7 +30,7 @@ pub struct SystemTable {
     runtime_services: &'static runtimeservices::RuntimeServices,
     boot_services: &'static bootservices::BootServices,
     configuration_table_entries: usize,
-    configuration_table: &'static table::ConfigurationTableInternal,
+    configuration_table: *const table::ConfigurationTable,
 }
 
 impl SystemTable {
@@ -28,6 +38,17 @@ impl SystemTable {
         console::Console::new(self, self.con_in, self.con_out)
     }
 
+    /// A console which writes to standard error, and reads from the console input device.
+    pub fn stderr(&'static self) -> console::Console {
+        console::Console::new(self, self.con_in, self.std_err)
+    }
+
+    /// The handle of the console input device, which may support protocols beyond
+    /// SimpleTextInput, such as SimpleTextInputEx.
+    pub fn console_in_handle(&self) -> base::Handle {
+        self.con_in_handle
+    }
+
     pub fn boot_services(&self) -> &'static bootservices::BootServices {
         return self.boot_services;
     }
@@ -36,20 +57,115 @@ impl SystemTable {
         return self.runtime_services;
     }
 
-    pub fn vendor(&self) -> *const u16 {
-        return self.vendor
+    /// The header of the system table, with its signature, revision and CRC32.
+    pub fn header(&self) -> &TableHeader {
+        &self.header
+    }
+
+    /// The revision of the UEFI specification the firmware conforms to.
+    pub fn revision(&self) -> Revision {
+        self.header.revision()
+    }
+
+    /// The vendor-specific revision of the firmware.
+    pub fn firmware_revision(&self) -> u32 {
+        self.firmware_revision
+    }
+
+    /// Decode the name of the firmware vendor into `buf`, returning `Status::BufferTooSmall` if
+    /// it doesn't fit, and `Status::InvalidParameter` if it isn't valid UCS-2. A firmware without
+    /// a vendor name gives an empty one.
+    pub fn vendor<'a>(&self, buf: &'a mut [u8]) -> Result<&'a str, Status> {
+        if self.vendor.is_null() {
+            return Ok("");
+        }
+
+        unsafe { CStr16::from_ptr(self.vendor) }?.to_str(buf)
+    }
+
+    /// The entries of the system configuration table.
+    pub fn configuration_tables(&self) -> &'static [table::ConfigurationTable] {
+        if self.configuration_table.is_null() {
+            return &[];
+        }
+
+        unsafe { slice::from_raw_parts(self.configuration_table, self.configuration_table_entries) }
+    }
+
+    /// Find the vendor table identified by `guid` in the system configuration table.
+    pub fn find_configuration_table(&self, guid: &Guid) -> Option<*const CVoid> {
+        self.configuration_tables()
+            .iter()
+            .find(|table| table.vendor_guid() == guid)
+            .map(|table| table.vendor_table())
+    }
+}
+
+#[cfg(feature = "mock")]
+impl SystemTable {
+    /// The mock firmware's system table. Its CRC32 is filled in by `update_crc32`, once it is at
+    /// its final address.
+    pub(crate) fn mock(vendor: *const u16,
+                       con_in: (base::Handle, &'static console::SimpleTextInputProtocol),
+                       con_out: (base::Handle, &'static console::SimpleTextOutputProtocol),
+                       std_err: (base::Handle, &'static console::SimpleTextOutputProtocol),
+                       runtime_services: &'static runtimeservices::RuntimeServices,
+                       boot_services: &'static bootservices::BootServices)
+                       -> SystemTable {
+        SystemTable {
+            header: TableHeader::new(EFI_SYSTEM_TABLE_SIGNATURE, mock::REVISION, mem::size_of::<SystemTable>()),
+            vendor,
+            firmware_revision: mock::FIRMWARE_REVISION,
+            con_in_handle: con_in.0,
+            con_in: con_in.1,
+            con_out_handle: con_out.0,
+            con_out: con_out.1,
+            std_err_handle: std_err.0,
+            std_err: std_err.1,
+            runtime_services,
+            boot_services,
+            configuration_table_entries: 0,
+            configuration_table: ptr::null(),
+        }
+    }
+
+    /// Point the system configuration table at `tables`, which must outlive the system table.
+    pub(crate) unsafe fn set_configuration_tables(&mut self, tables: &[table::ConfigurationTable]) {
+        self.configuration_table_entries = tables.len();
+        self.configuration_table = tables.as_ptr();
+        self.update_crc32();
+    }
+
+    /// Store the CRC32 of the table, after zeroing the padding it covers.
+    pub(crate) unsafe fn update_crc32(&mut self) {
+        let padding = (ptr::addr_of_mut!(self.firmware_revision) as *mut u8).add(mem::size_of::<u32>());
+        let con_in_handle = ptr::addr_of_mut!(self.con_in_handle) as *mut u8;
+        ptr::write_bytes(padding, 0, con_in_handle as usize - padding as usize);
+
+        self.header.update_crc32();
     }
 }
 
-static mut SYSTEM_TABLE : *const SystemTable = 0 as *const SystemTable;
+pub(crate) static mut SYSTEM_TABLE : *const SystemTable = 0 as *const SystemTable;
+
+/// Set System Table handle, after checking the signatures and CRC32s of the system, boot services
+/// and runtime services tables. Fails with `Status::CrcError` if a table is corrupt, and
+/// `Status::InvalidParameter` if it isn't a table at all.
+pub fn set_system_table(table: *const SystemTable) -> Result<&'static SystemTable, Status> {
+    if table.is_null() {
+        return Err(Status::InvalidParameter);
+    }
 
-/// Set System Table handle.
-pub fn set_system_table(table: *const SystemTable) -> &'static SystemTable {
     unsafe {
+        let st = &*table;
+        st.header.validate(EFI_SYSTEM_TABLE_SIGNATURE)?;
+        st.boot_services.header().validate(EFI_BOOT_SERVICES_SIGNATURE)?;
+        st.runtime_services.header().validate(EFI_RUNTIME_SERVICES_SIGNATURE)?;
+
         SYSTEM_TABLE = table;
     }
 
-    get_system_table()
+    Ok(get_system_table())
 }
 
 /// Retreive System Table handle.
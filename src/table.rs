@@ -1,6 +1,52 @@
-use void::NotYetDef;
+use core::{fmt, mem, slice};
+
+use base::Status;
+use void::CVoid;
 use guid::Guid;
 
+/// Signature of the EFI system table, "IBI SYST".
+pub const EFI_SYSTEM_TABLE_SIGNATURE: u64 = 0x5453_5953_2049_4249;
+
+/// Signature of the boot services table, "BOOTSERV".
+pub const EFI_BOOT_SERVICES_SIGNATURE: u64 = 0x5652_4553_544F_4F42;
+
+/// Signature of the runtime services table, "RUNTSERV".
+pub const EFI_RUNTIME_SERVICES_SIGNATURE: u64 = 0x5652_4553_544E_5552;
+
+/// A UEFI specification revision. The low 16 bits hold the minor version times ten plus the
+/// patch, so 2.3.1 is `(2 << 16) | 31` and 2.7 is `(2 << 16) | 70`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Revision(pub u32);
+
+impl Revision {
+    pub const fn new(major: u16, minor: u16, patch: u16) -> Revision {
+        Revision(((major as u32) << 16) | (minor as u32 * 10 + patch as u32))
+    }
+
+    pub fn major(&self) -> u16 {
+        (self.0 >> 16) as u16
+    }
+
+    pub fn minor(&self) -> u16 {
+        (self.0 & 0xFFFF) as u16 / 10
+    }
+
+    pub fn patch(&self) -> u16 {
+        (self.0 & 0xFFFF) as u16 % 10
+    }
+}
+
+impl fmt::Display for Revision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.major(), self.minor())?;
+        if self.patch() != 0 {
+            write!(f, ".{}", self.patch())?;
+        }
+
+        Ok(())
+    }
+}
+
 #[repr(C)]
 pub struct TableHeader {
     signature: u64,
@@ -10,9 +56,107 @@ pub struct TableHeader {
     reserved: u32,
 }
 
+impl TableHeader {
+    /// The signature identifying the table, such as `EFI_SYSTEM_TABLE_SIGNATURE`.
+    pub fn signature(&self) -> u64 {
+        self.signature
+    }
+
+    /// The revision of the specification the table conforms to.
+    pub fn revision(&self) -> Revision {
+        Revision(self.revision)
+    }
+
+    /// The size of the whole table, including this header.
+    pub fn header_size(&self) -> u32 {
+        self.header_size
+    }
+
+    /// The CRC32 of the `header_size` bytes of the table, computed with this field set to zero.
+    pub fn crc32(&self) -> u32 {
+        self.crc32
+    }
+
+    /// Compute the CRC32 of the `header_size` bytes of the table, with the `crc32` field taken as
+    /// zero.
+    pub(crate) unsafe fn compute_crc32(&self) -> u32 {
+        let bytes = slice::from_raw_parts(self as *const TableHeader as *const u8, self.header_size as usize);
+
+        let mut crc = crc32_update(!0, &bytes[..16]);
+        crc = crc32_update(crc, &[0; 4]);
+        crc = crc32_update(crc, &bytes[20..]);
+        !crc
+    }
+
+    /// Check that the table has the expected signature, and that its CRC32 is correct.
+    pub(crate) unsafe fn validate(&self, signature: u64) -> Result<(), Status> {
+        if self.signature != signature || (self.header_size as usize) < mem::size_of::<TableHeader>() {
+            return Err(Status::InvalidParameter);
+        }
+        if self.compute_crc32() != self.crc32 {
+            return Err(Status::CrcError);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "mock")]
+impl TableHeader {
+    pub(crate) fn new(signature: u64, revision: Revision, header_size: usize) -> TableHeader {
+        TableHeader {
+            signature,
+            revision: revision.0,
+            header_size: header_size as u32,
+            crc32: 0,
+            reserved: 0,
+        }
+    }
+
+    /// Store the CRC32 of the table, which must be `header_size` bytes long.
+    pub(crate) unsafe fn update_crc32(&mut self) {
+        self.crc32 = self.compute_crc32();
+    }
+}
+
+/// The CRC32 of `data`, as used in table headers. This is the same CRC32 as zlib's and
+/// `BootServices::calculate_crc32`.
+pub fn crc32(data: &[u8]) -> u32 {
+    !crc32_update(!0, data)
+}
+
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+
+    crc
+}
+
+/// An entry in the system configuration table, which points to a vendor table such as ACPI,
+/// SMBIOS or the ESRT.
 #[repr(C)]
-pub struct ConfigurationTableInternal {
+pub struct ConfigurationTable {
     vendor_guid: Guid,
-    vendor_table: *const NotYetDef,
+    vendor_table: *const CVoid,
 }
 
+impl ConfigurationTable {
+    pub fn vendor_guid(&self) -> &Guid {
+        &self.vendor_guid
+    }
+
+    pub fn vendor_table(&self) -> *const CVoid {
+        self.vendor_table
+    }
+}
+
+#[cfg(feature = "mock")]
+impl ConfigurationTable {
+    pub(crate) fn new(vendor_guid: Guid, vendor_table: *const CVoid) -> ConfigurationTable {
+        ConfigurationTable { vendor_guid, vendor_table }
+    }
+}
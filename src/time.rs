@@ -0,0 +1,362 @@
+// Copyright 2017 CoreOS, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conversions between `Time` and Unix time, RFC 3339 formatting and parsing, and comparison and
+//! arithmetic on `Time`.
+//!
+//! UEFI 2.7 errata settled the meaning of `Time::timezone` as `local time = UTC + timezone`, the
+//! same sign as an RFC 3339 offset. When `TIME_IN_DAYLIGHT` is set, the local time is a further
+//! hour ahead of UTC. A time with `UNSPECIFIED_TIMEZONE` is treated as UTC when converting, which
+//! is how most operating systems keep the real time clock.
+
+use core::cmp::Ordering;
+use core::fmt;
+use core::ops::{Add, Sub};
+use core::str::FromStr;
+use core::time::Duration;
+
+use base::{Status, Time, TIME_IN_DAYLIGHT, UNSPECIFIED_TIMEZONE};
+
+const SECONDS_PER_DAY: i64 = 86400;
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: u8, day: u8) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+/// The date of a number of days since 1970-01-01, as (year, month, day).
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let days = days + 719468;
+    let era = if days >= 0 { days } else { days - 146096 } / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+impl Time {
+    /// The offset of this time from UTC in minutes, including daylight savings time, or `None`
+    /// if the timezone is unspecified.
+    pub fn utc_offset(&self) -> Option<i32> {
+        self.timezone().map(|timezone| {
+            let daylight = if self.daylight & TIME_IN_DAYLIGHT != 0 { 60 } else { 0 };
+            timezone as i32 + daylight
+        })
+    }
+
+    /// Create a time from seconds and nanoseconds since the Unix epoch. With `timezone`, the
+    /// fields hold the local time at that offset from UTC (in minutes); with `None` they hold UTC
+    /// and the timezone is left unspecified.
+    pub fn from_unix(seconds: i64, nanosecond: u32, timezone: Option<i16>) -> Result<Time, Status> {
+        if nanosecond > 999_999_999 {
+            return Err(Status::InvalidParameter);
+        }
+        if let Some(timezone) = timezone {
+            if !(-1440..=1440).contains(&timezone) {
+                return Err(Status::InvalidParameter);
+            }
+        }
+
+        let local = match seconds.checked_add(timezone.unwrap_or(0) as i64 * 60) {
+            Some(local) => local,
+            None => return Err(Status::InvalidParameter),
+        };
+        let days = local.div_euclid(SECONDS_PER_DAY);
+        let seconds_of_day = local.rem_euclid(SECONDS_PER_DAY);
+        let (year, month, day) = civil_from_days(days);
+        if !(1900..=9999).contains(&year) {
+            return Err(Status::InvalidParameter);
+        }
+
+        Time::new(year as u16, month, day,
+                  (seconds_of_day / 3600) as u8, (seconds_of_day / 60 % 60) as u8, (seconds_of_day % 60) as u8)?
+            .with_nanosecond(nanosecond)?
+            .with_timezone(timezone, 0)
+    }
+
+    /// Seconds and nanoseconds since the Unix epoch.
+    pub fn to_unix(&self) -> (i64, u32) {
+        let days = days_from_civil(self.year as i64, self.month, self.day);
+        let local = days * SECONDS_PER_DAY + self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64;
+
+        (local - self.utc_offset().unwrap_or(0) as i64 * 60, self.nanosecond)
+    }
+
+    /// Add `duration`, keeping this time's timezone. Returns `None` if the result would be
+    /// outside the range of `Time`.
+    pub fn checked_add(&self, duration: Duration) -> Option<Time> {
+        let (seconds, nanosecond) = self.to_unix();
+        let mut nanosecond = nanosecond + duration.subsec_nanos();
+        let mut carry = 0;
+        if nanosecond > 999_999_999 {
+            nanosecond -= 1_000_000_000;
+            carry = 1;
+        }
+
+        if duration.as_secs() > i64::MAX as u64 {
+            return None;
+        }
+        seconds.checked_add(duration.as_secs() as i64)
+            .and_then(|seconds| seconds.checked_add(carry))
+            .and_then(|seconds| self.with_unix_time(seconds, nanosecond))
+    }
+
+    /// Subtract `duration`, keeping this time's timezone. Returns `None` if the result would be
+    /// outside the range of `Time`.
+    pub fn checked_sub(&self, duration: Duration) -> Option<Time> {
+        let (seconds, nanosecond) = self.to_unix();
+        let mut nanosecond = nanosecond as i64 - duration.subsec_nanos() as i64;
+        let mut borrow = 0;
+        if nanosecond < 0 {
+            nanosecond += 1_000_000_000;
+            borrow = 1;
+        }
+
+        if duration.as_secs() > i64::MAX as u64 {
+            return None;
+        }
+        seconds.checked_sub(duration.as_secs() as i64)
+            .and_then(|seconds| seconds.checked_sub(borrow))
+            .and_then(|seconds| self.with_unix_time(seconds, nanosecond as u32))
+    }
+
+    /// The time elapsed from `earlier` to this time, or `None` if `earlier` is later.
+    pub fn duration_since(&self, earlier: &Time) -> Option<Duration> {
+        let (seconds, nanosecond) = self.to_unix();
+        let (earlier_seconds, earlier_nanosecond) = earlier.to_unix();
+
+        let mut seconds = seconds - earlier_seconds;
+        let mut nanosecond = nanosecond as i64 - earlier_nanosecond as i64;
+        if nanosecond < 0 {
+            nanosecond += 1_000_000_000;
+            seconds -= 1;
+        }
+        if seconds < 0 {
+            return None;
+        }
+
+        Some(Duration::new(seconds as u64, nanosecond as u32))
+    }
+
+    fn with_unix_time(&self, seconds: i64, nanosecond: u32) -> Option<Time> {
+        // Keep the same wall clock offset, including any daylight savings adjustment.
+        let offset = self.utc_offset().unwrap_or(0) as i64 * 60;
+        let timezone_offset = if self.timezone == UNSPECIFIED_TIMEZONE { 0 } else { self.timezone as i64 * 60 };
+        let seconds = seconds + (offset - timezone_offset);
+
+        Time::from_unix(seconds, nanosecond, self.timezone())
+            .and_then(|time| time.with_timezone(self.timezone(), self.daylight))
+            .ok()
+    }
+
+    /// Format this time as an RFC 3339 timestamp, such as `2017-11-02T13:45:30.5+01:00`. A time
+    /// with an unspecified timezone gets the "unknown local offset" `-00:00`.
+    pub fn rfc3339(&self) -> Rfc3339 {
+        Rfc3339(*self)
+    }
+
+    /// Parse an RFC 3339 timestamp. An offset of `-00:00` gives an unspecified timezone. Leap
+    /// seconds can't be represented and are rejected.
+    pub fn parse_rfc3339(s: &str) -> Result<Time, Status> {
+        let mut parser = Parser { bytes: s.as_bytes() };
+
+        let year = parser.number(4)?;
+        parser.expect(b"-")?;
+        let month = parser.number(2)?;
+        parser.expect(b"-")?;
+        let day = parser.number(2)?;
+        parser.expect(b"Tt ")?;
+        let hour = parser.number(2)?;
+        parser.expect(b":")?;
+        let minute = parser.number(2)?;
+        parser.expect(b":")?;
+        let second = parser.number(2)?;
+
+        let mut nanosecond = 0;
+        if parser.peek() == Some(b'.') {
+            parser.expect(b".")?;
+            let mut digits = 0;
+            while let Some(c @ b'0'..=b'9') = parser.peek() {
+                // Digits beyond nanoseconds are dropped.
+                if digits < 9 {
+                    nanosecond = nanosecond * 10 + (c - b'0') as u32;
+                }
+                digits += 1;
+                parser.bytes = &parser.bytes[1..];
+            }
+            if digits == 0 {
+                return Err(Status::InvalidParameter);
+            }
+            for _ in digits..9 {
+                nanosecond *= 10;
+            }
+        }
+
+        let timezone = match parser.peek() {
+            Some(b'Z') | Some(b'z') => {
+                parser.bytes = &parser.bytes[1..];
+                Some(0)
+            },
+            Some(sign @ b'+') | Some(sign @ b'-') => {
+                parser.bytes = &parser.bytes[1..];
+                let hours = parser.number(2)?;
+                parser.expect(b":")?;
+                let minutes = parser.number(2)?;
+                if minutes > 59 {
+                    return Err(Status::InvalidParameter);
+                }
+
+                let offset = (hours * 60 + minutes) as i16;
+                match sign {
+                    b'-' if offset == 0 => None,
+                    b'-' => Some(-offset),
+                    _ => Some(offset),
+                }
+            },
+            _ => return Err(Status::InvalidParameter),
+        };
+
+        if !parser.bytes.is_empty() || month > 12 || day > 31 || hour > 23 || minute > 59 || second > 59 {
+            return Err(Status::InvalidParameter);
+        }
+
+        Time::new(year as u16, month as u8, day as u8, hour as u8, minute as u8, second as u8)?
+            .with_nanosecond(nanosecond)?
+            .with_timezone(timezone, 0)
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.first().cloned()
+    }
+
+    fn expect(&mut self, any_of: &[u8]) -> Result<(), Status> {
+        match self.peek() {
+            Some(c) if any_of.contains(&c) => {
+                self.bytes = &self.bytes[1..];
+                Ok(())
+            },
+            _ => Err(Status::InvalidParameter),
+        }
+    }
+
+    fn number(&mut self, digits: usize) -> Result<u32, Status> {
+        if self.bytes.len() < digits {
+            return Err(Status::InvalidParameter);
+        }
+
+        let mut value = 0;
+        for &c in &self.bytes[..digits] {
+            if !c.is_ascii_digit() {
+                return Err(Status::InvalidParameter);
+            }
+            value = value * 10 + (c - b'0') as u32;
+        }
+        self.bytes = &self.bytes[digits..];
+
+        Ok(value)
+    }
+}
+
+/// Formats a `Time` as RFC 3339; see `Time::rfc3339`.
+pub struct Rfc3339(Time);
+
+impl fmt::Display for Rfc3339 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let t = &self.0;
+        write!(f, "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}", t.year, t.month, t.day, t.hour, t.minute, t.second)?;
+
+        if t.nanosecond != 0 {
+            let mut nanosecond = t.nanosecond;
+            let mut digits = 9;
+            while nanosecond % 10 == 0 {
+                nanosecond /= 10;
+                digits -= 1;
+            }
+            write!(f, ".{:01$}", nanosecond, digits)?;
+        }
+
+        match t.utc_offset() {
+            Some(0) => write!(f, "Z"),
+            Some(offset) => {
+                let sign = if offset < 0 { '-' } else { '+' };
+                let offset = offset.abs();
+                write!(f, "{}{:02}:{:02}", sign, offset / 60, offset % 60)
+            },
+            None => write!(f, "-00:00"),
+        }
+    }
+}
+
+impl FromStr for Time {
+    type Err = Status;
+
+    fn from_str(s: &str) -> Result<Time, Status> {
+        Time::parse_rfc3339(s)
+    }
+}
+
+/// Times are compared as instants, so the same moment in two timezones is equal.
+impl PartialEq for Time {
+    fn eq(&self, other: &Time) -> bool {
+        self.to_unix() == other.to_unix()
+    }
+}
+
+impl Eq for Time {}
+
+impl PartialOrd for Time {
+    fn partial_cmp(&self, other: &Time) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Time {
+    fn cmp(&self, other: &Time) -> Ordering {
+        self.to_unix().cmp(&other.to_unix())
+    }
+}
+
+impl Add<Duration> for Time {
+    type Output = Time;
+
+    fn add(self, duration: Duration) -> Time {
+        self.checked_add(duration).expect("overflow when adding duration to time")
+    }
+}
+
+impl Sub<Duration> for Time {
+    type Output = Time;
+
+    fn sub(self, duration: Duration) -> Time {
+        self.checked_sub(duration).expect("overflow when subtracting duration from time")
+    }
+}
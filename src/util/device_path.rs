@@ -19,29 +19,25 @@ use void::CVoid;
 use util::*;
 
 pub fn create_file_device_node(filename: &str) -> Result<&DevicePathProtocol, Status> {
-    str_to_utf16_ptr(filename).and_then(|filename_ptr| {
-        let filename_len = utf16_strlen(filename_ptr);
-        let node_size_bytes = 4 + (filename_len + 1) * 2;
+    let filename: CString16 = filename.parse()?;
+    let filename = filename.as_slice_with_nul();
+    let node_size_bytes = 4 + filename.len() * 2;
 
-        ::get_system_table()
-            .boot_services()
-            .locate_protocol::<DevicePathUtilitiesProtocol>(0 as *const CVoid)
-            .and_then(|utilities| {
-                utilities.create_device_node(DevicePathTypes::Media, MediaSubTypes::FilePath, node_size_bytes as u16)
-                    .map(|node_ptr| {
-                        let node_filename_ptr: *mut u16 = unsafe { (node_ptr as *const u8).offset(4) as *mut u16 };
+    ::get_system_table()
+        .boot_services()
+        .locate_protocol::<DevicePathUtilitiesProtocol>(0 as *const CVoid)
+        .and_then(|utilities| {
+            utilities.create_device_node(DevicePathTypes::Media, MediaSubTypes::FilePath, node_size_bytes as u16)
+                .map(|node_ptr| {
+                    let node_filename_ptr: *mut u16 = unsafe { (node_ptr as *const u8).offset(4) as *mut u16 };
 
-                        for i in 0..filename_len as isize{
-                            unsafe {
-                                *node_filename_ptr.offset(i) = *filename_ptr.offset(i);
-                            }
-                        }
-                        unsafe { *node_filename_ptr.offset(filename_len as isize) = 0 };
+                    for (i, &c) in filename.iter().enumerate() {
+                        unsafe { *node_filename_ptr.add(i) = c };
+                    }
 
-                        unsafe { &*node_ptr }
-                    })
-            })
-    })
+                    unsafe { &*node_ptr }
+                })
+        })
 }
 
 /// Get the "parent" of a given device path - i.e., take all but the last DevicePathProtocol
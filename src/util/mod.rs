@@ -12,13 +12,44 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use core::fmt::{self, Write};
+use core::{slice, str};
+
+use base::Status;
+
 mod device_path;
+mod ucs2;
 pub use self::device_path::*;
+pub use self::ucs2::*;
 
-use core::slice;
-use core::str;
+/// Format `args` into `buf`, keeping as many whole characters as fit, and return the result.
+pub fn format_truncated<'a>(buf: &'a mut [u8], args: fmt::Arguments) -> &'a str {
+    let mut writer = TruncatingWriter { buf, len: 0 };
+    let _ = writer.write_fmt(args);
+    let len = writer.len;
 
-use base::Status;
+    // only whole characters were copied
+    unsafe { str::from_utf8_unchecked(&writer.buf[..len]) }
+}
+
+struct TruncatingWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Write for TruncatingWriter<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            let n = c.len_utf8();
+            if self.len + n > self.buf.len() {
+                return Err(fmt::Error);
+            }
+            c.encode_utf8(&mut self.buf[self.len..]);
+            self.len += n;
+        }
+        Ok(())
+    }
+}
 
 /// Take a null-terminated UTF-16 string (such as one returned by EFI functions) and determine its
 /// length.
@@ -34,56 +65,54 @@ pub fn utf16_strlen(c: *const u16) -> usize {
     len
 }
 
-/// Convert a raw pointer to a UTF-16 string to a rust &str.
-/// Note: This function expects to receive a fully ASCII-compatible string. If it does not, it will
-/// fail.
-pub fn utf16_ptr_to_str(chars: *const u16) -> Result<&'static str, Status> { 
-    let strlen = utf16_strlen(chars);
+/// Convert a raw pointer to a UTF-16 string to a rust &str, in a pool allocation which is never
+/// freed.
+#[deprecated(since = "0.1.3", note = "use `CStr16::from_ptr` and `CStr16::to_str`")]
+pub fn utf16_ptr_to_str(chars: *const u16) -> Result<&'static str, Status> {
+    let s = unsafe { CStr16::from_ptr(chars) }?;
+    let len = s.chars().map(char::len_utf8).sum();
+    let buf: *mut u8 = ::get_system_table().boot_services().allocate_pool(len)?;
+
+    s.to_str(unsafe { slice::from_raw_parts_mut(buf, len) })
+}
 
-    let raw_u8_ptr: Result<*mut u8, Status> = ::get_system_table().boot_services().allocate_pool(strlen);
-    if let Err(status) = raw_u8_ptr {
-        return Err(status);
+/// Convert a rust &str to a pointer to a UTF-16 string, which the caller frees with `free_pool`.
+#[deprecated(since = "0.1.3", note = "use `CString16`, by parsing the string")]
+pub fn str_to_utf16_ptr(chars: &str) -> Result<*const u16, Status> {
+    chars.parse::<CString16>().map(|s| s.into_raw() as *const u16)
+}
+
+/// Iterator over the UTF-16 code units stored little-endian in a byte slice, as found in UEFI
+/// variables and other firmware blobs which make no alignment guarantees.
+#[derive(Clone, Debug)]
+pub struct Utf16Bytes<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Utf16Bytes<'a> {
+    pub fn new(bytes: &'a [u8]) -> Utf16Bytes<'a> {
+        Utf16Bytes { bytes }
     }
-    let raw_u8_ptr = raw_u8_ptr.unwrap();
-
-    for i in 0..strlen as isize {
-        unsafe {
-            // If the character is not ASCII, fail.
-            if *(chars.offset(i)) >= 128 {
-                ::get_system_table().boot_services().free_pool(raw_u8_ptr);
-                return Err(Status::InvalidParameter);
-            }
+}
 
-            *(raw_u8_ptr.offset(i)) = *(chars.offset(i)) as u8;
+impl<'a> Iterator for Utf16Bytes<'a> {
+    type Item = u16;
+
+    fn next(&mut self) -> Option<u16> {
+        if self.bytes.len() < 2 {
+            return None;
         }
-    }
 
-    let u8_slice = unsafe { slice::from_raw_parts(raw_u8_ptr, strlen) };
-    unsafe {
-        Ok(str::from_utf8_unchecked(u8_slice))
+        let unit = (self.bytes[0] as u16) | ((self.bytes[1] as u16) << 8);
+        self.bytes = &self.bytes[2..];
+        Some(unit)
     }
 }
 
-/// Convert a rust &str to a pointer to a UTF-16 string.
-/// Note: This function expects to receive a fully ASCII-compatible string. If it does not, it will
-/// fail.
-pub fn str_to_utf16_ptr(chars: &str) -> Result<*const u16, Status> {
-    ::get_system_table()
-        .boot_services()
-        .allocate_pool(chars.len() + 1)
-        .and_then(|u16_ptr| {
-            for (i, c) in chars.chars().enumerate() {
-                if c.len_utf8() > 1 {
-                    ::get_system_table().boot_services().free_pool(u16_ptr);
-                    return Err(Status::Unsupported);
-                }
-
-                unsafe {
-                    *((u16_ptr as *mut u16).offset(i as isize)) = c as u16;
-                }
-            }
-            unsafe { *((u16_ptr as *mut u16).offset(chars.len() as isize)) = 0 };
-
-            Ok(u16_ptr as *const u16)
-        })
+/// Find the length in bytes, excluding the terminator, of a null-terminated UTF-16LE string at
+/// the start of `bytes`. Returns `None` if there is no terminator.
+pub fn utf16_bytes_strlen(bytes: &[u8]) -> Option<usize> {
+    bytes.chunks(2)
+        .position(|unit| unit.len() == 2 && unit[0] == 0 && unit[1] == 0)
+        .map(|units| units * 2)
 }
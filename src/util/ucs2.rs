@@ -0,0 +1,285 @@
+// Copyright 2017 CoreOS, Inc.
+// 
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+// 
+//     http://www.apache.org/licenses/LICENSE-2.0
+// 
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::{char, fmt, mem, ops, slice, str};
+use core::fmt::Write;
+use core::str::FromStr;
+
+use base::Status;
+use bootservices::boot_services_exited;
+use util::utf16_strlen;
+
+/// Check that `units` holds a single null, at the end, and no surrogates.
+fn validate(units: &[u16]) -> Result<(), Status> {
+    match units.iter().position(|&c| c == 0) {
+        Some(len) if len == units.len() - 1 => (),
+        _ => return Err(Status::InvalidParameter),
+    }
+    if units.iter().any(|c| (0xD800..=0xDFFF).contains(c)) {
+        return Err(Status::InvalidParameter);
+    }
+
+    Ok(())
+}
+
+/// The number of UCS-2 characters needed to encode `s`. Characters outside the Basic
+/// Multilingual Plane can't be represented and cause `Status::Unsupported`; nulls cause
+/// `Status::InvalidParameter`.
+fn encoded_len(s: &str) -> Result<usize, Status> {
+    for c in s.chars() {
+        if c.len_utf16() > 1 {
+            return Err(Status::Unsupported);
+        }
+        if c == '\0' {
+            return Err(Status::InvalidParameter);
+        }
+    }
+
+    Ok(s.chars().count())
+}
+
+/// A borrowed null-terminated UCS-2 string, as taken and returned by the firmware. Every
+/// character is in the Basic Multilingual Plane: UCS-2 has no surrogate pairs, so lone surrogates
+/// are rejected.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct CStr16([u16]);
+
+impl CStr16 {
+    /// Borrow `units`, which must end with its only null.
+    pub fn from_u16_with_nul(units: &[u16]) -> Result<&CStr16, Status> {
+        validate(units)?;
+
+        Ok(unsafe { CStr16::from_u16_with_nul_unchecked(units) })
+    }
+
+    /// Borrow `units` without checking it.
+    ///
+    /// # Safety
+    ///
+    /// `units` must end with its only null, and contain no surrogates.
+    pub unsafe fn from_u16_with_nul_unchecked(units: &[u16]) -> &CStr16 {
+        &*(units as *const [u16] as *const CStr16)
+    }
+
+    /// Borrow the string at `ptr`, up to and including its null terminator.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be null or point to a null-terminated string which lives for `'a`.
+    pub unsafe fn from_ptr<'a>(ptr: *const u16) -> Result<&'a CStr16, Status> {
+        if ptr.is_null() {
+            return Err(Status::InvalidParameter);
+        }
+
+        CStr16::from_u16_with_nul(slice::from_raw_parts(ptr, utf16_strlen(ptr) + 1))
+    }
+
+    /// Encode `s` into `buf`, returning `Status::BufferTooSmall` if it doesn't fit.
+    pub fn from_str_with_buf<'a>(s: &str, buf: &'a mut [u16]) -> Result<&'a CStr16, Status> {
+        let len = encoded_len(s)?;
+        if len >= buf.len() {
+            return Err(Status::BufferTooSmall);
+        }
+
+        for (unit, c) in buf.iter_mut().zip(s.chars()) {
+            *unit = c as u16;
+        }
+        buf[len] = 0;
+
+        Ok(unsafe { CStr16::from_u16_with_nul_unchecked(&buf[..len + 1]) })
+    }
+
+    pub fn as_ptr(&self) -> *const u16 {
+        self.0.as_ptr()
+    }
+
+    /// The characters of the string, without the terminator.
+    pub fn as_slice(&self) -> &[u16] {
+        &self.0[..self.0.len() - 1]
+    }
+
+    pub fn as_slice_with_nul(&self) -> &[u16] {
+        &self.0
+    }
+
+    /// The length of the string in characters, excluding the terminator.
+    pub fn len(&self) -> usize {
+        self.0.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn chars<'a>(&'a self) -> impl Iterator<Item = char> + 'a {
+        self.as_slice().iter().map(|&c| char::from_u32(c as u32).unwrap_or(char::REPLACEMENT_CHARACTER))
+    }
+
+    /// Decode the string into `buf` as UTF-8, returning `Status::BufferTooSmall` if it doesn't
+    /// fit.
+    pub fn to_str<'a>(&self, buf: &'a mut [u8]) -> Result<&'a str, Status> {
+        let mut len = 0;
+        for c in self.chars() {
+            if len + c.len_utf8() > buf.len() {
+                return Err(Status::BufferTooSmall);
+            }
+
+            len += c.encode_utf8(&mut buf[len..]).len();
+        }
+
+        Ok(unsafe { str::from_utf8_unchecked(&buf[..len]) })
+    }
+}
+
+impl PartialEq<str> for CStr16 {
+    fn eq(&self, other: &str) -> bool {
+        self.chars().eq(other.chars())
+    }
+}
+
+impl<'a> PartialEq<&'a str> for CStr16 {
+    fn eq(&self, other: &&'a str) -> bool {
+        *self == **other
+    }
+}
+
+impl fmt::Display for CStr16 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in self.chars() {
+            f.write_char(c)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for CStr16 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_char('"')?;
+        for c in self.chars() {
+            for e in c.escape_debug() {
+                f.write_char(e)?;
+            }
+        }
+        f.write_char('"')
+    }
+}
+
+/// An owned null-terminated UCS-2 string in pool memory, which is freed when it's dropped.
+/// Convert from a `&str` with `parse`.
+pub struct CString16 {
+    units: *mut u16,
+    // including the terminator
+    len: usize,
+}
+
+impl CString16 {
+    /// Take ownership of a string the firmware allocated from pool, such as one returned by the
+    /// device path to text protocol. The string is freed straight away if it isn't valid.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be null or a null-terminated string allocated with `allocate_pool`, which
+    /// isn't freed by anything else.
+    pub unsafe fn from_pool(ptr: *mut u16) -> Result<CString16, Status> {
+        if ptr.is_null() {
+            return Err(Status::InvalidParameter);
+        }
+
+        let string = CString16 { units: ptr, len: utf16_strlen(ptr) + 1 };
+        validate(string.as_slice_with_nul())?;
+
+        Ok(string)
+    }
+
+    /// Give up ownership of the string, returning the pool allocation holding it.
+    pub fn into_raw(self) -> *mut u16 {
+        let units = self.units;
+        mem::forget(self);
+        units
+    }
+}
+
+impl FromStr for CString16 {
+    type Err = Status;
+
+    fn from_str(s: &str) -> Result<CString16, Status> {
+        let len = encoded_len(s)? + 1;
+        let units: *mut u16 = ::get_system_table().boot_services().allocate_pool(len * mem::size_of::<u16>())?;
+
+        let buf = unsafe { slice::from_raw_parts_mut(units, len) };
+        for (unit, c) in buf.iter_mut().zip(s.chars()) {
+            *unit = c as u16;
+        }
+        buf[len - 1] = 0;
+
+        Ok(CString16 { units, len })
+    }
+}
+
+impl ops::Deref for CString16 {
+    type Target = CStr16;
+
+    fn deref(&self) -> &CStr16 {
+        unsafe { CStr16::from_u16_with_nul_unchecked(slice::from_raw_parts(self.units, self.len)) }
+    }
+}
+
+impl AsRef<CStr16> for CString16 {
+    fn as_ref(&self) -> &CStr16 {
+        self
+    }
+}
+
+impl Drop for CString16 {
+    fn drop(&mut self) {
+        // pool memory is gone, or owned by the OS, once boot services have exited
+        if !boot_services_exited() {
+            ::get_system_table().boot_services().free_pool(self.units);
+        }
+    }
+}
+
+impl PartialEq for CString16 {
+    fn eq(&self, other: &CString16) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for CString16 {}
+
+impl PartialEq<str> for CString16 {
+    fn eq(&self, other: &str) -> bool {
+        **self == *other
+    }
+}
+
+impl<'a> PartialEq<&'a str> for CString16 {
+    fn eq(&self, other: &&'a str) -> bool {
+        **self == **other
+    }
+}
+
+impl fmt::Display for CString16 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl fmt::Debug for CString16 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
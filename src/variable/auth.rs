@@ -0,0 +1,141 @@
+// Copyright 2017 CoreOS, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Time-based authenticated variable writes, as used to update `PK`, `KEK`, `db` and `dbx`.
+
+use base::{Status, Time};
+use guid::Guid;
+use runtimeservices::{VariableAttributes, VARIABLE_APPEND_WRITE, VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS};
+use util::CStr16;
+use super::{runtime_services, write_guid, write_u16, write_u32, EFI_CERT_TYPE_PKCS7_GUID};
+
+/// WIN_CERTIFICATE revision used by UEFI.
+const WIN_CERT_REVISION: u16 = 0x0200;
+
+/// WIN_CERTIFICATE type for a WIN_CERTIFICATE_UEFI_GUID.
+const WIN_CERT_TYPE_EFI_GUID: u16 = 0x0EF1;
+
+/// Size of an EFI_TIME.
+const TIME_SIZE: usize = 16;
+
+/// Size of a WIN_CERTIFICATE_UEFI_GUID without its certificate data.
+const WIN_CERTIFICATE_UEFI_GUID_SIZE: usize = 24;
+
+fn write_time(bytes: &mut [u8], time: &Time) {
+    // Only the date and time are covered; the specification requires the nanosecond, timezone
+    // and daylight fields, as well as the padding, to be zero.
+    for b in bytes[..TIME_SIZE].iter_mut() {
+        *b = 0;
+    }
+
+    write_u16(bytes, time.year);
+    bytes[2] = time.month;
+    bytes[3] = time.day;
+    bytes[4] = time.hour;
+    bytes[5] = time.minute;
+    bytes[6] = time.second;
+}
+
+/// A variable payload prefixed with an EFI_VARIABLE_AUTHENTICATION_2 header. The PKCS#7 blob is
+/// a detached signature over `signed_data`, produced by the caller with the appropriate key.
+#[derive(Clone, Copy, Debug)]
+pub struct AuthenticatedVariable<'a> {
+    /// Must be later than the variable's current timestamp, unless appending.
+    pub timestamp: Time,
+    /// DER-encoded PKCS#7 SignedData.
+    pub pkcs7: &'a [u8],
+    /// The new variable contents, e.g. a signature list for `db`.
+    pub data: &'a [u8],
+}
+
+impl<'a> AuthenticatedVariable<'a> {
+    pub fn new(timestamp: Time, pkcs7: &'a [u8], data: &'a [u8]) -> AuthenticatedVariable<'a> {
+        AuthenticatedVariable { timestamp, pkcs7, data }
+    }
+
+    /// The size in bytes of the EFI_VARIABLE_AUTHENTICATION_2 header.
+    pub fn header_len(&self) -> usize {
+        TIME_SIZE + WIN_CERTIFICATE_UEFI_GUID_SIZE + self.pkcs7.len()
+    }
+
+    /// The size in bytes of the header and data together.
+    pub fn encoded_len(&self) -> usize {
+        self.header_len() + self.data.len()
+    }
+
+    /// Serialize the header followed by the data into `buf`, and return the number of bytes used.
+    pub fn encode(&self, buf: &mut [u8]) -> Result<usize, Status> {
+        let len = self.encoded_len();
+        if buf.len() < len {
+            return Err(Status::BufferTooSmall);
+        }
+
+        write_time(buf, &self.timestamp);
+
+        let cert = &mut buf[TIME_SIZE..];
+        write_u32(cert, (WIN_CERTIFICATE_UEFI_GUID_SIZE + self.pkcs7.len()) as u32);
+        write_u16(&mut cert[4..], WIN_CERT_REVISION);
+        write_u16(&mut cert[6..], WIN_CERT_TYPE_EFI_GUID);
+        write_guid(&mut cert[8..], &EFI_CERT_TYPE_PKCS7_GUID);
+        cert[WIN_CERTIFICATE_UEFI_GUID_SIZE..WIN_CERTIFICATE_UEFI_GUID_SIZE + self.pkcs7.len()].copy_from_slice(self.pkcs7);
+
+        buf[self.header_len()..len].copy_from_slice(self.data);
+
+        Ok(len)
+    }
+
+    /// The size in bytes of the data which must be signed to write `name`.
+    pub fn signed_data_len(&self, name: &CStr16) -> usize {
+        name.len() * 2 + 16 + 4 + TIME_SIZE + self.data.len()
+    }
+
+    /// Serialize the data the PKCS#7 signature must cover into `buf`: the variable name without
+    /// its terminator, vendor GUID, attributes, timestamp and new contents. `attributes` must be
+    /// the same as those passed to `write_authenticated_variable`, including the authentication
+    /// and append bits.
+    pub fn signed_data(&self, name: &CStr16, vendor: &Guid, attributes: VariableAttributes, buf: &mut [u8]) -> Result<usize, Status> {
+        let len = self.signed_data_len(name);
+        if buf.len() < len {
+            return Err(Status::BufferTooSmall);
+        }
+
+        let mut offset = 0;
+        for &c in name.as_slice() {
+            write_u16(&mut buf[offset..], c);
+            offset += 2;
+        }
+        write_guid(&mut buf[offset..], vendor);
+        offset += 16;
+        write_u32(&mut buf[offset..], attributes.bits());
+        offset += 4;
+        write_time(&mut buf[offset..], &self.timestamp);
+        offset += TIME_SIZE;
+        buf[offset..len].copy_from_slice(self.data);
+
+        Ok(len)
+    }
+}
+
+/// Submit an authenticated write of `variable` to `name`, owned by `vendor`. `attributes` are
+/// the variable's usual attributes; the time-based authentication bit is added, as is
+/// `APPEND_WRITE` if `append` is set. `scratch` must hold `variable.encoded_len()` bytes.
+pub fn write_authenticated_variable(name: &CStr16, vendor: &Guid, attributes: VariableAttributes, append: bool, variable: &AuthenticatedVariable, scratch: &mut [u8]) -> Result<(), Status> {
+    let mut attributes = attributes | VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS;
+    if append {
+        attributes |= VARIABLE_APPEND_WRITE;
+    }
+
+    let len = variable.encode(scratch)?;
+    runtime_services().set_variable(name, vendor, attributes, &scratch[..len])
+}
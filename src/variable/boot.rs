@@ -0,0 +1,558 @@
+// Copyright 2017 CoreOS, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Boot manager variables: `Boot####`, `Driver####` and `SysPrep####` load options, and the
+//! `BootOrder`, `BootCurrent` and `BootNext` variables which drive the firmware boot manager.
+
+use core::char::{self, DecodeUtf16};
+use core::fmt::{self, Write};
+use core::str;
+
+use base::Status;
+use protocol::{DevicePathProtocol, DevicePathTypes, EndPathSubTypes};
+use runtimeservices::{VariableAttributes, EFI_GLOBAL_VARIABLE_GUID,
+                      VARIABLE_NON_VOLATILE, VARIABLE_BOOTSERVICE_ACCESS, VARIABLE_RUNTIME_ACCESS};
+use util::{CStr16, utf16_bytes_strlen, Utf16Bytes};
+use super::{read_u16, read_u32, write_u32, runtime_services, u16_bytes, u16_bytes_mut};
+
+bitflags! {
+    /// Attributes of an EFI_LOAD_OPTION.
+    pub struct LoadOptionAttributes: u32 {
+        /// The boot manager will try this option.
+        const LOAD_OPTION_ACTIVE = 0x00000001;
+        /// Reconnect all drivers after loading this (driver) option.
+        const LOAD_OPTION_FORCE_RECONNECT = 0x00000002;
+        /// Don't show this option in the boot manager's menu.
+        const LOAD_OPTION_HIDDEN = 0x00000008;
+        /// Mask of the category bits. A category of zero means a normal boot option.
+        const LOAD_OPTION_CATEGORY = 0x00001F00;
+        /// The option is an application, only launched from the boot menu.
+        const LOAD_OPTION_CATEGORY_APP = 0x00000100;
+    }
+}
+
+/// The kinds of load options understood by the boot manager.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LoadOptionType {
+    /// `Boot####`, ordered by `BootOrder`.
+    Boot,
+    /// `Driver####`, ordered by `DriverOrder`.
+    Driver,
+    /// `SysPrep####`, ordered by `SysPrepOrder`.
+    SysPrep,
+}
+
+impl LoadOptionType {
+    fn prefix(&self) -> &'static str {
+        match *self {
+            LoadOptionType::Boot => "Boot",
+            LoadOptionType::Driver => "Driver",
+            LoadOptionType::SysPrep => "SysPrep",
+        }
+    }
+
+    fn order_name(&self) -> &'static str {
+        match *self {
+            LoadOptionType::Boot => "BootOrder",
+            LoadOptionType::Driver => "DriverOrder",
+            LoadOptionType::SysPrep => "SysPrepOrder",
+        }
+    }
+
+    /// Build the null-terminated variable name of load option `number`, such as `Boot0001`.
+    pub fn option_name<'a>(&self, number: u16, buf: &'a mut [u16; 12]) -> &'a CStr16 {
+        const HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+        let prefix = self.prefix();
+        for (i, c) in prefix.bytes().enumerate() {
+            buf[i] = c as u16;
+        }
+
+        let len = prefix.len();
+        for i in 0..4 {
+            buf[len + i] = HEX[((number >> (12 - 4 * i)) & 0xF) as usize] as u16;
+        }
+        buf[len + 4] = 0;
+
+        unsafe { CStr16::from_u16_with_nul_unchecked(&buf[..len + 5]) }
+    }
+
+    /// Parse a variable name such as `Boot0001` (with or without a null terminator) back into
+    /// its option number. Returns `None` if `name` isn't a load option of this type.
+    pub fn parse_option_name(&self, name: &[u16]) -> Option<u16> {
+        let prefix = self.prefix();
+        let name = match name.iter().position(|&c| c == 0) {
+            Some(len) => &name[..len],
+            None => name,
+        };
+
+        if name.len() != prefix.len() + 4 {
+            return None;
+        }
+        if !prefix.bytes().zip(name.iter()).all(|(p, &c)| p as u16 == c) {
+            return None;
+        }
+
+        let mut number: u16 = 0;
+        for &c in &name[prefix.len()..] {
+            // The specification requires upper case hex digits.
+            let digit = match c {
+                0x30..=0x39 => c - 0x30,
+                0x41..=0x46 => c - 0x41 + 10,
+                _ => return None,
+            };
+            number = (number << 4) | digit;
+        }
+
+        Some(number)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Description<'a> {
+    Encoded(&'a [u8]),
+    Str(&'a str),
+}
+
+/// A decoded EFI_LOAD_OPTION, the contents of a `Boot####`, `Driver####` or `SysPrep####`
+/// variable. All fields borrow from the buffer the option was parsed from, or from the arguments
+/// it was built with.
+#[derive(Clone, Copy, Debug)]
+pub struct LoadOption<'a> {
+    pub attributes: LoadOptionAttributes,
+    description: Description<'a>,
+    file_path_list: &'a [u8],
+    /// Data passed to the loaded image as its load options.
+    pub optional_data: &'a [u8],
+}
+
+impl<'a> LoadOption<'a> {
+    /// Build a load option. `file_path_list` is one or more device paths, each terminated with an
+    /// End Entire Path node; the first is the image to load.
+    ///
+    /// Fails with `Status::Unsupported` if the description has characters outside the Basic
+    /// Multilingual Plane, and `Status::InvalidParameter` if it contains a null or the file path
+    /// list is malformed.
+    pub fn new(attributes: LoadOptionAttributes, description: &'a str, file_path_list: &'a [u8], optional_data: &'a [u8]) -> Result<LoadOption<'a>, Status> {
+        if description.chars().any(|c| c.len_utf16() > 1) {
+            return Err(Status::Unsupported);
+        }
+        // the description is stored null-terminated
+        if description.contains('\0') {
+            return Err(Status::InvalidParameter);
+        }
+        if file_path_list.len() > u16::MAX as usize || !valid_file_path_list(file_path_list) {
+            return Err(Status::InvalidParameter);
+        }
+
+        Ok(LoadOption {
+            attributes,
+            description: Description::Str(description),
+            file_path_list,
+            optional_data,
+        })
+    }
+
+    /// Decode a load option from the contents of a load option variable.
+    pub fn parse(data: &'a [u8]) -> Result<LoadOption<'a>, Status> {
+        if data.len() < 6 {
+            return Err(Status::InvalidParameter);
+        }
+
+        let attributes = read_u32(data);
+        let file_path_list_len = read_u16(&data[4..]) as usize;
+
+        let description_len = match utf16_bytes_strlen(&data[6..]) {
+            Some(len) => len,
+            None => return Err(Status::InvalidParameter),
+        };
+        let description = &data[6..6 + description_len];
+
+        let file_path_list_start = 6 + description_len + 2;
+        let file_path_list_end = file_path_list_start + file_path_list_len;
+        if file_path_list_end > data.len() {
+            return Err(Status::InvalidParameter);
+        }
+
+        let file_path_list = &data[file_path_list_start..file_path_list_end];
+        if !valid_file_path_list(file_path_list) {
+            return Err(Status::InvalidParameter);
+        }
+
+        Ok(LoadOption {
+            attributes: LoadOptionAttributes::from_bits_truncate(attributes),
+            description: Description::Encoded(description),
+            file_path_list,
+            optional_data: &data[file_path_list_end..],
+        })
+    }
+
+    /// The human-readable description shown by the boot manager.
+    pub fn description(&self) -> DescriptionChars<'a> {
+        match self.description {
+            Description::Encoded(bytes) => DescriptionChars::Encoded(char::decode_utf16(Utf16Bytes::new(bytes))),
+            Description::Str(s) => DescriptionChars::Str(s.chars()),
+        }
+    }
+
+    /// The raw list of device paths in this option.
+    pub fn file_path_list(&self) -> &'a [u8] {
+        self.file_path_list
+    }
+
+    /// Iterate over the device paths in this option. The first one is the image to load.
+    pub fn device_paths(&self) -> DevicePaths<'a> {
+        DevicePaths { bytes: self.file_path_list }
+    }
+
+    /// The device path of the image this option loads.
+    pub fn device_path(&self) -> &'a DevicePathProtocol {
+        // Both constructors check that the list holds at least one complete device path.
+        self.device_paths().next().unwrap()
+    }
+
+    fn description_units(&self) -> usize {
+        match self.description {
+            Description::Encoded(bytes) => bytes.len() / 2,
+            Description::Str(s) => s.chars().count(),
+        }
+    }
+
+    /// The size in bytes of this option once encoded.
+    pub fn encoded_len(&self) -> usize {
+        6 + (self.description_units() + 1) * 2 + self.file_path_list.len() + self.optional_data.len()
+    }
+
+    /// Encode this option into `buf` in the EFI_LOAD_OPTION format, and return the number of
+    /// bytes used.
+    pub fn encode(&self, buf: &mut [u8]) -> Result<usize, Status> {
+        let len = self.encoded_len();
+        if buf.len() < len {
+            return Err(Status::BufferTooSmall);
+        }
+
+        write_u32(buf, self.attributes.bits());
+        buf[4] = self.file_path_list.len() as u8;
+        buf[5] = (self.file_path_list.len() >> 8) as u8;
+
+        let mut offset = 6;
+        match self.description {
+            Description::Encoded(bytes) => {
+                buf[offset..offset + bytes.len()].copy_from_slice(bytes);
+                offset += bytes.len();
+            },
+            Description::Str(s) => {
+                for c in s.chars() {
+                    buf[offset] = c as u32 as u8;
+                    buf[offset + 1] = (c as u32 >> 8) as u8;
+                    offset += 2;
+                }
+            },
+        }
+        buf[offset] = 0;
+        buf[offset + 1] = 0;
+        offset += 2;
+
+        buf[offset..offset + self.file_path_list.len()].copy_from_slice(self.file_path_list);
+        offset += self.file_path_list.len();
+        buf[offset..offset + self.optional_data.len()].copy_from_slice(self.optional_data);
+
+        Ok(len)
+    }
+}
+
+/// Iterator over the characters of a load option's description. Unpaired surrogates are replaced
+/// with U+FFFD.
+#[derive(Clone)]
+pub enum DescriptionChars<'a> {
+    #[doc(hidden)]
+    Encoded(DecodeUtf16<Utf16Bytes<'a>>),
+    #[doc(hidden)]
+    Str(str::Chars<'a>),
+}
+
+impl<'a> Iterator for DescriptionChars<'a> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        match *self {
+            DescriptionChars::Encoded(ref mut units) => {
+                units.next().map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            },
+            DescriptionChars::Str(ref mut chars) => chars.next(),
+        }
+    }
+}
+
+impl<'a> fmt::Display for DescriptionChars<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in self.clone() {
+            f.write_char(c)?;
+        }
+        Ok(())
+    }
+}
+
+/// Iterator over the device paths in a load option's file path list.
+#[derive(Clone, Debug)]
+pub struct DevicePaths<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Iterator for DevicePaths<'a> {
+    type Item = &'a DevicePathProtocol;
+
+    fn next(&mut self) -> Option<&'a DevicePathProtocol> {
+        let len = match device_path_len(self.bytes) {
+            Some(len) => len,
+            None => return None,
+        };
+
+        // DevicePathProtocol is packed, so any byte pointer is suitably aligned for it.
+        let path = unsafe { &*(self.bytes.as_ptr() as *const DevicePathProtocol) };
+        self.bytes = &self.bytes[len..];
+        Some(path)
+    }
+}
+
+/// Find the length of the device path at the start of `bytes`, including its End Entire Path
+/// node, checking that every node lies within `bytes`.
+fn device_path_len(bytes: &[u8]) -> Option<usize> {
+    let end_type: u8 = DevicePathTypes::End.into();
+    let end_entire: u8 = EndPathSubTypes::EndEntirePath.into();
+    let mut offset = 0;
+
+    while offset + 4 <= bytes.len() {
+        let node_len = (bytes[offset + 2] as usize) | ((bytes[offset + 3] as usize) << 8);
+        if node_len < 4 || offset + node_len > bytes.len() {
+            return None;
+        }
+
+        let is_end = bytes[offset] == end_type && bytes[offset + 1] == end_entire;
+        offset += node_len;
+        if is_end {
+            return Some(offset);
+        }
+    }
+
+    None
+}
+
+fn valid_file_path_list(mut bytes: &[u8]) -> bool {
+    if bytes.is_empty() {
+        return false;
+    }
+
+    while !bytes.is_empty() {
+        match device_path_len(bytes) {
+            Some(len) => bytes = &bytes[len..],
+            None => return false,
+        }
+    }
+
+    true
+}
+
+/// Boot manager variables are non-volatile and visible both before and after ExitBootServices.
+fn boot_variable_attributes() -> VariableAttributes {
+    VARIABLE_NON_VOLATILE | VARIABLE_BOOTSERVICE_ACCESS | VARIABLE_RUNTIME_ACCESS
+}
+
+fn read_u16_variable(name: &str) -> Result<u16, Status> {
+    let mut name_buf = [0u16; 16];
+    let name = CStr16::from_str_with_buf(name, &mut name_buf)?;
+    let mut value = [0u16; 1];
+
+    let (size, _) = runtime_services().get_variable(name, &EFI_GLOBAL_VARIABLE_GUID, u16_bytes_mut(&mut value))?;
+    if size != 2 {
+        return Err(Status::VolumeCorrupted);
+    }
+
+    Ok(value[0])
+}
+
+/// Read load option `number` of type `kind` into `buf`, and decode it.
+pub fn read_load_option(kind: LoadOptionType, number: u16, buf: &mut [u8]) -> Result<LoadOption<'_>, Status> {
+    let mut name_buf = [0u16; 12];
+    let name = kind.option_name(number, &mut name_buf);
+
+    let (size, _) = runtime_services().get_variable(name, &EFI_GLOBAL_VARIABLE_GUID, buf)?;
+    LoadOption::parse(&buf[..size])
+}
+
+/// Encode `option` into `scratch` and store it as load option `number` of type `kind`.
+pub fn write_load_option(kind: LoadOptionType, number: u16, option: &LoadOption, scratch: &mut [u8]) -> Result<(), Status> {
+    let mut name_buf = [0u16; 12];
+    let name = kind.option_name(number, &mut name_buf);
+
+    let len = option.encode(scratch)?;
+    runtime_services().set_variable(name, &EFI_GLOBAL_VARIABLE_GUID, boot_variable_attributes(), &scratch[..len])
+}
+
+/// Delete load option `number` of type `kind`. This does not remove it from the order variable;
+/// see `remove_load_option`.
+pub fn delete_load_option(kind: LoadOptionType, number: u16) -> Result<(), Status> {
+    let mut name_buf = [0u16; 12];
+    let name = kind.option_name(number, &mut name_buf);
+
+    runtime_services().delete_variable(name, &EFI_GLOBAL_VARIABLE_GUID)
+}
+
+/// Find the lowest option number of type `kind` which isn't in use.
+pub fn unused_load_option_number(kind: LoadOptionType) -> Result<u16, Status> {
+    let mut name_buf = [0u16; 12];
+
+    for number in 0..=u16::MAX {
+        let name = kind.option_name(number, &mut name_buf);
+        match runtime_services().get_variable_size(name, &EFI_GLOBAL_VARIABLE_GUID) {
+            Err(Status::NotFound) => return Ok(number),
+            Err(e) => return Err(e),
+            Ok(_) => continue,
+        }
+    }
+
+    Err(Status::OutOfResources)
+}
+
+/// Read the order variable for `kind` (e.g. `BootOrder`) into `buf`, and return the used part.
+/// A missing order variable reads as an empty order.
+pub fn read_order(kind: LoadOptionType, buf: &mut [u16]) -> Result<&mut [u16], Status> {
+    let mut name_buf = [0u16; 16];
+    let name = CStr16::from_str_with_buf(kind.order_name(), &mut name_buf)?;
+
+    let size = match runtime_services().get_variable(name, &EFI_GLOBAL_VARIABLE_GUID, u16_bytes_mut(buf)) {
+        Ok((size, _)) => size,
+        Err(Status::NotFound) => 0,
+        Err(e) => return Err(e),
+    };
+
+    Ok(&mut buf[..size / 2])
+}
+
+/// Replace the order variable for `kind` with `order`.
+pub fn write_order(kind: LoadOptionType, order: &[u16]) -> Result<(), Status> {
+    let mut name_buf = [0u16; 16];
+    let name = CStr16::from_str_with_buf(kind.order_name(), &mut name_buf)?;
+
+    runtime_services().set_variable(name, &EFI_GLOBAL_VARIABLE_GUID, boot_variable_attributes(), u16_bytes(order))
+}
+
+/// Store `option` under the first unused number of type `kind`, and insert it into the order at
+/// `position`, or at the end if `None`. `scratch` must be big enough to hold the encoded option,
+/// and `order_buf` the order with one more entry. Returns the new option's number.
+pub fn add_load_option(kind: LoadOptionType, option: &LoadOption, position: Option<usize>, scratch: &mut [u8], order_buf: &mut [u16]) -> Result<u16, Status> {
+    let number = unused_load_option_number(kind)?;
+    let len = read_order(kind, order_buf)?.len();
+    if len >= order_buf.len() {
+        return Err(Status::BufferTooSmall);
+    }
+
+    write_load_option(kind, number, option, scratch)?;
+
+    let position = position.map_or(len, |p| if p > len { len } else { p });
+    let order = &mut order_buf[..len + 1];
+    for i in (position..len).rev() {
+        order[i + 1] = order[i];
+    }
+    order[position] = number;
+
+    if let Err(e) = write_order(kind, order) {
+        // don't leave an option behind which isn't in the order
+        let _ = delete_load_option(kind, number);
+        return Err(e);
+    }
+
+    Ok(number)
+}
+
+/// Delete load option `number` of type `kind` and remove it from the order. `order_buf` must be
+/// big enough to hold the current order.
+pub fn remove_load_option(kind: LoadOptionType, number: u16, order_buf: &mut [u16]) -> Result<(), Status> {
+    let order = read_order(kind, order_buf)?;
+    let mut len = 0;
+    for i in 0..order.len() {
+        if order[i] != number {
+            order[len] = order[i];
+            len += 1;
+        }
+    }
+
+    if len != order.len() {
+        write_order(kind, &order[..len])?;
+    }
+
+    delete_load_option(kind, number)
+}
+
+/// Move load option `number` of type `kind` to `position` in the order, adding it if it isn't
+/// there yet. `order_buf` must be big enough to hold the order with one more entry.
+pub fn move_load_option(kind: LoadOptionType, number: u16, position: usize, order_buf: &mut [u16]) -> Result<(), Status> {
+    let mut len = read_order(kind, order_buf)?.len();
+    let current = order_buf[..len].iter().position(|&n| n == number);
+
+    match current {
+        Some(current) => {
+            for i in current..len - 1 {
+                order_buf[i] = order_buf[i + 1];
+            }
+            len -= 1;
+        },
+        None => {
+            if len >= order_buf.len() {
+                return Err(Status::BufferTooSmall);
+            }
+        },
+    }
+
+    let position = if position > len { len } else { position };
+    for i in (position..len).rev() {
+        order_buf[i + 1] = order_buf[i];
+    }
+    order_buf[position] = number;
+
+    write_order(kind, &order_buf[..len + 1])
+}
+
+/// The number of the `Boot####` option the firmware booted this time.
+pub fn boot_current() -> Result<u16, Status> {
+    read_u16_variable("BootCurrent")
+}
+
+/// The `Boot####` option the firmware will try first on the next boot only, if any.
+pub fn boot_next() -> Result<Option<u16>, Status> {
+    match read_u16_variable("BootNext") {
+        Ok(number) => Ok(Some(number)),
+        Err(Status::NotFound) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Make the firmware try `Boot####` option `number` first on the next boot only.
+pub fn set_boot_next(number: u16) -> Result<(), Status> {
+    let mut name_buf = [0u16; 16];
+    let name = CStr16::from_str_with_buf("BootNext", &mut name_buf)?;
+
+    runtime_services().set_variable(name, &EFI_GLOBAL_VARIABLE_GUID, boot_variable_attributes(), u16_bytes(&[number]))
+}
+
+/// Remove `BootNext`, if set.
+pub fn clear_boot_next() -> Result<(), Status> {
+    let mut name_buf = [0u16; 16];
+    let name = CStr16::from_str_with_buf("BootNext", &mut name_buf)?;
+
+    match runtime_services().delete_variable(name, &EFI_GLOBAL_VARIABLE_GUID) {
+        Ok(()) | Err(Status::NotFound) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
@@ -0,0 +1,77 @@
+// Copyright 2017 CoreOS, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Typed access to the variables defined by the UEFI specification, built on
+//! `RuntimeServices::get_variable` and `RuntimeServices::set_variable`.
+
+mod auth;
+mod boot;
+mod os_indications;
+mod secure_boot;
+
+pub use self::auth::*;
+pub use self::boot::*;
+pub use self::os_indications::*;
+pub use self::secure_boot::*;
+
+use core::slice;
+
+use guid::Guid;
+use runtimeservices::RuntimeServices;
+
+fn runtime_services() -> &'static RuntimeServices {
+    ::get_system_table().runtime_services()
+}
+
+fn read_u16(bytes: &[u8]) -> u16 {
+    (bytes[0] as u16) | ((bytes[1] as u16) << 8)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    (bytes[0] as u32) | ((bytes[1] as u32) << 8) | ((bytes[2] as u32) << 16) | ((bytes[3] as u32) << 24)
+}
+
+fn write_u16(bytes: &mut [u8], value: u16) {
+    bytes[0] = value as u8;
+    bytes[1] = (value >> 8) as u8;
+}
+
+fn write_u32(bytes: &mut [u8], value: u32) {
+    bytes[0] = value as u8;
+    bytes[1] = (value >> 8) as u8;
+    bytes[2] = (value >> 16) as u8;
+    bytes[3] = (value >> 24) as u8;
+}
+
+/// Read a GUID stored in its mixed-endian wire format.
+fn read_guid(bytes: &[u8]) -> Guid {
+    let mut b = [0u8; 16];
+    b.copy_from_slice(&bytes[..16]);
+    Guid::from_bytes(b)
+}
+
+/// Write a GUID in its mixed-endian wire format.
+fn write_guid(bytes: &mut [u8], guid: &Guid) {
+    bytes[..16].copy_from_slice(&guid.to_bytes());
+}
+
+// UEFI platforms are little-endian, so the native layout of a u16 slice matches the variable
+// format of BootOrder and friends.
+fn u16_bytes(buf: &[u16]) -> &[u8] {
+    unsafe { slice::from_raw_parts(buf.as_ptr() as *const u8, buf.len() * 2) }
+}
+
+fn u16_bytes_mut(buf: &mut [u16]) -> &mut [u8] {
+    unsafe { slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut u8, buf.len() * 2) }
+}
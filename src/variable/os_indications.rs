@@ -0,0 +1,112 @@
+// Copyright 2017 CoreOS, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The `OsIndicationsSupported` and `OsIndications` variables, through which the OS asks the
+//! firmware to do something on the next boot, such as entering its setup menu.
+
+use core::mem;
+
+use base::Status;
+use runtimeservices::{ResetType, EFI_GLOBAL_VARIABLE_GUID, VARIABLE_NON_VOLATILE,
+                      VARIABLE_BOOTSERVICE_ACCESS, VARIABLE_RUNTIME_ACCESS};
+use util::CStr16;
+use super::runtime_services;
+
+bitflags! {
+    /// Bits of `OsIndicationsSupported` and `OsIndications`.
+    pub struct OsIndications: u64 {
+        /// Stop in the firmware's setup menu on the next boot.
+        const OS_INDICATIONS_BOOT_TO_FW_UI = 0x0000000000000001;
+        const OS_INDICATIONS_TIMESTAMP_REVOCATION = 0x0000000000000002;
+        /// Process capsules found in `\EFI\UpdateCapsule` on the next boot.
+        const OS_INDICATIONS_FILE_CAPSULE_DELIVERY_SUPPORTED = 0x0000000000000004;
+        const OS_INDICATIONS_FMP_CAPSULE_SUPPORTED = 0x0000000000000008;
+        const OS_INDICATIONS_CAPSULE_RESULT_VAR_SUPPORTED = 0x0000000000000010;
+        /// Start OS-defined recovery on the next boot.
+        const OS_INDICATIONS_START_OS_RECOVERY = 0x0000000000000020;
+        /// Start platform recovery on the next boot.
+        const OS_INDICATIONS_START_PLATFORM_RECOVERY = 0x0000000000000040;
+        const OS_INDICATIONS_JSON_CONFIG_DATA_REFRESH = 0x0000000000000080;
+    }
+}
+
+/// Read one of the variables as a raw value, so bits this crate doesn't know about survive being
+/// written back.
+fn read_os_indications_variable(name: &str) -> Result<u64, Status> {
+    let mut name_buf = [0u16; 24];
+    let name = CStr16::from_str_with_buf(name, &mut name_buf)?;
+    let mut value = [0u8; 8];
+
+    match runtime_services().get_variable(name, &EFI_GLOBAL_VARIABLE_GUID, &mut value) {
+        Ok((size, _)) if size == mem::size_of::<u64>() => Ok(u64::from_le_bytes(value)),
+        Ok(_) => Err(Status::VolumeCorrupted),
+        Err(Status::NotFound) => Ok(0),
+        Err(e) => Err(e),
+    }
+}
+
+/// The indications the firmware supports.
+pub fn os_indications_supported() -> Result<OsIndications, Status> {
+    read_os_indications_variable("OsIndicationsSupported").map(OsIndications::from_bits_truncate)
+}
+
+/// The indications requested for the next boot.
+pub fn os_indications() -> Result<OsIndications, Status> {
+    read_os_indications_variable("OsIndications").map(OsIndications::from_bits_truncate)
+}
+
+/// Replace the indications requested for the next boot.
+pub fn set_os_indications(indications: OsIndications) -> Result<(), Status> {
+    write_os_indications(indications.bits())
+}
+
+fn write_os_indications(value: u64) -> Result<(), Status> {
+    let mut name_buf = [0u16; 24];
+    let name = CStr16::from_str_with_buf("OsIndications", &mut name_buf)?;
+    let attributes = VARIABLE_NON_VOLATILE | VARIABLE_BOOTSERVICE_ACCESS | VARIABLE_RUNTIME_ACCESS;
+
+    runtime_services().set_variable(name, &EFI_GLOBAL_VARIABLE_GUID, attributes, &value.to_le_bytes())
+}
+
+/// Add `indication` to those requested for the next boot, failing with `Status::Unsupported`
+/// if the firmware doesn't support it.
+pub fn request_os_indication(indication: OsIndications) -> Result<(), Status> {
+    if !os_indications_supported()?.contains(indication) {
+        return Err(Status::Unsupported);
+    }
+
+    write_os_indications(read_os_indications_variable("OsIndications")? | indication.bits())
+}
+
+/// Whether the firmware can be asked to stop in its setup menu, so a "Firmware settings" entry
+/// makes sense.
+pub fn firmware_setup_supported() -> bool {
+    os_indications_supported()
+        .map(|supported| supported.contains(OS_INDICATIONS_BOOT_TO_FW_UI))
+        .unwrap_or(false)
+}
+
+/// Request `indication` and reset the system so the firmware acts on it. Only returns if the
+/// indication couldn't be requested, with the reason.
+pub fn reboot_with_os_indication(indication: OsIndications) -> Status {
+    match request_os_indication(indication) {
+        Ok(()) => runtime_services().reset_system(ResetType::Cold, Status::Success),
+        Err(e) => e,
+    }
+}
+
+/// Reboot into the firmware's setup menu. Only returns if that isn't possible, with the reason.
+pub fn reboot_into_firmware_setup() -> Status {
+    reboot_with_os_indication(OS_INDICATIONS_BOOT_TO_FW_UI)
+}
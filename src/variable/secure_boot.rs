@@ -0,0 +1,395 @@
+// Copyright 2017 CoreOS, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Secure Boot state, and the EFI_SIGNATURE_LIST format used by the `PK`, `KEK`, `db` and `dbx`
+//! signature databases.
+
+use base::Status;
+use guid::Guid;
+use runtimeservices::EFI_GLOBAL_VARIABLE_GUID;
+use util::CStr16;
+use super::{read_guid, read_u32, runtime_services};
+
+/// GUID for the `db`, `dbx` and `dbt` signature database variables
+pub static EFI_IMAGE_SECURITY_DATABASE_GUID: Guid = Guid(0xD719B2CB, 0x3D3A, 0x4596, [0xA3,0xBC,0xDA,0xD0,0x0E,0x67,0x65,0x6F]);
+
+/// GUID for the variables owned by shim, such as `MokList`
+pub static SHIM_LOCK_GUID: Guid = Guid(0x605DAB50, 0xE046, 0x4300, [0xAB,0xB6,0x3D,0xD8,0x10,0xDD,0x8B,0x23]);
+
+/// Signature type GUID for SHA-1 hashes
+pub static EFI_CERT_SHA1_GUID: Guid = Guid(0x826CA512, 0xCF10, 0x4AC9, [0xB1,0x87,0xBE,0x01,0x49,0x66,0x31,0xBD]);
+/// Signature type GUID for SHA-224 hashes
+pub static EFI_CERT_SHA224_GUID: Guid = Guid(0x0B6E5233, 0xA65C, 0x44C9, [0x94,0x07,0xD9,0xAB,0x83,0xBF,0xC8,0xBD]);
+/// Signature type GUID for SHA-256 hashes
+pub static EFI_CERT_SHA256_GUID: Guid = Guid(0xC1C41626, 0x504C, 0x4092, [0xAC,0xA9,0x41,0xF9,0x36,0x93,0x43,0x28]);
+/// Signature type GUID for SHA-384 hashes
+pub static EFI_CERT_SHA384_GUID: Guid = Guid(0xFF3E5307, 0x9FD0, 0x48C9, [0x85,0xF1,0x8A,0xD5,0x6C,0x70,0x1E,0x01]);
+/// Signature type GUID for SHA-512 hashes
+pub static EFI_CERT_SHA512_GUID: Guid = Guid(0x093E0FAE, 0xA6C4, 0x4F50, [0x9F,0x1B,0xD4,0x1E,0x2B,0x89,0xC1,0x9A]);
+/// Signature type GUID for RSA-2048 public keys
+pub static EFI_CERT_RSA2048_GUID: Guid = Guid(0x3C5766E8, 0x269C, 0x4E34, [0xAA,0x14,0xED,0x77,0x6E,0x85,0xB3,0xB6]);
+/// Signature type GUID for RSA-2048 signatures of SHA-1 hashes
+pub static EFI_CERT_RSA2048_SHA1_GUID: Guid = Guid(0x67F8444F, 0x8743, 0x48F1, [0xA3,0x28,0x1E,0xAA,0xB8,0x73,0x60,0x80]);
+/// Signature type GUID for RSA-2048 signatures of SHA-256 hashes
+pub static EFI_CERT_RSA2048_SHA256_GUID: Guid = Guid(0xE2B36190, 0x879B, 0x4A3D, [0xAD,0x8D,0xF2,0xE7,0xBB,0xA3,0x27,0x84]);
+/// Signature type GUID for DER-encoded X.509 certificates
+pub static EFI_CERT_X509_GUID: Guid = Guid(0xA5C059A1, 0x94E4, 0x4AA7, [0x87,0xB5,0xAB,0x15,0x5C,0x2B,0xF0,0x72]);
+/// Signature type GUID for SHA-256 hashes of X.509 certificates
+pub static EFI_CERT_X509_SHA256_GUID: Guid = Guid(0x3BD2A492, 0x96C0, 0x4079, [0xB4,0x20,0xFC,0xF9,0x8E,0xF1,0x03,0xED]);
+/// Signature type GUID for SHA-384 hashes of X.509 certificates
+pub static EFI_CERT_X509_SHA384_GUID: Guid = Guid(0x7076876E, 0x80C2, 0x4EE6, [0xAA,0xD2,0x28,0xB3,0x49,0xA6,0x86,0x5B]);
+/// Signature type GUID for SHA-512 hashes of X.509 certificates
+pub static EFI_CERT_X509_SHA512_GUID: Guid = Guid(0x446DBF63, 0x2502, 0x4CDA, [0xBC,0xFA,0x24,0x65,0xD2,0xB0,0xFE,0x9D]);
+/// Signature type GUID for PKCS#7 signed data
+pub static EFI_CERT_TYPE_PKCS7_GUID: Guid = Guid(0x4AAFD29D, 0x68DF, 0x49EE, [0x8A,0xA9,0x34,0x7D,0x37,0x56,0x65,0xA7]);
+
+/// The Secure Boot modes defined by UEFI 2.5.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SecureBootMode {
+    /// No platform key is enrolled, and anything can be changed.
+    Setup,
+    /// A platform key is enrolled, and images are verified.
+    User,
+    /// No platform key is enrolled, and verification results are only logged.
+    Audit,
+    /// A platform key is enrolled, and the mode can't be changed without clearing it.
+    Deployed,
+}
+
+fn read_bool_variable(name: &str) -> Result<bool, Status> {
+    let mut name_buf = [0u16; 16];
+    let name = CStr16::from_str_with_buf(name, &mut name_buf)?;
+    let mut value = [0u8; 1];
+
+    let (size, _) = runtime_services().get_variable(name, &EFI_GLOBAL_VARIABLE_GUID, &mut value)?;
+    if size != 1 {
+        return Err(Status::VolumeCorrupted);
+    }
+
+    Ok(value[0] == 1)
+}
+
+/// Variables added after UEFI 2.3.1 are missing on older firmware, where they'd read as zero.
+fn read_optional_bool_variable(name: &str) -> Result<bool, Status> {
+    match read_bool_variable(name) {
+        Err(Status::NotFound) => Ok(false),
+        r => r,
+    }
+}
+
+/// Whether the firmware is enforcing Secure Boot. Firmware without Secure Boot support reports
+/// `false`.
+pub fn secure_boot_enabled() -> Result<bool, Status> {
+    read_optional_bool_variable("SecureBoot")
+}
+
+/// Whether the platform is in setup mode, with no platform key enrolled.
+pub fn setup_mode() -> Result<bool, Status> {
+    read_optional_bool_variable("SetupMode")
+}
+
+/// Whether the platform is in audit mode.
+pub fn audit_mode() -> Result<bool, Status> {
+    read_optional_bool_variable("AuditMode")
+}
+
+/// Whether the platform is in deployed mode.
+pub fn deployed_mode() -> Result<bool, Status> {
+    read_optional_bool_variable("DeployedMode")
+}
+
+/// Work out the current Secure Boot mode from `SetupMode`, `AuditMode` and `DeployedMode`.
+pub fn secure_boot_mode() -> Result<SecureBootMode, Status> {
+    if audit_mode()? {
+        return Ok(SecureBootMode::Audit);
+    }
+    if deployed_mode()? {
+        return Ok(SecureBootMode::Deployed);
+    }
+    if setup_mode()? {
+        return Ok(SecureBootMode::Setup);
+    }
+
+    Ok(SecureBootMode::User)
+}
+
+/// The type of the signatures in a signature list, identified by its signature type GUID.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SignatureType {
+    Sha1,
+    Sha224,
+    Sha256,
+    Sha384,
+    Sha512,
+    Rsa2048,
+    Rsa2048Sha1,
+    Rsa2048Sha256,
+    X509,
+    X509Sha256,
+    X509Sha384,
+    X509Sha512,
+    Pkcs7,
+    Unknown(Guid),
+}
+
+impl SignatureType {
+    pub fn from_guid(guid: &Guid) -> SignatureType {
+        let known = [
+            (&EFI_CERT_SHA1_GUID, SignatureType::Sha1),
+            (&EFI_CERT_SHA224_GUID, SignatureType::Sha224),
+            (&EFI_CERT_SHA256_GUID, SignatureType::Sha256),
+            (&EFI_CERT_SHA384_GUID, SignatureType::Sha384),
+            (&EFI_CERT_SHA512_GUID, SignatureType::Sha512),
+            (&EFI_CERT_RSA2048_GUID, SignatureType::Rsa2048),
+            (&EFI_CERT_RSA2048_SHA1_GUID, SignatureType::Rsa2048Sha1),
+            (&EFI_CERT_RSA2048_SHA256_GUID, SignatureType::Rsa2048Sha256),
+            (&EFI_CERT_X509_GUID, SignatureType::X509),
+            (&EFI_CERT_X509_SHA256_GUID, SignatureType::X509Sha256),
+            (&EFI_CERT_X509_SHA384_GUID, SignatureType::X509Sha384),
+            (&EFI_CERT_X509_SHA512_GUID, SignatureType::X509Sha512),
+            (&EFI_CERT_TYPE_PKCS7_GUID, SignatureType::Pkcs7),
+        ];
+
+        for &(known_guid, signature_type) in known.iter() {
+            if known_guid == guid {
+                return signature_type;
+            }
+        }
+
+        SignatureType::Unknown(*guid)
+    }
+
+    pub fn guid(&self) -> Guid {
+        match *self {
+            SignatureType::Sha1 => EFI_CERT_SHA1_GUID,
+            SignatureType::Sha224 => EFI_CERT_SHA224_GUID,
+            SignatureType::Sha256 => EFI_CERT_SHA256_GUID,
+            SignatureType::Sha384 => EFI_CERT_SHA384_GUID,
+            SignatureType::Sha512 => EFI_CERT_SHA512_GUID,
+            SignatureType::Rsa2048 => EFI_CERT_RSA2048_GUID,
+            SignatureType::Rsa2048Sha1 => EFI_CERT_RSA2048_SHA1_GUID,
+            SignatureType::Rsa2048Sha256 => EFI_CERT_RSA2048_SHA256_GUID,
+            SignatureType::X509 => EFI_CERT_X509_GUID,
+            SignatureType::X509Sha256 => EFI_CERT_X509_SHA256_GUID,
+            SignatureType::X509Sha384 => EFI_CERT_X509_SHA384_GUID,
+            SignatureType::X509Sha512 => EFI_CERT_X509_SHA512_GUID,
+            SignatureType::Pkcs7 => EFI_CERT_TYPE_PKCS7_GUID,
+            SignatureType::Unknown(guid) => guid,
+        }
+    }
+}
+
+/// Size of the fixed part of an EFI_SIGNATURE_LIST.
+const SIGNATURE_LIST_HEADER_SIZE: usize = 28;
+
+/// Size of the owner GUID which starts every EFI_SIGNATURE_DATA.
+const SIGNATURE_OWNER_SIZE: usize = 16;
+
+/// One signature (a hash, key or certificate) from a signature list.
+#[derive(Clone, Copy, Debug)]
+pub struct Signature<'a> {
+    /// The agent which added this signature.
+    pub owner: Guid,
+    pub data: &'a [u8],
+}
+
+/// A decoded EFI_SIGNATURE_LIST: a run of signatures of the same type and size.
+#[derive(Clone, Copy, Debug)]
+pub struct SignatureList<'a> {
+    pub signature_type: SignatureType,
+    /// Type-specific header; empty for all types defined by the specification.
+    pub header: &'a [u8],
+    signature_size: usize,
+    signatures: &'a [u8],
+}
+
+impl<'a> SignatureList<'a> {
+    /// Decode the signature list at the start of `data`, and return it along with its total size.
+    fn parse(data: &'a [u8]) -> Result<(SignatureList<'a>, usize), Status> {
+        if data.len() < SIGNATURE_LIST_HEADER_SIZE {
+            return Err(Status::InvalidParameter);
+        }
+
+        let list_size = read_u32(&data[16..]) as usize;
+        let header_size = read_u32(&data[20..]) as usize;
+        let signature_size = read_u32(&data[24..]) as usize;
+
+        let signatures_start = match SIGNATURE_LIST_HEADER_SIZE.checked_add(header_size) {
+            Some(start) => start,
+            None => return Err(Status::InvalidParameter),
+        };
+        if list_size > data.len() || list_size < signatures_start || signature_size < SIGNATURE_OWNER_SIZE {
+            return Err(Status::InvalidParameter);
+        }
+        let signatures_len = list_size - signatures_start;
+        if signatures_len / signature_size * signature_size != signatures_len {
+            return Err(Status::InvalidParameter);
+        }
+
+        let list = SignatureList {
+            signature_type: SignatureType::from_guid(&read_guid(data)),
+            header: &data[SIGNATURE_LIST_HEADER_SIZE..signatures_start],
+            signature_size,
+            signatures: &data[signatures_start..list_size],
+        };
+
+        Ok((list, list_size))
+    }
+
+    /// The number of signatures in this list.
+    pub fn len(&self) -> usize {
+        self.signatures.len() / self.signature_size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.signatures.is_empty()
+    }
+
+    pub fn signatures(&self) -> Signatures<'a> {
+        Signatures {
+            bytes: self.signatures,
+            signature_size: self.signature_size,
+        }
+    }
+}
+
+/// Iterator over the signatures in a signature list.
+#[derive(Clone, Debug)]
+pub struct Signatures<'a> {
+    bytes: &'a [u8],
+    signature_size: usize,
+}
+
+impl<'a> Iterator for Signatures<'a> {
+    type Item = Signature<'a>;
+
+    fn next(&mut self) -> Option<Signature<'a>> {
+        if self.bytes.is_empty() {
+            return None;
+        }
+
+        let (signature, rest) = self.bytes.split_at(self.signature_size);
+        self.bytes = rest;
+        Some(Signature {
+            owner: read_guid(signature),
+            data: &signature[SIGNATURE_OWNER_SIZE..],
+        })
+    }
+}
+
+/// A signature database: the contents of `PK`, `KEK`, `db`, `dbx`, `MokList` and similar
+/// variables, which are a sequence of signature lists.
+#[derive(Clone, Copy, Debug)]
+pub struct SignatureDatabase<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> SignatureDatabase<'a> {
+    /// Check that `data` is a well-formed sequence of signature lists.
+    pub fn parse(data: &'a [u8]) -> Result<SignatureDatabase<'a>, Status> {
+        let mut rest = data;
+        while !rest.is_empty() {
+            let (_, size) = SignatureList::parse(rest)?;
+            rest = &rest[size..];
+        }
+
+        Ok(SignatureDatabase { bytes: data })
+    }
+
+    pub fn lists(&self) -> SignatureLists<'a> {
+        SignatureLists { bytes: self.bytes }
+    }
+
+    /// Whether the database holds a signature of type `signature_type` whose data is exactly
+    /// `data`, e.g. the SHA-256 hash of an image in `dbx`.
+    pub fn contains(&self, signature_type: SignatureType, data: &[u8]) -> bool {
+        self.lists()
+            .filter(|list| list.signature_type == signature_type)
+            .any(|list| list.signatures().any(|signature| signature.data == data))
+    }
+}
+
+/// Iterator over the signature lists in a signature database.
+#[derive(Clone, Debug)]
+pub struct SignatureLists<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Iterator for SignatureLists<'a> {
+    type Item = SignatureList<'a>;
+
+    fn next(&mut self) -> Option<SignatureList<'a>> {
+        // SignatureDatabase::parse has already checked every list.
+        match SignatureList::parse(self.bytes) {
+            Ok((list, size)) => {
+                self.bytes = &self.bytes[size..];
+                Some(list)
+            },
+            Err(_) => None,
+        }
+    }
+}
+
+/// The variables which hold signature databases.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SignatureDatabaseVariable {
+    /// The platform key.
+    PK,
+    /// Key exchange keys, which may update `db` and `dbx`.
+    KEK,
+    /// Allowed signatures.
+    Db,
+    /// Forbidden signatures.
+    Dbx,
+    /// Timestamp signatures.
+    Dbt,
+    /// Machine owner keys enrolled with shim.
+    MokList,
+}
+
+impl SignatureDatabaseVariable {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            SignatureDatabaseVariable::PK => "PK",
+            SignatureDatabaseVariable::KEK => "KEK",
+            SignatureDatabaseVariable::Db => "db",
+            SignatureDatabaseVariable::Dbx => "dbx",
+            SignatureDatabaseVariable::Dbt => "dbt",
+            SignatureDatabaseVariable::MokList => "MokList",
+        }
+    }
+
+    pub fn vendor(&self) -> &'static Guid {
+        match *self {
+            SignatureDatabaseVariable::PK | SignatureDatabaseVariable::KEK => &EFI_GLOBAL_VARIABLE_GUID,
+            SignatureDatabaseVariable::Db | SignatureDatabaseVariable::Dbx | SignatureDatabaseVariable::Dbt => &EFI_IMAGE_SECURITY_DATABASE_GUID,
+            SignatureDatabaseVariable::MokList => &SHIM_LOCK_GUID,
+        }
+    }
+}
+
+/// Read a signature database variable into `buf` and decode it. A missing variable reads as an
+/// empty database. Use `RuntimeServices::get_variable_size` to size `buf`.
+pub fn read_signature_database(variable: SignatureDatabaseVariable, buf: &mut [u8]) -> Result<SignatureDatabase<'_>, Status> {
+    let mut name_buf = [0u16; 8];
+    let name = CStr16::from_str_with_buf(variable.name(), &mut name_buf)?;
+
+    let size = match runtime_services().get_variable(name, variable.vendor(), buf) {
+        Ok((size, _)) => size,
+        Err(Status::NotFound) => 0,
+        Err(e) => return Err(e),
+    };
+
+    SignatureDatabase::parse(&buf[..size])
+}
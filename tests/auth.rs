@@ -0,0 +1,67 @@
+extern crate uefi;
+
+use uefi::Time;
+use uefi::util::CStr16;
+use uefi::variable::{AuthenticatedVariable, EFI_IMAGE_SECURITY_DATABASE_GUID};
+use uefi::{VARIABLE_NON_VOLATILE, VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS};
+
+fn timestamp() -> Time {
+    let mut time = Time::default();
+    time.year = 2017;
+    time.month = 11;
+    time.day = 2;
+    time.hour = 13;
+    time.minute = 45;
+    time.second = 30;
+    // Must not end up in the header.
+    time.nanosecond = 5;
+    time.timezone = 60;
+    time
+}
+
+#[test]
+fn authentication_2_encode() {
+    let variable = AuthenticatedVariable::new(timestamp(), &[0x30, 0x82], &[0xAA, 0xBB, 0xCC]);
+    let expected: [u8; 45] = [
+        // EFI_TIME
+        0xE1, 0x07, 0x0B, 0x02, 0x0D, 0x2D, 0x1E, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        // WIN_CERTIFICATE: length, revision, type
+        0x1A, 0x00, 0x00, 0x00, 0x00, 0x02, 0xF1, 0x0E,
+        // EFI_CERT_TYPE_PKCS7_GUID
+        0x9D, 0xD2, 0xAF, 0x4A, 0xDF, 0x68, 0xEE, 0x49,
+        0x8A, 0xA9, 0x34, 0x7D, 0x37, 0x56, 0x65, 0xA7,
+        // PKCS7 blob and data
+        0x30, 0x82, 0xAA, 0xBB, 0xCC,
+    ];
+
+    let mut buf = [0u8; 64];
+    assert_eq!(variable.header_len(), 42);
+    let len = variable.encode(&mut buf).unwrap();
+    assert_eq!(&buf[..len], &expected[..]);
+    assert!(variable.encode(&mut buf[..44]).is_err());
+}
+
+#[test]
+fn authentication_2_signed_data() {
+    let variable = AuthenticatedVariable::new(timestamp(), &[], &[0xAA]);
+    let name = CStr16::from_u16_with_nul(&[0x64, 0x62, 0]).unwrap();
+    let attributes = VARIABLE_NON_VOLATILE | VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS;
+    let expected: [u8; 41] = [
+        // "db"
+        0x64, 0x00, 0x62, 0x00,
+        // EFI_IMAGE_SECURITY_DATABASE_GUID
+        0xCB, 0xB2, 0x19, 0xD7, 0x3A, 0x3D, 0x96, 0x45,
+        0xA3, 0xBC, 0xDA, 0xD0, 0x0E, 0x67, 0x65, 0x6F,
+        // attributes
+        0x21, 0x00, 0x00, 0x00,
+        // EFI_TIME
+        0xE1, 0x07, 0x0B, 0x02, 0x0D, 0x2D, 0x1E, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0xAA,
+    ];
+
+    let mut buf = [0u8; 64];
+    let len = variable.signed_data(name, &EFI_IMAGE_SECURITY_DATABASE_GUID, attributes, &mut buf).unwrap();
+    assert_eq!(&buf[..len], &expected[..]);
+}
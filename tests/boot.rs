@@ -0,0 +1,68 @@
+extern crate uefi;
+
+use uefi::Status;
+use uefi::variable::{LoadOption, LoadOptionType, LOAD_OPTION_ACTIVE, LOAD_OPTION_HIDDEN};
+
+const FILE_PATH_LIST: [u8; 14] = [
+    // Media/File Path node: "\a"
+    0x04, 0x04, 0x0A, 0x00, 0x5C, 0x00, 0x61, 0x00, 0x00, 0x00,
+    // End Entire Path node
+    0x7F, 0xFF, 0x04, 0x00,
+];
+
+const LOAD_OPTION: [u8; 34] = [
+    // attributes, file path list length
+    0x01, 0x00, 0x00, 0x00, 0x0E, 0x00,
+    // "Linux"
+    0x4C, 0x00, 0x69, 0x00, 0x6E, 0x00, 0x75, 0x00, 0x78, 0x00, 0x00, 0x00,
+    0x04, 0x04, 0x0A, 0x00, 0x5C, 0x00, 0x61, 0x00, 0x00, 0x00,
+    0x7F, 0xFF, 0x04, 0x00,
+    // optional data
+    0x61, 0x62,
+];
+
+#[test]
+fn load_option_parse() {
+    let option = LoadOption::parse(&LOAD_OPTION).unwrap();
+
+    assert_eq!(option.attributes, LOAD_OPTION_ACTIVE);
+    assert!(option.description().eq("Linux".chars()));
+    assert_eq!(format!("{}", option.description()), "Linux");
+    assert_eq!(option.file_path_list(), &FILE_PATH_LIST[..]);
+    assert_eq!(option.device_paths().count(), 1);
+    assert_eq!(option.device_path().type_, 0x04);
+    assert_eq!(option.optional_data, b"ab");
+}
+
+#[test]
+fn load_option_parse_truncated() {
+    assert!(LoadOption::parse(&LOAD_OPTION[..20]).is_err());
+}
+
+#[test]
+fn load_option_round_trip() {
+    let option = LoadOption::parse(&LOAD_OPTION).unwrap();
+    let mut buf = [0u8; 64];
+    let len = option.encode(&mut buf).unwrap();
+    assert_eq!(&buf[..len], &LOAD_OPTION[..]);
+
+    let option = LoadOption::new(LOAD_OPTION_ACTIVE, "Linux", &FILE_PATH_LIST, b"ab").unwrap();
+    assert_eq!(option.encoded_len(), LOAD_OPTION.len());
+    let len = option.encode(&mut buf).unwrap();
+    assert_eq!(&buf[..len], &LOAD_OPTION[..]);
+
+    assert!(option.encode(&mut buf[..10]).is_err());
+    assert!(LoadOption::new(LOAD_OPTION_HIDDEN, "Linux", &FILE_PATH_LIST[..10], b"").is_err());
+    assert_eq!(LoadOption::new(LOAD_OPTION_ACTIVE, "Lin\0ux", &FILE_PATH_LIST, b"").err(), Some(Status::InvalidParameter));
+}
+
+#[test]
+fn load_option_names() {
+    let mut buf = [0u16; 12];
+    let name: Vec<u16> = "Boot00A1\0".encode_utf16().collect();
+    assert_eq!(LoadOptionType::Boot.option_name(0xA1, &mut buf).as_slice_with_nul(), &name[..]);
+    assert_eq!(LoadOptionType::Boot.parse_option_name(&name), Some(0xA1));
+    assert_eq!(LoadOptionType::Driver.parse_option_name(&name), None);
+
+    assert_eq!(LoadOptionType::SysPrep.option_name(0xFFFF, &mut buf), "SysPrepFFFF");
+}
@@ -0,0 +1,111 @@
+#![cfg(feature = "mock")]
+
+extern crate uefi;
+
+use std::slice;
+
+use uefi::mock::MockFirmware;
+use uefi::{deliver_capsule, Capsule, CapsuleHeader, ResetType, ScatterGatherList, Status, Guid,
+           CAPSULE_FLAGS_PERSIST_ACROSS_RESET};
+
+const CAPSULE_GUID: Guid = Guid(0x6DCBD5ED, 0xE82D, 0x4C44, [0xBD, 0xA1, 0x71, 0x94, 0x19, 0x9A, 0xD9, 0x2A]);
+
+/// A capsule with a header of `header_size` bytes, followed by `payload`.
+fn capsule(header_size: u32, flags: u32, payload: &[u8]) -> Vec<u8> {
+    let image_size = header_size + payload.len() as u32;
+    let mut data = Vec::new();
+    data.extend_from_slice(&CAPSULE_GUID.to_bytes());
+    data.extend_from_slice(&header_size.to_le_bytes());
+    data.extend_from_slice(&flags.to_le_bytes());
+    data.extend_from_slice(&image_size.to_le_bytes());
+    data.resize(header_size as usize, 0);
+    data.extend_from_slice(payload);
+    data
+}
+
+#[test]
+fn header_validation() {
+    let firmware = MockFirmware::new();
+
+    let data = capsule(28, 0, b"payload");
+    assert_eq!(Capsule::new(&data[..27]).err(), Some(Status::InvalidParameter));
+    assert_eq!(Capsule::new(&data[..data.len() - 1]).err(), Some(Status::InvalidParameter));
+    assert_eq!(Capsule::new(&capsule(27, 0, b"payload")).err(), Some(Status::InvalidParameter));
+
+    let mut oversized_header = capsule(28, 0, b"payload");
+    oversized_header[16..20].copy_from_slice(&64u32.to_le_bytes());
+    assert_eq!(Capsule::new(&oversized_header).err(), Some(Status::InvalidParameter));
+    assert_eq!(firmware.page_allocations(), 0);
+
+    let capsule = Capsule::new(&data).unwrap();
+    assert_eq!(capsule.len(), data.len());
+    assert_eq!(capsule.header().capsule_guid, CAPSULE_GUID);
+    assert_eq!(unsafe { slice::from_raw_parts(capsule.address() as usize as *const u8, capsule.len()) }, &data[..]);
+    assert_eq!(firmware.page_allocations(), 1);
+    capsule.free().unwrap();
+    assert_eq!(firmware.page_allocations(), 0);
+}
+
+#[test]
+fn scatter_gather_list() {
+    let _firmware = MockFirmware::new();
+    let a = Capsule::new(&capsule(28, 0, b"a")).unwrap();
+    let b = Capsule::new(&capsule(32, 0, b"bb")).unwrap();
+
+    let list = ScatterGatherList::new(&[&a, &b]).unwrap();
+    let descriptors = list.descriptors();
+    assert_eq!(list.address(), descriptors.as_ptr() as usize as u64);
+    assert_eq!(descriptors.len(), 3);
+    assert_eq!((descriptors[0].length, descriptors[0].address), (29, a.address()));
+    assert_eq!((descriptors[1].length, descriptors[1].address), (34, b.address()));
+    assert_eq!((descriptors[2].length, descriptors[2].address), (0, 0));
+}
+
+#[test]
+fn delivery() {
+    let firmware = MockFirmware::new();
+    let data = capsule(28, 0, b"payload");
+
+    assert_eq!(deliver_capsule(&data), Err(Status::Unsupported));
+    let rs = firmware.system_table().runtime_services();
+    let header = Capsule::new(&data).unwrap();
+    assert_eq!(rs.query_capsule_capabilities(&[header.header()]).err(), Some(Status::Unsupported));
+    assert_eq!(rs.update_capsule(&[header.header()], None), Err(Status::Unsupported));
+    header.free().unwrap();
+
+    firmware.set_capsule_capabilities(Ok((16, 0)));
+    assert_eq!(deliver_capsule(&data), Err(Status::BadBufferSize));
+
+    firmware.set_capsule_capabilities(Ok((4096, 7)));
+    assert_eq!(deliver_capsule(&data), Err(Status::DeviceError));
+    assert!(firmware.capsules().is_empty());
+    assert_eq!(firmware.page_allocations(), 0);
+
+    // processed immediately, so freed straight away
+    firmware.set_capsule_capabilities(Ok((4096, 1)));
+    assert_eq!(deliver_capsule(&data), Ok(ResetType::Warm));
+    let capsules = firmware.capsules();
+    assert_eq!(capsules.len(), 1);
+    assert_eq!(capsules[0].0[0].capsule_guid, CAPSULE_GUID);
+    assert_eq!(capsules[0].1, 0);
+    assert_eq!(firmware.page_allocations(), 0);
+
+    // left for the firmware to read after the reset, along with its scatter-gather list
+    let data = capsule(28, CAPSULE_FLAGS_PERSIST_ACROSS_RESET.bits(), b"payload");
+    assert_eq!(deliver_capsule(&data), Ok(ResetType::Warm));
+    let capsules = firmware.capsules();
+    assert_eq!(capsules.len(), 2);
+    assert_ne!(capsules[1].1, 0);
+    assert_eq!(firmware.page_allocations(), 2);
+}
+
+#[test]
+fn header_flags() {
+    let header = CapsuleHeader {
+        capsule_guid: CAPSULE_GUID,
+        header_size: 28,
+        flags: CAPSULE_FLAGS_PERSIST_ACROSS_RESET.bits() | 0x1234,
+        capsule_image_size: 28,
+    };
+    assert_eq!(header.flags(), CAPSULE_FLAGS_PERSIST_ACROSS_RESET);
+}
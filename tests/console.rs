@@ -0,0 +1,90 @@
+extern crate uefi;
+
+use std::cell::RefCell;
+
+use uefi::{Attribute, SimpleTextOutput, Status};
+
+/// An output device which records each string written to it, and can display ASCII plus the
+/// characters in `extra`.
+struct Recorder {
+    writes: RefCell<Vec<Vec<u16>>>,
+    extra: Vec<u16>,
+}
+
+impl Recorder {
+    fn new(extra: &str) -> Recorder {
+        Recorder { writes: RefCell::new(Vec::new()), extra: extra.encode_utf16().collect() }
+    }
+
+    fn writes(&self) -> Vec<String> {
+        self.writes.borrow().iter().map(|w| String::from_utf16(w).unwrap()).collect()
+    }
+}
+
+unsafe fn ucs2(str: *const u16) -> Vec<u16> {
+    let mut v = Vec::new();
+    let mut i = 0;
+    while *str.add(i) != 0 {
+        v.push(*str.add(i));
+        i += 1;
+    }
+    v
+}
+
+impl SimpleTextOutput for Recorder {
+    fn write_raw(&self, str: *const u16) -> Status {
+        self.writes.borrow_mut().push(unsafe { ucs2(str) });
+        Status::Success
+    }
+
+    fn test_raw(&self, str: *const u16) -> Status {
+        let displayable = unsafe { ucs2(str) }.iter().all(|c| *c < 0x80 || self.extra.contains(c));
+        if displayable { Status::Success } else { Status::Unsupported }
+    }
+
+    fn set_attribute(&self, _: Attribute) -> Status {
+        Status::Success
+    }
+}
+
+#[test]
+fn chunks() {
+    let output = Recorder::new("");
+    let long = "a".repeat(62);
+    assert_eq!(output.write(&format!("{}b", long)), Status::Success);
+    assert_eq!(output.writes(), [long, "b".to_string()]);
+
+    // a \r\n pair isn't split across chunks
+    let output = Recorder::new("");
+    let long = "a".repeat(61);
+    output.write(&format!("{}\nb", long));
+    assert_eq!(output.writes(), [format!("{}\r\n", long), "b".to_string()]);
+
+    let output = Recorder::new("");
+    output.write("");
+    assert!(output.writes().is_empty());
+}
+
+#[test]
+fn newlines() {
+    let output = Recorder::new("");
+    output.write("one\ntwo\r\nthree\n");
+    assert_eq!(output.writes(), ["one\r\ntwo\r\nthree\r\n"]);
+}
+
+#[test]
+fn replacement() {
+    // outside the BMP, so it can't be sent as one UCS-2 character
+    let output = Recorder::new("\u{FFFD}");
+    output.write("a😀b");
+    assert_eq!(output.writes(), ["a\u{FFFD}b"]);
+
+    // the device can't show U+FFFD either
+    let output = Recorder::new("");
+    output.write("a😀bé");
+    assert_eq!(output.writes(), ["a?b?"]);
+
+    let output = Recorder::new("é");
+    output.write("café");
+    assert_eq!(output.writes(), ["café"]);
+}
@@ -0,0 +1,15 @@
+#[macro_use]
+extern crate uefi;
+
+use uefi::{Handle, Status, SystemTable};
+
+fn main(_image: Handle, _st: &SystemTable) -> Result<(), Status> {
+    Ok(())
+}
+
+efi_main!(main);
+
+#[test]
+fn entry_point_signature() {
+    let _entry: extern "efiapi" fn(Handle, *const SystemTable) -> Status = efi_entry;
+}
@@ -0,0 +1,53 @@
+extern crate uefi;
+
+use uefi::{Esrt, FirmwareType, Guid, LastAttemptStatus};
+
+const ESRT: [u8; 96] = [
+    // count, count max, version
+    0x02, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    // system firmware, version 0x10, lowest 0x8, last attempt 0x10 succeeded
+    0x78, 0x56, 0x34, 0x12, 0xBC, 0x9A, 0xF0, 0xDE, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+    0x01, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    // device firmware, last attempt failed with a vendor error
+    0x11, 0x11, 0x11, 0x11, 0x22, 0x22, 0x33, 0x33, 0x44, 0x44, 0x55, 0x55, 0x55, 0x55, 0x55, 0x55,
+    0x02, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x01, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0x10, 0x00, 0x00,
+];
+
+#[test]
+fn esrt_parse() {
+    let esrt = Esrt::parse(&ESRT).unwrap();
+    assert_eq!(esrt.len(), 2);
+    assert_eq!(esrt.fw_resource_count_max, 4);
+
+    let entries: Vec<_> = esrt.entries().collect();
+    assert_eq!(entries[0].fw_class, Guid(0x12345678, 0x9ABC, 0xDEF0, [1, 2, 3, 4, 5, 6, 7, 8]));
+    assert_eq!(entries[0].fw_type(), FirmwareType::SystemFirmware);
+    assert_eq!(entries[0].fw_version, 0x10);
+    assert_eq!(entries[0].lowest_supported_fw_version, 0x8);
+    assert!(entries[0].last_attempt_status().is_success());
+
+    assert_eq!(entries[1].fw_type(), FirmwareType::DeviceFirmware);
+    assert_eq!(entries[1].capsule_flags, 0x10000);
+    assert_eq!(entries[1].last_attempt_status(), LastAttemptStatus::Vendor(0x1001));
+    assert_eq!(entries[1].last_attempt_status().to_string(), "vendor error 0x1001");
+
+    let class = Guid(0x11111111, 0x2222, 0x3333, [0x44, 0x44, 0x55, 0x55, 0x55, 0x55, 0x55, 0x55]);
+    assert_eq!(esrt.find(&class).unwrap().last_attempt_version, 4);
+}
+
+#[test]
+fn esrt_malformed() {
+    assert!(Esrt::parse(&ESRT[..95]).is_err());
+
+    let mut esrt = ESRT;
+    esrt[8] = 2;
+    assert!(Esrt::parse(&esrt).is_err());
+
+    // more entries than the table's maximum
+    let mut esrt = ESRT;
+    esrt[0] = 5;
+    assert!(Esrt::parse(&esrt).is_err());
+}
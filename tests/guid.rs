@@ -0,0 +1,55 @@
+#[macro_use]
+extern crate uefi;
+
+use std::collections::HashSet;
+
+use uefi::{Guid, Status, EFI_GLOBAL_VARIABLE_GUID};
+
+const GLOBAL: Guid = guid!("8be4df61-93ca-11d2-aa0d-00e098032b8c");
+
+#[test]
+fn parse() {
+    assert_eq!(GLOBAL, EFI_GLOBAL_VARIABLE_GUID);
+    assert_eq!("8BE4DF61-93CA-11D2-AA0D-00E098032B8C".parse::<Guid>(), Ok(GLOBAL));
+    assert_eq!(GLOBAL.to_string().parse::<Guid>(), Ok(GLOBAL));
+}
+
+#[test]
+fn parse_invalid() {
+    for s in &["",
+               "8be4df61-93ca-11d2-aa0d-00e098032b8",
+               "8be4df61-93ca-11d2-aa0d-00e098032b8c0",
+               "8be4df61_93ca-11d2-aa0d-00e098032b8c",
+               "8be4df6193ca-11d2-aa0d-00e098032b8c-",
+               "8be4df61-93ca-11d2-aa0d-00e098032g8c",
+               "{8be4df61-93ca-11d2-aa0d-00e098032b8}"] {
+        assert_eq!(s.parse::<Guid>(), Err(Status::InvalidParameter), "{}", s);
+    }
+}
+
+#[test]
+fn bytes() {
+    let bytes = [0x61, 0xDF, 0xE4, 0x8B, 0xCA, 0x93, 0xD2, 0x11,
+                 0xAA, 0x0D, 0x00, 0xE0, 0x98, 0x03, 0x2B, 0x8C];
+    assert_eq!(Guid::from_bytes(bytes), GLOBAL);
+    assert_eq!(GLOBAL.to_bytes(), bytes);
+}
+
+#[test]
+fn ordering() {
+    let a = guid!("00000000-0000-0000-0000-000000000001");
+    let b = guid!("00000000-0000-0001-0000-000000000000");
+    assert!(a < b);
+
+    let set: HashSet<Guid> = [a, b, a].iter().cloned().collect();
+    assert_eq!(set.len(), 2);
+}
+
+#[test]
+fn random() {
+    let guid = Guid::from_random_bytes([0xFF; 16]);
+    assert_eq!(guid.to_string(), "FFFFFFFF-FFFF-4FFF-BFFF-FFFFFFFFFFFF");
+
+    let guid = Guid::from_random_bytes([0; 16]);
+    assert_eq!(guid.to_string(), "00000000-0000-4000-8000-000000000000");
+}
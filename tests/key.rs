@@ -0,0 +1,61 @@
+extern crate uefi;
+
+use uefi::protocol::{KeyData, KeyShiftState, KeyState, KeyToggleState, LEFT_CONTROL_PRESSED, NUM_LOCK_ACTIVE,
+                     RIGHT_CONTROL_PRESSED, SHIFT_STATE_VALID, TOGGLE_STATE_VALID};
+use uefi::{InputKey, Key};
+
+fn key(scan_code: u16, unicode_char: u16) -> Key {
+    InputKey { scan_code, unicode_char }.key()
+}
+
+#[test]
+fn scan_codes() {
+    assert_eq!(key(0x01, 0), Key::Up);
+    assert_eq!(key(0x0A, 0), Key::PageDown);
+    assert_eq!(key(0x0B, 0), Key::Function(1));
+    assert_eq!(key(0x16, 0), Key::Function(12));
+    assert_eq!(key(0x17, 0), Key::Escape);
+    assert_eq!(key(0x68, 0), Key::Function(13));
+    assert_eq!(key(0x73, 0), Key::Function(24));
+    assert_eq!(key(0x106, 0), Key::Eject);
+    assert_eq!(key(0x8001, 0), Key::Oem(0x8001));
+    assert_eq!(key(0x42, 0), Key::Unknown(InputKey { scan_code: 0x42, unicode_char: 0 }));
+}
+
+#[test]
+fn characters() {
+    assert_eq!(key(0, 'a' as u16), Key::Char('a'));
+    assert_eq!(key(0, 0x08), Key::Backspace);
+    assert_eq!(key(0, 0x09), Key::Tab);
+    assert_eq!(key(0, 0x0D), Key::Enter);
+    assert_eq!(key(0, 0x0A), Key::Enter);
+    assert_eq!(key(0, 0xD800), Key::Unknown(InputKey { scan_code: 0, unicode_char: 0xD800 }));
+    assert_eq!(key(0, 0), Key::Unknown(InputKey { scan_code: 0, unicode_char: 0 }));
+}
+
+fn key_data(unicode_char: char, shift_state: KeyShiftState) -> KeyData {
+    KeyData {
+        key: InputKey { scan_code: 0, unicode_char: unicode_char as u16 },
+        key_state: KeyState::new(shift_state, KeyToggleState::empty()),
+    }
+}
+
+#[test]
+fn key_data_matches() {
+    // without a valid shift state, any modifiers match
+    let any = key_data('c', KeyShiftState::empty());
+    assert!(any.matches(&key_data('c', SHIFT_STATE_VALID | LEFT_CONTROL_PRESSED)));
+    assert!(any.matches(&key_data('c', KeyShiftState::empty())));
+    assert!(!any.matches(&key_data('d', KeyShiftState::empty())));
+
+    let control = key_data('c', SHIFT_STATE_VALID | LEFT_CONTROL_PRESSED);
+    assert!(control.matches(&key_data('c', SHIFT_STATE_VALID | LEFT_CONTROL_PRESSED)));
+    assert!(!control.matches(&key_data('c', SHIFT_STATE_VALID | RIGHT_CONTROL_PRESSED)));
+    assert!(!control.matches(&key_data('c', SHIFT_STATE_VALID)));
+    assert!(!control.matches(&key_data('c', KeyShiftState::empty())));
+
+    // the lock keys don't count
+    let mut num_lock = key_data('c', SHIFT_STATE_VALID | LEFT_CONTROL_PRESSED);
+    num_lock.key_state = KeyState::new(num_lock.key_state.shift_state(), TOGGLE_STATE_VALID | NUM_LOCK_ACTIVE);
+    assert!(control.matches(&num_lock));
+}
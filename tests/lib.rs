@@ -26,8 +26,23 @@ fn handle_iterator() {
 
         assert_eq!(nhandles, ctr);
 
+        // the buffer didn't come from the firmware's pool, so don't let Handles free it
+        mem::forget(handles);
         unsafe {
             free(hptr as *mut libc::c_void);
         }
 }
 
+
+#[test]
+fn reset_data() {
+    let mut buf = [0xFFFFu16; 8];
+    let data = uefi::encode_reset_data("ok\u{1F600}", &[0xAA, 0xBB], &mut buf);
+    assert_eq!(data, &[0x6F, 0x00, 0x6B, 0x00, 0xFD, 0xFF, 0x00, 0x00, 0xAA, 0xBB]);
+
+    // Truncated to fit, keeping the terminator and extra data.
+    let data = uefi::encode_reset_data("reason", &[0xAA], &mut buf[..4]);
+    assert_eq!(data, &[0x72, 0x00, 0x65, 0x00, 0x00, 0x00, 0xAA]);
+
+    assert!(uefi::encode_reset_data("reason", &[0xAA; 4], &mut buf[..2]).is_empty());
+}
@@ -0,0 +1,69 @@
+#![cfg(feature = "log")]
+
+#[macro_use]
+extern crate log;
+extern crate uefi;
+
+use std::ptr;
+
+use log::LevelFilter;
+use uefi::logger::{self, Logger, RingBuffer};
+
+#[test]
+fn ring_buffer_wraps() {
+    let mut buf = [0u8; 8];
+    let mut ring = RingBuffer::new(&mut buf);
+    ring.push(b"abcde");
+    assert_eq!(ring.contents(), (&b"abcde"[..], &b""[..]));
+
+    ring.push(b"fghij");
+    assert_eq!(ring.len(), 8);
+    assert_eq!(ring.contents(), (&b"cdefgh"[..], &b"ij"[..]));
+
+    let mut out = [0u8; 16];
+    let n = ring.copy_to(&mut out);
+    assert_eq!(&out[..n], b"cdefghij");
+}
+
+#[test]
+fn parse_levels() {
+    assert_eq!(logger::parse_level(&[0]), Some(LevelFilter::Off));
+    assert_eq!(logger::parse_level(&[4]), Some(LevelFilter::Debug));
+    assert_eq!(logger::parse_level(&[9]), None);
+    assert_eq!(logger::parse_level(b"warn"), Some(LevelFilter::Warn));
+    assert_eq!(logger::parse_level(b"TRACE\0"), Some(LevelFilter::Trace));
+    assert_eq!(logger::parse_level(b"e\0r\0r\0o\0r\0\0\0"), Some(LevelFilter::Error));
+    assert_eq!(logger::parse_level(b"verbose"), None);
+}
+
+static mut LOG_BUFFER: [u8; 256] = [0; 256];
+
+#[test]
+fn logs_to_ring_buffer() {
+    let mut l = Logger::new();
+    l.set_level(LevelFilter::Debug);
+    l.set_ring_buffer(Some(unsafe { &mut *ptr::addr_of_mut!(LOG_BUFFER) }));
+    logger::init(l).unwrap();
+    assert!(logger::init(Logger::new()).is_err());
+
+    info!(target: "boot", "loading {}", "kernel");
+    trace!("not recorded");
+    error!(target: "disk", "unlock failed");
+
+    let mut out = [0u8; 256];
+    let n = logger::logger().unwrap().copy_ring_buffer(&mut out);
+    assert_eq!(std::str::from_utf8(&out[..n]).unwrap(),
+               "INFO  boot: loading kernel\nERROR disk: unlock failed\n");
+
+    // with boot services, the record is written under a raised TPL, which is then restored
+    #[cfg(feature = "mock")]
+    {
+        let firmware = uefi::mock::MockFirmware::new();
+        warn!(target: "net", "no link");
+        assert_eq!(firmware.tpl(), uefi::TPL::Application as usize);
+        assert!(firmware.output().contains("net: no link"));
+
+        let n = logger::logger().unwrap().copy_ring_buffer(&mut out);
+        assert!(std::str::from_utf8(&out[..n]).unwrap().ends_with("unlock failed\nWARN  net: no link\n"));
+    }
+}
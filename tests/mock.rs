@@ -0,0 +1,361 @@
+#![cfg(feature = "mock")]
+
+#[macro_use]
+extern crate uefi;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use std::{mem, ptr, thread};
+
+use uefi::mock::{MockFirmware, DESCRIPTOR_SIZE, FIRMWARE_REVISION, FIRMWARE_VENDOR};
+use uefi::protocol::{KeyData, KeyState, KeyToggleState, LoadedImageProtocol, SimpleTextInputExProtocol, LEFT_SHIFT_PRESSED,
+                     MAX_KEY_NOTIFICATIONS, SHIFT_STATE_VALID};
+use uefi::util::{CStr16, CString16};
+use uefi::variable::{self, LoadOption, LoadOptionType, LOAD_OPTION_ACTIVE};
+use uefi::{CVoid, LineEditor, ResetType, MemoryAttribute, MemoryType, Revision, MEMORY_RUNTIME, Status, SimpleTextOutput, SystemTable, TPL, EFI_GLOBAL_VARIABLE_GUID,
+           EFI_SYSTEM_RESOURCE_TABLE_GUID,
+           VARIABLE_BOOTSERVICE_ACCESS, VARIABLE_NON_VOLATILE, VARIABLE_RUNTIME_ACCESS};
+
+#[test]
+fn variables() {
+    let firmware = MockFirmware::new();
+    let rs = firmware.system_table().runtime_services();
+    let attributes = VARIABLE_NON_VOLATILE | VARIABLE_BOOTSERVICE_ACCESS | VARIABLE_RUNTIME_ACCESS;
+
+    let mut name_buf = [0u16; 16];
+    let name = CStr16::from_str_with_buf("Test", &mut name_buf).unwrap();
+    rs.set_variable(name, &EFI_GLOBAL_VARIABLE_GUID, attributes, b"hello").unwrap();
+
+    let mut data = [0u8; 2];
+    assert_eq!(rs.get_variable(name, &EFI_GLOBAL_VARIABLE_GUID, &mut data), Err(Status::BufferTooSmall));
+    assert_eq!(rs.get_variable_size(name, &EFI_GLOBAL_VARIABLE_GUID), Ok(5));
+    assert_eq!(firmware.variable("Test", &EFI_GLOBAL_VARIABLE_GUID), Some((attributes, b"hello".to_vec())));
+
+    variable::set_boot_next(3).unwrap();
+    assert_eq!(variable::boot_next(), Ok(Some(3)));
+
+    let mut next = [0u16; 16];
+    let mut vendor = EFI_GLOBAL_VARIABLE_GUID;
+    rs.get_next_variable_name(&mut next, &mut vendor).unwrap();
+    assert_eq!(&next[..5], name.as_slice_with_nul());
+    rs.get_next_variable_name(&mut next, &mut vendor).unwrap();
+    assert_eq!(CStr16::from_u16_with_nul(&next[..9]).unwrap(), "BootNext");
+    assert_eq!(rs.get_next_variable_name(&mut next, &mut vendor), Err(Status::NotFound));
+
+    rs.delete_variable(name, &EFI_GLOBAL_VARIABLE_GUID).unwrap();
+    assert_eq!(firmware.variable("Test", &EFI_GLOBAL_VARIABLE_GUID), None);
+
+    let mut order = [0u16; 8];
+    assert!(variable::read_order(LoadOptionType::Boot, &mut order).unwrap().is_empty());
+}
+
+#[test]
+fn add_load_option() {
+    let firmware = MockFirmware::new();
+    // Media/File Path node "\a", then End Entire Path
+    let file_path_list = [0x04, 0x04, 0x0A, 0x00, 0x5C, 0x00, 0x61, 0x00, 0x00, 0x00, 0x7F, 0xFF, 0x04, 0x00];
+    let option = LoadOption::new(LOAD_OPTION_ACTIVE, "Linux", &file_path_list, b"").unwrap();
+    let mut scratch = [0u8; 64];
+    let mut order = [0u16; 8];
+
+    assert_eq!(variable::add_load_option(LoadOptionType::Boot, &option, None, &mut scratch, &mut order), Ok(0));
+    assert_eq!(variable::read_order(LoadOptionType::Boot, &mut order).unwrap(), &[0]);
+
+    // the mock refuses to change a variable's attributes, so writing the order fails
+    firmware.set_variable("BootOrder", &EFI_GLOBAL_VARIABLE_GUID, VARIABLE_NON_VOLATILE, &[0, 0]);
+    assert_eq!(variable::add_load_option(LoadOptionType::Boot, &option, None, &mut scratch, &mut order),
+               Err(Status::InvalidParameter));
+    assert_eq!(firmware.variable("Boot0001", &EFI_GLOBAL_VARIABLE_GUID), None);
+    assert!(firmware.variable("Boot0000", &EFI_GLOBAL_VARIABLE_GUID).is_some());
+}
+
+#[test]
+fn os_indications() {
+    let firmware = MockFirmware::new();
+    let attributes = VARIABLE_NON_VOLATILE | VARIABLE_BOOTSERVICE_ACCESS | VARIABLE_RUNTIME_ACCESS;
+    let supported = variable::OS_INDICATIONS_BOOT_TO_FW_UI.bits();
+    firmware.set_variable("OsIndicationsSupported", &EFI_GLOBAL_VARIABLE_GUID, VARIABLE_BOOTSERVICE_ACCESS | VARIABLE_RUNTIME_ACCESS,
+                          &supported.to_le_bytes());
+
+    assert_eq!(variable::request_os_indication(variable::OS_INDICATIONS_START_OS_RECOVERY), Err(Status::Unsupported));
+
+    // a bit this crate doesn't know about is kept when another is requested
+    let unknown = 1u64 << 40;
+    firmware.set_variable("OsIndications", &EFI_GLOBAL_VARIABLE_GUID, attributes, &unknown.to_le_bytes());
+    assert!(variable::os_indications().unwrap().is_empty());
+    variable::request_os_indication(variable::OS_INDICATIONS_BOOT_TO_FW_UI).unwrap();
+    let expected = unknown | supported;
+    assert_eq!(firmware.variable("OsIndications", &EFI_GLOBAL_VARIABLE_GUID), Some((attributes, expected.to_le_bytes().to_vec())));
+}
+
+static F2_PRESSES: AtomicUsize = AtomicUsize::new(0);
+static SHIFT_F2_PRESSES: AtomicUsize = AtomicUsize::new(0);
+
+#[test]
+fn key_notifications() {
+    let firmware = MockFirmware::new();
+    let input = SimpleTextInputExProtocol::for_console().unwrap();
+
+    let f2 = KeyData { key: uefi::InputKey { scan_code: 0x0C, unicode_char: 0 }, key_state: KeyState::default() };
+    let shift_f2 = KeyData {
+        key: f2.key,
+        key_state: KeyState::new(SHIFT_STATE_VALID | LEFT_SHIFT_PRESSED, KeyToggleState::empty()),
+    };
+    let any = input.register_key_notify(f2, &|_| { F2_PRESSES.fetch_add(1, Ordering::SeqCst); }).unwrap();
+    let shifted = input.register_key_notify(shift_f2, &|_| { SHIFT_F2_PRESSES.fetch_add(1, Ordering::SeqCst); }).unwrap();
+    assert_eq!(firmware.tpl(), TPL::Application as usize);
+
+    // both registrations match, and each callback runs once
+    firmware.press_key_ex(shift_f2);
+    assert_eq!((F2_PRESSES.load(Ordering::SeqCst), SHIFT_F2_PRESSES.load(Ordering::SeqCst)), (1, 1));
+    firmware.press_key_ex(f2);
+    assert_eq!((F2_PRESSES.load(Ordering::SeqCst), SHIFT_F2_PRESSES.load(Ordering::SeqCst)), (2, 1));
+
+    input.unregister_key_notify(any).unwrap();
+    assert_eq!(input.unregister_key_notify(any), Err(Status::InvalidParameter));
+    firmware.press_key_ex(shift_f2);
+    assert_eq!((F2_PRESSES.load(Ordering::SeqCst), SHIFT_F2_PRESSES.load(Ordering::SeqCst)), (2, 2));
+    input.unregister_key_notify(shifted).unwrap();
+
+    // every slot can be used, and freed slots are reused
+    let handles: Vec<_> = (0..MAX_KEY_NOTIFICATIONS).map(|_| input.register_key_notify(f2, &|_| ()).unwrap()).collect();
+    assert_eq!(input.register_key_notify(f2, &|_| ()), Err(Status::OutOfResources));
+    for handle in handles {
+        input.unregister_key_notify(handle).unwrap();
+    }
+}
+
+#[test]
+fn console() {
+    let firmware = MockFirmware::new();
+    let st = firmware.system_table();
+    firmware.push_input("passwrd");
+    let left = uefi::InputKey { scan_code: 0x04, unicode_char: 0 };
+    firmware.push_keys(&[left, left]);
+    firmware.push_input("o\r");
+
+    let console = st.console();
+    console.write("Passphrase: ");
+    let mut editor = LineEditor::new();
+    editor.set_mask(Some('*'));
+    let mut buf = [0u8; 32];
+    assert_eq!(editor.read_line(&console, &mut buf), Ok("password"));
+    assert!(firmware.output().starts_with("Passphrase: *"));
+    assert!(!firmware.output().contains("pass"));
+
+    // the script is exhausted
+    assert_eq!(editor.read_line(&console, &mut buf), Err(Status::DeviceError));
+
+    let attribute = uefi::Attribute::new(uefi::ForegroundColor::Yellow, uefi::BackgroundColor::Blue);
+    assert_eq!(console.set_attribute(attribute), Status::Success);
+    assert_eq!(console.mode().attribute(), attribute);
+
+    println!("to {}", "stdout");
+    eprintln!("to stderr");
+    assert!(firmware.take_output().ends_with("to stdout\r\n"));
+    assert_eq!(firmware.error_output(), "to stderr\r\n");
+    assert_eq!(firmware.output(), "");
+}
+
+#[test]
+fn pool_and_protocols() {
+    let firmware = MockFirmware::new();
+    let bs = firmware.system_table().boot_services();
+
+    let p: *mut u8 = bs.allocate_pool(64).unwrap();
+    assert_eq!(firmware.pool_allocations(), 1);
+    bs.free_pool(p);
+    assert_eq!(firmware.pool_allocations(), 0);
+
+    let image: &LoadedImageProtocol = bs.handle_protocol(firmware.image_handle()).unwrap();
+    assert!(std::ptr::eq(image, uefi::protocol::get_current_image()));
+    assert_eq!(uefi::protocol::SerialIOProtocol::new().err(), Some(Status::NotFound));
+
+    let handle = firmware.create_handle();
+    firmware.install_protocol::<LoadedImageProtocol>(handle, image);
+    let handles = bs.locate_handle_by_protocol::<LoadedImageProtocol>().unwrap();
+    let found: Vec<String> = handles.into_iter().map(|h| format!("{:?}", h)).collect();
+    assert_eq!(found, vec![format!("{:?}", firmware.image_handle()), format!("{:?}", handle)]);
+    assert_eq!(firmware.pool_allocations(), 1);
+    drop(handles);
+    assert_eq!(firmware.pool_allocations(), 0);
+}
+
+#[test]
+fn reset() {
+    let firmware = MockFirmware::new();
+    assert_eq!(firmware.reset(), None);
+
+    // reset_system never returns, so it's called from a thread which is left parked
+    thread::spawn(|| uefi::get_system_table().runtime_services().reset_system(ResetType::Warm, Status::Aborted));
+    while firmware.reset().is_none() {
+        thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(firmware.reset(), Some((ResetType::Warm, Status::Aborted)));
+}
+
+fn reset_with_data(data: &'static [u8]) -> Vec<u8> {
+    let firmware = MockFirmware::new();
+    thread::spawn(move || {
+        uefi::get_system_table().runtime_services().reset_system_with_data(ResetType::Cold, Status::Success, data)
+    });
+    while firmware.reset().is_none() {
+        thread::sleep(Duration::from_millis(1));
+    }
+    firmware.reset_data()
+}
+
+/// A copy of `data` at an odd or even address, which lives for good.
+fn leak_at_offset(data: &[u8], offset: usize) -> &'static [u8] {
+    let units: &'static mut [u16] = Box::leak(vec![0u16; data.len() / 2 + 1].into_boxed_slice());
+    let bytes = unsafe { std::slice::from_raw_parts_mut(units.as_mut_ptr() as *mut u8, units.len() * 2) };
+    bytes[offset..offset + data.len()].copy_from_slice(data);
+    &bytes[offset..offset + data.len()]
+}
+
+#[test]
+fn reset_data() {
+    // a reason followed by binary data, which is passed on aligned or not
+    let data = [0x6F, 0x00, 0x6B, 0x00, 0x00, 0x00, 0xAA];
+    assert_eq!(reset_with_data(leak_at_offset(&data, 0)), data);
+    assert_eq!(reset_with_data(leak_at_offset(&data, 1)), data);
+
+    // the string isn't terminated
+    assert!(reset_with_data(leak_at_offset(&data[..3], 0)).is_empty());
+    assert!(reset_with_data(&[]).is_empty());
+}
+
+#[test]
+fn task_priority() {
+    let firmware = MockFirmware::new();
+    let bs = firmware.system_table().boot_services();
+    assert_eq!(firmware.tpl(), TPL::Application as usize);
+
+    let old_tpl = bs.raise_tpl(TPL::Notify);
+    assert_eq!(old_tpl, TPL::Application as usize);
+    assert_eq!(firmware.tpl(), TPL::Notify as usize);
+    bs.restore_tpl(old_tpl);
+    assert_eq!(firmware.tpl(), TPL::Application as usize);
+}
+
+#[test]
+fn memory_map() {
+    let firmware = MockFirmware::new();
+    let bs = firmware.system_table().boot_services();
+    assert_eq!(bs.get_memory_map_size(), Ok(2 * DESCRIPTOR_SIZE));
+
+    let mut buf = [0u64; 16];
+    let bytes = unsafe { std::slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut u8, 128) };
+    assert_eq!(bs.get_memory_map(&mut bytes[..DESCRIPTOR_SIZE]).err(), Some(Status::BufferTooSmall));
+    assert_eq!(bs.get_memory_map(&mut bytes[1..]).err(), Some(Status::InvalidParameter));
+
+    let mut map = bs.get_memory_map(bytes).unwrap();
+    assert_eq!(map.descriptor_size, DESCRIPTOR_SIZE);
+    assert_eq!(map.len(), 2);
+    let descriptors: Vec<_> = map.iter().map(|d| (d.type_, d.physical_start, d.attributes())).collect();
+    assert_eq!(descriptors, vec![(MemoryType::Conventional as u32, 0x10_0000, MemoryAttribute::from_bits_truncate(0xF)),
+                                 (MemoryType::RuntimeServicesData as u32, 0x8000_0000, MEMORY_RUNTIME | MemoryAttribute::from_bits_truncate(0xF))]);
+
+    for descriptor in map.iter_mut() {
+        descriptor.virtual_start = descriptor.physical_start + 0x1000;
+    }
+    assert_eq!(map.iter().map(|d| d.virtual_start).collect::<Vec<_>>(), vec![0x10_1000, 0x8000_1000]);
+    // the padding after each descriptor is left alone
+    assert!(map.as_mut_bytes()[DESCRIPTOR_SIZE - 8..DESCRIPTOR_SIZE].iter().all(|&b| b == 0xFF));
+}
+
+#[test]
+fn exit_boot_services() {
+    let firmware = MockFirmware::new();
+    let bs = firmware.system_table().boot_services();
+
+    let mut buf = [0u64; 16];
+    let bytes = unsafe { std::slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut u8, 128) };
+    let key = bs.get_memory_map(bytes).unwrap().key;
+    bs.allocate_pages(uefi::AllocateType::AnyPages, 0, 1).unwrap();
+    assert_eq!(bs.exit_boot_services(firmware.image_handle(), key), Err(Status::InvalidParameter));
+
+    let map = bs.get_memory_map(bytes).unwrap();
+    assert_eq!(map.len(), 2);
+    bs.exit_boot_services(firmware.image_handle(), map.key).unwrap();
+    assert!(uefi::boot_services_exited());
+
+    drop(firmware);
+    assert!(!uefi::boot_services_exited());
+}
+
+#[test]
+fn table_headers() {
+    let firmware = MockFirmware::new();
+    let st = firmware.system_table();
+    assert_eq!(st.revision(), Revision::new(2, 7, 0));
+    assert_eq!(st.boot_services().header().revision(), Revision::new(2, 7, 0));
+    assert_eq!(st.firmware_revision(), FIRMWARE_REVISION);
+
+    let mut buf = [0u8; 32];
+    assert_eq!(st.vendor(&mut buf), Ok(FIRMWARE_VENDOR));
+    assert_eq!(st.vendor(&mut buf[..4]), Err(Status::BufferTooSmall));
+
+    // a copy of the system table, with a byte of the firmware revision or signature changed
+    let mut copy: SystemTable = unsafe { ptr::read(st) };
+    let bytes = &mut copy as *mut SystemTable as *mut u8;
+    let firmware_revision = 24 + mem::size_of::<usize>();
+    unsafe { *bytes.add(firmware_revision) ^= 1 };
+    assert_eq!(uefi::set_system_table(&copy).err(), Some(Status::CrcError));
+    unsafe { *bytes ^= 1 };
+    assert_eq!(uefi::set_system_table(&copy).err(), Some(Status::InvalidParameter));
+
+    assert!(uefi::set_system_table(st).is_ok());
+}
+
+#[test]
+fn esrt() {
+    let firmware = MockFirmware::new();
+    assert_eq!(uefi::esrt().err(), Some(Status::NotFound));
+
+    // count 1, count max 1, version 1, then an entry with fw_version 7
+    let mut table = [0u8; 16 + 40];
+    table[0] = 1;
+    table[4] = 1;
+    table[8] = 1;
+    table[16 + 20] = 7;
+    firmware.install_configuration_table(&EFI_SYSTEM_RESOURCE_TABLE_GUID, table.as_ptr() as *const CVoid);
+    let esrt = uefi::esrt().unwrap();
+    assert_eq!(esrt.len(), 1);
+    assert_eq!(esrt.entries().next().unwrap().fw_version, 7);
+
+    // a count past the table's maximum is rejected before the entries are touched
+    table[0..4].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert_eq!(uefi::esrt().err(), Some(Status::InvalidParameter));
+
+    firmware.install_configuration_table(&EFI_SYSTEM_RESOURCE_TABLE_GUID, ptr::null());
+    assert_eq!(uefi::esrt().err(), Some(Status::NotFound));
+}
+
+#[test]
+fn owned_strings() {
+    let firmware = MockFirmware::new();
+
+    let s: CString16 = "Grüße".parse().unwrap();
+    assert_eq!(s, "Grüße");
+    assert_eq!(s.as_slice_with_nul().len(), 6);
+    assert_eq!(firmware.pool_allocations(), 1);
+    drop(s);
+    assert_eq!(firmware.pool_allocations(), 0);
+
+    assert_eq!("😀".parse::<CString16>(), Err(Status::Unsupported));
+    assert_eq!(firmware.pool_allocations(), 0);
+
+    let s: CString16 = "text".parse().unwrap();
+    let raw = s.into_raw();
+    let s = unsafe { CString16::from_pool(raw) }.unwrap();
+    assert_eq!(s.to_string(), "text");
+    drop(s);
+
+    // an invalid string from the firmware is freed straight away
+    let bs = firmware.system_table().boot_services();
+    let raw: *mut u16 = bs.allocate_pool(4).unwrap();
+    unsafe { raw.copy_from_nonoverlapping([0xD800, 0].as_ptr(), 2) };
+    assert_eq!(unsafe { CString16::from_pool(raw) }, Err(Status::InvalidParameter));
+    assert_eq!(firmware.pool_allocations(), 0);
+}
@@ -0,0 +1,26 @@
+#![cfg(feature = "mock")]
+
+//! Calls into the mock firmware's services after it has been dropped. This is its own test binary
+//! so that no other test installs a new mock firmware in the meantime.
+
+extern crate uefi;
+
+use std::ptr;
+
+use uefi::mock::MockFirmware;
+use uefi::{BootServices, RuntimeServices, Status, TPL};
+
+#[test]
+fn services_after_drop() {
+    let firmware = MockFirmware::new();
+    // copies of the tables, which are freed along with the firmware
+    let bs: BootServices = unsafe { ptr::read(firmware.system_table().boot_services()) };
+    let rs: RuntimeServices = unsafe { ptr::read(firmware.system_table().runtime_services()) };
+    drop(firmware);
+
+    assert_eq!(rs.get_time().err(), Some(Status::NotReady));
+    assert_eq!(bs.raise_tpl(TPL::Notify), TPL::Application as usize);
+    bs.restore_tpl(TPL::Application as usize);
+    bs.stall(10);
+    bs.free_pool(8 as *const u8);
+}
@@ -0,0 +1,27 @@
+#[macro_use]
+extern crate uefi;
+
+use std::sync::Mutex;
+
+static OUTPUT: Mutex<String> = Mutex::new(String::new());
+
+fn sink(s: &str) {
+    OUTPUT.lock().unwrap().push_str(s);
+}
+
+#[test]
+fn print_without_console_goes_to_sink() {
+    // no system table has been set, so the console can't be used
+    print!("no sink");
+
+    uefi::set_print_sink(Some(sink));
+    print!("{}-{}", 1, 2);
+    println!();
+    println!("answer: {}", 42);
+    eprint!("err ");
+    eprintln!("{:?}", "quoted");
+    uefi::set_print_sink(None);
+    println!("dropped");
+
+    assert_eq!(*OUTPUT.lock().unwrap(), "1-2\nanswer: 42\nerr \"quoted\"\n");
+}
@@ -0,0 +1,321 @@
+extern crate uefi;
+
+use std::cell::{Cell, RefCell};
+
+use uefi::{Attribute, BackgroundColor, ForegroundColor, History, InputKey, LineEditor, SimpleTextInput, SimpleTextOutput,
+           SimpleTextOutputMode, Status};
+
+const COLUMNS: usize = 20;
+const ROWS: usize = 5;
+
+/// A console which replays keys and keeps a screen of text.
+struct FakeConsole {
+    keys: RefCell<Vec<InputKey>>,
+    screen: RefCell<[[char; COLUMNS]; ROWS]>,
+    column: Cell<usize>,
+    row: Cell<usize>,
+    attribute: Cell<usize>,
+}
+
+impl FakeConsole {
+    fn new(keys: &[InputKey]) -> FakeConsole {
+        FakeConsole {
+            keys: RefCell::new(keys.iter().rev().cloned().collect()),
+            screen: RefCell::new([[' '; COLUMNS]; ROWS]),
+            column: Cell::new(0),
+            row: Cell::new(0),
+            attribute: Cell::new(0x07),
+        }
+    }
+
+    fn line(&self, row: usize) -> String {
+        self.screen.borrow()[row].iter().collect::<String>().trim_end().to_string()
+    }
+
+    fn newline(&self) {
+        if self.row.get() + 1 == ROWS {
+            let mut screen = self.screen.borrow_mut();
+            for row in 1..ROWS {
+                screen[row - 1] = screen[row];
+            }
+            screen[ROWS - 1] = [' '; COLUMNS];
+        } else {
+            self.row.set(self.row.get() + 1);
+        }
+    }
+}
+
+impl SimpleTextOutput for FakeConsole {
+    fn write_raw(&self, str: *const u16) -> Status {
+        let mut i = 0;
+        loop {
+            let c = unsafe { *str.offset(i) };
+            i += 1;
+            match c {
+                0 => return Status::Success,
+                0x0D => self.column.set(0),
+                0x0A => self.newline(),
+                c => {
+                    self.screen.borrow_mut()[self.row.get()][self.column.get()] = std::char::from_u32(c as u32).unwrap();
+                    self.column.set(self.column.get() + 1);
+                    if self.column.get() == COLUMNS {
+                        self.column.set(0);
+                        self.newline();
+                    }
+                },
+            }
+        }
+    }
+
+    fn test_raw(&self, _: *const u16) -> Status {
+        Status::Success
+    }
+
+    fn set_attribute(&self, attribute: Attribute) -> Status {
+        self.attribute.set(attribute.to_efi_attribute());
+        Status::Success
+    }
+
+    fn clear_screen(&self) -> Status {
+        Status::Success
+    }
+
+    fn query_mode(&self, _: usize) -> Result<(usize, usize), Status> {
+        Ok((COLUMNS, ROWS))
+    }
+
+    fn set_mode(&self, _: usize) -> Status {
+        Status::Success
+    }
+
+    fn set_cursor_position(&self, column: usize, row: usize) -> Status {
+        if column >= COLUMNS || row >= ROWS {
+            return Status::Unsupported;
+        }
+        self.column.set(column);
+        self.row.set(row);
+        Status::Success
+    }
+
+    fn enable_cursor(&self, _: bool) -> Status {
+        Status::Success
+    }
+
+    fn mode(&self) -> SimpleTextOutputMode {
+        let mut mode = SimpleTextOutputMode::default();
+        mode.max_mode = 1;
+        mode.cursor_column = self.column.get() as i32;
+        mode.cursor_row = self.row.get() as i32;
+        mode
+    }
+}
+
+impl SimpleTextInput for FakeConsole {
+    fn read_key_async(&self) -> Result<InputKey, Status> {
+        self.keys.borrow_mut().pop().ok_or(Status::NotReady)
+    }
+
+    fn read_key(&self) -> Result<InputKey, Status> {
+        self.keys.borrow_mut().pop().ok_or(Status::DeviceError)
+    }
+}
+
+fn chars(s: &str) -> Vec<InputKey> {
+    s.chars().map(|c| InputKey { scan_code: 0, unicode_char: c as u16 }).collect()
+}
+
+fn scan(scan_code: u16) -> InputKey {
+    InputKey { scan_code, unicode_char: 0 }
+}
+
+const ENTER: InputKey = InputKey { scan_code: 0, unicode_char: 0x0D };
+const BACKSPACE: InputKey = InputKey { scan_code: 0, unicode_char: 0x08 };
+const UP: u16 = 0x01;
+const DOWN: u16 = 0x02;
+const LEFT: u16 = 0x04;
+const HOME: u16 = 0x05;
+const END: u16 = 0x06;
+const DELETE: u16 = 0x08;
+const ESCAPE: u16 = 0x17;
+
+#[test]
+fn editing() {
+    let mut keys = chars("helo wrld");
+    keys.extend(&[scan(LEFT), scan(LEFT), scan(LEFT)]);
+    keys.extend(chars("o"));
+    keys.extend(&[scan(HOME), scan(DELETE)]);
+    keys.extend(chars("H"));
+    keys.extend(&[scan(END), BACKSPACE]);
+    keys.extend(chars("d!"));
+    keys.push(ENTER);
+    let console = FakeConsole::new(&keys);
+
+    let mut buf = [0u8; 64];
+    let line = LineEditor::new().read_line(&console, &mut buf).unwrap();
+    assert_eq!(line, "Helo world!");
+    assert_eq!(console.line(0), "Helo world!");
+    assert_eq!((console.column.get(), console.row.get()), (0, 1));
+}
+
+#[test]
+fn buffer_full() {
+    let mut keys = chars("abcdef");
+    keys.push(ENTER);
+    let console = FakeConsole::new(&keys);
+
+    let mut buf = [0u8; 4];
+    assert_eq!(LineEditor::new().read_line(&console, &mut buf).unwrap(), "abcd");
+}
+
+#[test]
+fn wrap_and_scroll() {
+    let console = FakeConsole::new(&[]);
+    console.set_cursor_position(15, 4);
+    let mut keys = chars("0123456789abc");
+    keys.push(BACKSPACE);
+    keys.push(ENTER);
+    *console.keys.borrow_mut() = keys.into_iter().rev().collect();
+
+    let mut buf = [0u8; 64];
+    assert_eq!(LineEditor::new().read_line(&console, &mut buf).unwrap(), "0123456789ab");
+    assert_eq!(console.line(2), "               01234");
+    assert_eq!(console.line(3), "56789ab");
+}
+
+#[test]
+fn masked() {
+    let mut keys = chars("secret");
+    keys.push(ENTER);
+    let console = FakeConsole::new(&keys);
+
+    let mut history_buf = [0u8; 32];
+    let mut editor = LineEditor::with_history(History::new(&mut history_buf));
+    editor.set_mask(Some('*'));
+    let mut buf = [0u8; 64];
+    assert_eq!(editor.read_line(&console, &mut buf).unwrap(), "secret");
+    assert_eq!(console.line(0), "******");
+    assert!(editor.history().unwrap().is_empty());
+}
+
+#[test]
+fn escape() {
+    let mut keys = chars("abc");
+    keys.push(scan(ESCAPE));
+    let console = FakeConsole::new(&keys);
+
+    let mut buf = [0u8; 64];
+    assert_eq!(LineEditor::new().read_line(&console, &mut buf), Err(Status::Aborted));
+}
+
+#[test]
+fn history_recall() {
+    let mut history_buf = [0u8; 32];
+    let mut editor = LineEditor::with_history(History::new(&mut history_buf));
+    let mut buf = [0u8; 64];
+
+    for line in &["first", "second"] {
+        let mut keys = chars(line);
+        keys.push(ENTER);
+        let console = FakeConsole::new(&keys);
+        editor.read_line(&console, &mut buf).unwrap();
+    }
+
+    let mut keys = chars("third");
+    keys.extend(&[scan(UP), scan(UP), scan(UP), scan(DOWN)]);
+    keys.extend(chars("!"));
+    keys.push(ENTER);
+    let console = FakeConsole::new(&keys);
+    assert_eq!(editor.read_line(&console, &mut buf).unwrap(), "second!");
+    assert_eq!(console.line(0), "second!");
+}
+
+#[test]
+fn history_eviction() {
+    let mut buf = [0u8; 16];
+    let mut history = History::new(&mut buf);
+    history.push("one");
+    history.push("two");
+    history.push("two");
+    history.push("");
+    assert_eq!(history.len(), 2);
+
+    history.push("three");
+    history.push("four");
+    assert_eq!(history.len(), 3);
+    assert_eq!(history.get(0), Some("four"));
+    assert_eq!(history.get(2), Some("two"));
+    assert_eq!(history.get(3), None);
+
+    history.push("this line is too long");
+    assert_eq!(history.get(0), Some("four"));
+}
+
+#[test]
+fn attributes() {
+    let console = FakeConsole::new(&[]);
+    let attribute = Attribute::new(ForegroundColor::Yellow, BackgroundColor::Blue);
+    console.set_attribute(attribute);
+    assert_eq!(console.attribute.get(), 0x1E);
+    assert_eq!(Attribute::from_efi_attribute(0x1E), attribute);
+
+    let attribute = attribute.with_foreground(ForegroundColor::White);
+    console.set_attribute(attribute);
+    assert_eq!(console.attribute.get(), 0x1F);
+}
+
+/// An output device which only implements the required methods.
+struct WriteOnly;
+
+impl SimpleTextOutput for WriteOnly {
+    fn write_raw(&self, _: *const u16) -> Status {
+        Status::Success
+    }
+
+    fn set_attribute(&self, _: Attribute) -> Status {
+        Status::Success
+    }
+}
+
+#[test]
+fn unsupported_output() {
+    let output = WriteOnly;
+    assert_eq!(output.clear_screen(), Status::Unsupported);
+    assert_eq!(output.set_cursor_position(1, 1), Status::Unsupported);
+    assert_eq!(output.modes().count(), 0);
+    assert_eq!(output.size(), Err(Status::Unsupported));
+    assert_eq!(output.set_largest_mode(), Err(Status::Unsupported));
+    assert_eq!(output.test_raw([0xFFFD, 0].as_ptr()), Status::Success);
+}
+
+/// A console which claims to be zero columns wide.
+struct NoColumns;
+
+impl SimpleTextOutput for NoColumns {
+    fn write_raw(&self, _: *const u16) -> Status {
+        Status::Success
+    }
+
+    fn set_attribute(&self, _: Attribute) -> Status {
+        Status::Success
+    }
+
+    fn query_mode(&self, _: usize) -> Result<(usize, usize), Status> {
+        Ok((0, ROWS))
+    }
+}
+
+impl SimpleTextInput for NoColumns {
+    fn read_key_async(&self) -> Result<InputKey, Status> {
+        Ok(ENTER)
+    }
+
+    fn read_key(&self) -> Result<InputKey, Status> {
+        Ok(ENTER)
+    }
+}
+
+#[test]
+fn no_columns() {
+    let mut buf = [0u8; 8];
+    assert_eq!(LineEditor::new().read_line(&NoColumns, &mut buf), Err(Status::Unsupported));
+}
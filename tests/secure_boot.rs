@@ -0,0 +1,66 @@
+extern crate uefi;
+
+use uefi::variable::{SignatureDatabase, SignatureType, SHIM_LOCK_GUID};
+
+fn signature_list(type_guid: &[u8; 16], signature_size: u32, signatures: &[&[u8]]) -> Vec<u8> {
+    let list_size = 28 + signature_size * signatures.len() as u32;
+    let mut list = Vec::new();
+    list.extend_from_slice(type_guid);
+    list.extend_from_slice(&list_size.to_le_bytes());
+    list.extend_from_slice(&0u32.to_le_bytes());
+    list.extend_from_slice(&signature_size.to_le_bytes());
+    for signature in signatures {
+        // Owner: SHIM_LOCK_GUID
+        list.extend_from_slice(&[0x50, 0xAB, 0x5D, 0x60, 0x46, 0xE0, 0x00, 0x43, 0xAB, 0xB6, 0x3D, 0xD8, 0x10, 0xDD, 0x8B, 0x23]);
+        list.extend_from_slice(signature);
+    }
+    list
+}
+
+const SHA256_GUID: [u8; 16] = [0x26, 0x16, 0xC4, 0xC1, 0x4C, 0x50, 0x92, 0x40, 0xAC, 0xA9, 0x41, 0xF9, 0x36, 0x93, 0x43, 0x28];
+const X509_GUID: [u8; 16] = [0xA1, 0x59, 0xC0, 0xA5, 0xE4, 0x94, 0xA7, 0x4A, 0x87, 0xB5, 0xAB, 0x15, 0x5C, 0x2B, 0xF0, 0x72];
+
+#[test]
+fn signature_database_parse() {
+    let hash_a = [0xAAu8; 32];
+    let hash_b = [0xBBu8; 32];
+    let cert = [0x30u8, 0x82, 0x00, 0x01, 0x00];
+
+    let mut db = signature_list(&SHA256_GUID, 48, &[&hash_a, &hash_b]);
+    db.extend(signature_list(&X509_GUID, 21, &[&cert]));
+
+    let db = SignatureDatabase::parse(&db).unwrap();
+    let lists: Vec<_> = db.lists().collect();
+    assert_eq!(lists.len(), 2);
+
+    assert_eq!(lists[0].signature_type, SignatureType::Sha256);
+    assert_eq!(lists[0].len(), 2);
+    let signatures: Vec<_> = lists[0].signatures().collect();
+    assert_eq!(signatures[0].owner, SHIM_LOCK_GUID);
+    assert_eq!(signatures[1].data, &hash_b[..]);
+
+    assert_eq!(lists[1].signature_type, SignatureType::X509);
+    assert_eq!(lists[1].signatures().next().unwrap().data, &cert[..]);
+
+    assert!(db.contains(SignatureType::Sha256, &hash_a));
+    assert!(!db.contains(SignatureType::Sha256, &cert));
+}
+
+#[test]
+fn signature_database_malformed() {
+    let hash = [0u8; 32];
+    let mut db = signature_list(&SHA256_GUID, 48, &[&hash]);
+    assert!(SignatureDatabase::parse(&db[..db.len() - 1]).is_err());
+
+    // A list size which doesn't divide into whole signatures.
+    db[16] -= 1;
+    db.pop();
+    assert!(SignatureDatabase::parse(&db).is_err());
+
+    assert!(SignatureDatabase::parse(&[]).unwrap().lists().next().is_none());
+
+    // A header size which overflows when added to the fixed header on 32-bit targets.
+    let mut db = signature_list(&SHA256_GUID, 48, &[&hash]);
+    db[20..24].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(SignatureDatabase::parse(&db).is_err());
+}
@@ -0,0 +1,41 @@
+extern crate uefi;
+
+use uefi::Status;
+
+const ERROR: usize = !(usize::MAX >> 1);
+
+#[test]
+fn classification() {
+    assert!(Status::Success.is_success());
+    assert!(Status::NotFound.is_error());
+    assert!(!Status::NotFound.is_warning());
+    assert!(Status::WarnUnknownGlyph.is_warning());
+    assert_eq!(Status::NotFound, Status(14 | ERROR));
+    assert_eq!(Status::HttpError, Status(35 | ERROR));
+
+    let oem = Status(ERROR | (ERROR >> 1) | 5);
+    assert!(oem.is_error() && oem.is_oem());
+}
+
+#[test]
+fn results() {
+    assert_eq!(Status::Success.to_result(), Ok(()));
+    assert_eq!(Status::WarnStaleData.to_result(), Err(Status::WarnStaleData));
+    assert_eq!(Status::Success.to_result_with(3), Ok(3));
+
+    let r: uefi::Result<u8> = Err(Status::Aborted);
+    match r {
+        Err(Status::Aborted) => (),
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn formatting() {
+    assert_eq!(format!("{}", Status::BufferTooSmall), "buffer too small");
+    assert_eq!(format!("{}", Status::WarnResetRequired), "warning: reset required");
+    assert_eq!(format!("{:?}", Status::CompromisedData), "CompromisedData");
+    assert_eq!(format!("{}", Status(ERROR | 29)), "unknown error 0x1d");
+    assert_eq!(format!("{}", Status(ERROR | (ERROR >> 1) | 2)), "OEM error 0x2");
+    assert_eq!(format!("{:?}", Status(100)), "Status(0x64)");
+}
@@ -0,0 +1,24 @@
+extern crate uefi;
+
+use uefi::{crc32, Revision};
+
+#[test]
+fn revision() {
+    let revision = Revision((2 << 16) | 31);
+    assert_eq!((revision.major(), revision.minor(), revision.patch()), (2, 3, 1));
+    assert_eq!(revision, Revision::new(2, 3, 1));
+    assert_eq!(revision.to_string(), "2.3.1");
+    assert_eq!(Revision::new(2, 7, 0).to_string(), "2.7");
+    assert_eq!(Revision::new(2, 10, 0).to_string(), "2.10");
+
+    assert!(Revision::new(1, 10, 0) < Revision::new(2, 0, 0));
+    assert!(Revision::new(2, 7, 0) < Revision::new(2, 10, 0));
+    assert!(revision >= Revision::new(2, 0, 0));
+}
+
+#[test]
+fn crc32_known_answers() {
+    assert_eq!(crc32(b"123456789"), 0xCBF43926);
+    assert_eq!(crc32(b""), 0);
+    assert_eq!(crc32(b"The quick brown fox jumps over the lazy dog"), 0x414FA339);
+}
@@ -0,0 +1,65 @@
+extern crate uefi;
+
+use std::time::Duration;
+
+use uefi::{Time, TIME_ADJUST_DAYLIGHT, TIME_IN_DAYLIGHT};
+
+#[test]
+fn unix_round_trip() {
+    let time = Time::from_unix(0, 0, None).unwrap();
+    assert_eq!((time.year, time.month, time.day, time.hour), (1970, 1, 1, 0));
+    assert_eq!(time.timezone(), None);
+
+    // 2017-11-02T13:45:30.5Z
+    let time = Time::from_unix(1509630330, 500_000_000, Some(60)).unwrap();
+    assert_eq!((time.year, time.month, time.day, time.hour, time.minute, time.second), (2017, 11, 2, 14, 45, 30));
+    assert_eq!(time.to_unix(), (1509630330, 500_000_000));
+
+    let time = Time::from_unix(-2208988800, 0, None).unwrap();
+    assert_eq!((time.year, time.month, time.day), (1900, 1, 1));
+    assert!(Time::from_unix(-2208988801, 0, None).is_err());
+}
+
+#[test]
+fn daylight_offset() {
+    let time = Time::new(2017, 7, 1, 12, 0, 0).unwrap()
+        .with_timezone(Some(60), TIME_ADJUST_DAYLIGHT | TIME_IN_DAYLIGHT).unwrap();
+    assert_eq!(time.utc_offset(), Some(120));
+    assert_eq!(time.to_unix().0, Time::parse_rfc3339("2017-07-01T10:00:00Z").unwrap().to_unix().0);
+    assert_eq!(time.rfc3339().to_string(), "2017-07-01T12:00:00+02:00");
+}
+
+#[test]
+fn rfc3339() {
+    let time = Time::parse_rfc3339("2017-11-02T13:45:30.5+01:00").unwrap();
+    assert_eq!(time.nanosecond, 500_000_000);
+    assert_eq!(time.timezone(), Some(60));
+    assert_eq!(time.rfc3339().to_string(), "2017-11-02T13:45:30.5+01:00");
+
+    let time: Time = "1999-12-31t23:59:59.000000001-00:00".parse().unwrap();
+    assert_eq!(time.timezone(), None);
+    assert_eq!(time.rfc3339().to_string(), "1999-12-31T23:59:59.000000001-00:00");
+
+    assert_eq!(Time::parse_rfc3339("2000-01-01 00:00:00Z").unwrap().rfc3339().to_string(), "2000-01-01T00:00:00Z");
+    assert!(Time::parse_rfc3339("2016-12-31T23:59:60Z").is_err());
+    assert!(Time::parse_rfc3339("2017-02-29T00:00:00Z").is_err());
+    assert!(Time::parse_rfc3339("2017-01-01T00:00:00").is_err());
+    assert!(Time::parse_rfc3339("2017-01-01T00:00:00Zjunk").is_err());
+}
+
+#[test]
+fn ordering_and_arithmetic() {
+    let utc = Time::parse_rfc3339("2017-11-02T12:45:30Z").unwrap();
+    let cet = Time::parse_rfc3339("2017-11-02T13:45:30+01:00").unwrap();
+    assert_eq!(utc, cet);
+
+    let later = cet + Duration::new(86400, 250_000_000);
+    assert!(later > utc);
+    assert_eq!(later.rfc3339().to_string(), "2017-11-03T13:45:30.25+01:00");
+    assert_eq!(later.duration_since(&utc), Some(Duration::new(86400, 250_000_000)));
+    assert_eq!(utc.duration_since(&later), None);
+    assert_eq!(later - Duration::new(86400, 250_000_000), utc);
+
+    let max = Time::parse_rfc3339("9999-12-31T23:59:59Z").unwrap();
+    assert!(max.checked_add(Duration::from_secs(1)).is_none());
+}
@@ -0,0 +1,93 @@
+extern crate uefi;
+
+use uefi::Status;
+use uefi::util::CStr16;
+#[cfg(feature = "mock")]
+use uefi::mock::MockFirmware;
+#[cfg(feature = "mock")]
+use uefi::util::CString16;
+#[cfg(feature = "mock")]
+use uefi::{EFI_GLOBAL_VARIABLE_GUID, VARIABLE_BOOTSERVICE_ACCESS, VARIABLE_NON_VOLATILE};
+
+#[test]
+fn borrowed() {
+    let units: Vec<u16> = "Grüße, 世界\0".encode_utf16().collect();
+    let s = CStr16::from_u16_with_nul(&units).unwrap();
+    assert_eq!(s.len(), 9);
+    assert_eq!(s.as_slice(), &units[..9]);
+    assert_eq!(s.to_string(), "Grüße, 世界");
+    assert_eq!(format!("{:?}", s), "\"Grüße, 世界\"");
+    assert_eq!(s, "Grüße, 世界");
+
+    let mut buf = [0u8; 32];
+    assert_eq!(s.to_str(&mut buf), Ok("Grüße, 世界"));
+    assert_eq!(s.to_str(&mut buf[..4]), Err(Status::BufferTooSmall));
+
+    assert!(CStr16::from_u16_with_nul(&[0]).unwrap().is_empty());
+    assert_eq!(unsafe { CStr16::from_ptr(units.as_ptr()) }, Ok(s));
+}
+
+#[test]
+fn invalid() {
+    assert_eq!(CStr16::from_u16_with_nul(&[]), Err(Status::InvalidParameter));
+    assert_eq!(CStr16::from_u16_with_nul(&[0x41]), Err(Status::InvalidParameter));
+    assert_eq!(CStr16::from_u16_with_nul(&[0x41, 0, 0x42, 0]), Err(Status::InvalidParameter));
+    assert_eq!(CStr16::from_u16_with_nul(&[0xD83D, 0xDE00, 0]), Err(Status::InvalidParameter));
+}
+
+#[test]
+fn from_str() {
+    let mut buf = [0u16; 8];
+    assert_eq!(CStr16::from_str_with_buf("Boot0001", &mut buf), Err(Status::BufferTooSmall));
+    assert_eq!(CStr16::from_str_with_buf("Boot\0", &mut buf), Err(Status::InvalidParameter));
+    assert_eq!(CStr16::from_str_with_buf("😀", &mut buf), Err(Status::Unsupported));
+
+    let s = CStr16::from_str_with_buf("Ünïcode", &mut buf).unwrap();
+    assert_eq!(s.as_slice_with_nul(), &[0xDC, 0x6E, 0xEF, 0x63, 0x6F, 0x64, 0x65, 0][..]);
+}
+
+#[cfg(feature = "mock")]
+#[test]
+fn owned() {
+    let firmware = MockFirmware::new();
+    let rs = firmware.system_table().runtime_services();
+
+    let name: CString16 = "Grüße".parse().unwrap();
+    assert_eq!(firmware.pool_allocations(), 1);
+    assert_eq!(name, "Grüße");
+    assert_eq!("😀".parse::<CString16>(), Err(Status::Unsupported));
+    assert_eq!("a\0b".parse::<CString16>(), Err(Status::InvalidParameter));
+
+    // the name is a CString16, and so is the value
+    let value: CString16 = "Welt".parse().unwrap();
+    let bytes: Vec<u8> = value.as_slice_with_nul().iter().flat_map(|unit| unit.to_le_bytes()).collect();
+    rs.set_variable(&name, &EFI_GLOBAL_VARIABLE_GUID, VARIABLE_NON_VOLATILE | VARIABLE_BOOTSERVICE_ACCESS, &bytes)
+        .unwrap();
+
+    let mut data = [0u8; 16];
+    let (size, _) = rs.get_variable(&name, &EFI_GLOBAL_VARIABLE_GUID, &mut data).unwrap();
+    let units: Vec<u16> = data[..size].chunks(2).map(|unit| u16::from_le_bytes([unit[0], unit[1]])).collect();
+    assert_eq!(CStr16::from_u16_with_nul(&units), Ok(&*value));
+
+    drop(name);
+    drop(value);
+    assert_eq!(firmware.pool_allocations(), 0);
+}
+
+#[cfg(feature = "mock")]
+#[test]
+#[allow(deprecated)]
+fn deprecated() {
+    use uefi::util::{str_to_utf16_ptr, utf16_ptr_to_str};
+
+    let firmware = MockFirmware::new();
+    let bs = firmware.system_table().boot_services();
+
+    let s = str_to_utf16_ptr("Grüße").unwrap();
+    assert_eq!(unsafe { CStr16::from_ptr(s) }, Ok(CStr16::from_u16_with_nul(&[0x47, 0x72, 0xFC, 0xDF, 0x65, 0]).unwrap()));
+    assert_eq!(utf16_ptr_to_str(s), Ok("Grüße"));
+
+    bs.free_pool(s);
+    // the &str is never freed
+    assert_eq!(firmware.pool_allocations(), 1);
+}
@@ -0,0 +1,14 @@
+extern crate uefi;
+
+use uefi::util::format_truncated;
+
+#[test]
+fn truncated_formatting() {
+    let mut buf = [0u8; 16];
+    assert_eq!(format_truncated(&mut buf, format_args!("panicked at {}:{}", "a.rs", 7)), "panicked at a.rs");
+    assert_eq!(format_truncated(&mut buf, format_args!("{}", "short")), "short");
+
+    // a character which doesn't fit is left out whole
+    assert_eq!(format_truncated(&mut buf, format_args!("{}", "fifteen bytes!.ü")), "fifteen bytes!.");
+    assert_eq!(format_truncated(&mut buf[..0], format_args!("{}", "x")), "");
+}